@@ -3,4 +3,5 @@ static TARGET_PATH: &str = "../user/target/riscv64gc-unknown-none-elf/release/";
 fn main() {
     println!("cargo:rerun-if-changed=../user/src/");
     println!("cargo:rerun-if-changed={}", TARGET_PATH);
+    println!("cargo:rerun-if-env-changed=SCHED");
 }
@@ -11,6 +11,29 @@ pub const KERNEL_HEAP_SIZE: usize = 0x20_0000;
 pub const MEMORY_END: usize = 0x80800000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
+/// How long a task may run before the timer preempts it.
+pub const TIME_SLICE_MS: usize = 10;
+/// `rr`, `stride` or `priority`, set with `make run SCHED=...`.
+pub const SCHED_POLICY: &str = match option_env!("SCHED") {
+    Some(policy) => policy,
+    None => "rr",
+};
+
+/// User stacks of all threads are placed upwards from here, the heap grows
+/// from the end of the ELF towards it.
+pub const USER_STACK_BASE: usize = 0x10_0000_0000;
+/// Where `mmap` starts looking for room when no address is given.
+pub const MMAP_BASE: usize = 0x20_0000_0000;
+/// End of the lower half of Sv39, user addresses stay below it.
+pub const USER_SPACE_END: usize = 0x40_0000_0000;
+/// Longest string, with its `\0`, the kernel takes from user space.
+pub const PATH_MAX: usize = 4096;
+/// Frames one tmpfs may fill with file data, so that /tmp cannot starve
+/// the processes of memory.
+pub const TMPFS_MAX_PAGES: usize = 256;
+/// Sectors the FAT block cache keeps before it evicts the least recently
+/// used one.
+pub const BLOCK_CACHE_SIZE: usize = 64;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
@@ -29,6 +29,14 @@ pub fn print(args: fmt::Arguments) {
     // STDOUT.exclusive_access().write_fmt(args).unwrap()
 }
 
+/// Write raw bytes, which need not be UTF-8, between whole `print!`s.
+pub fn write_bytes(bytes: &[u8]) {
+    let stdout = STDOUT.lock();
+    for &byte in bytes {
+        stdout.0.putchar(byte);
+    }
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
@@ -1,3 +1,4 @@
+mod ramdisk;
 mod sdcard;
 mod sdcard_d1;
 mod virtio_blk;
@@ -5,6 +6,7 @@ mod virtio_blk;
 use super::BlockDevice;
 use alloc::sync::Arc;
 use lazy_static::*;
+pub use ramdisk::RamDisk;
 
 #[cfg(feature = "board_qemu")]
 type BlockDeviceImpl = virtio_blk::VirtIOBlock;
@@ -17,6 +19,8 @@ type BlockDeviceImpl = sdcard_d1::SDCardWrapper;
 
 lazy_static! {
     pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+    /// 1 MiB, easy-fs keeps half of it for a table of 4096 inodes.
+    pub static ref RAM_DISK: Arc<RamDisk> = Arc::new(RamDisk::new(2048));
 }
 
 #[allow(unused)]
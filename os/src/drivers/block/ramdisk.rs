@@ -0,0 +1,83 @@
+use super::BlockDevice;
+use crate::config::PAGE_SIZE;
+use crate::mm::{frame_alloc, FrameTracker};
+use crate::sync::SpinMutex;
+use alloc::collections::BTreeMap;
+
+const BLOCK_SIZE: usize = 512;
+
+/// A block device in kernel memory, for filesystems other than the one on
+/// `BLOCK_DEVICE`. Its contents are gone at shutdown. A page gets a frame
+/// only once something other than zeros is written to it, so formatting
+/// the disk costs little.
+pub struct RamDisk {
+    size: usize,
+    pages: SpinMutex<BTreeMap<usize, FrameTracker>>,
+}
+
+impl RamDisk {
+    pub fn new(blocks: usize) -> Self {
+        Self {
+            size: blocks * BLOCK_SIZE,
+            pages: SpinMutex::new(BTreeMap::new()),
+        }
+    }
+    pub fn blocks(&self) -> usize {
+        self.size / BLOCK_SIZE
+    }
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    /// Copy out what lies at `offset`, short at the end of the disk.
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let len = self.size.saturating_sub(offset).min(buf.len());
+        let pages = self.pages.lock();
+        let mut done = 0;
+        while done < len {
+            let start = (offset + done) % PAGE_SIZE;
+            let chunk = &mut buf[done..len.min(done + PAGE_SIZE - start)];
+            match pages.get(&((offset + done) / PAGE_SIZE)) {
+                Some(frame) => {
+                    chunk.copy_from_slice(&frame.ppn.get_bytes_array()[start..start + chunk.len()])
+                }
+                None => chunk.fill(0),
+            }
+            done += chunk.len();
+        }
+        len
+    }
+    /// Short at the end of the disk or when memory runs out.
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let len = self.size.saturating_sub(offset).min(buf.len());
+        let mut pages = self.pages.lock();
+        let mut done = 0;
+        while done < len {
+            let start = (offset + done) % PAGE_SIZE;
+            let chunk = &buf[done..len.min(done + PAGE_SIZE - start)];
+            let page = (offset + done) / PAGE_SIZE;
+            if !pages.contains_key(&page) {
+                // a missing page reads as zeros already
+                if chunk.iter().all(|&byte| byte == 0) {
+                    done += chunk.len();
+                    continue;
+                }
+                match frame_alloc() {
+                    Some(frame) => pages.insert(page, frame),
+                    None => break,
+                };
+            }
+            pages[&page].ppn.get_bytes_array()[start..start + chunk.len()].copy_from_slice(chunk);
+            done += chunk.len();
+        }
+        done
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.read_at(block_id * BLOCK_SIZE, buf);
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.write_at(block_id * BLOCK_SIZE, buf);
+    }
+}
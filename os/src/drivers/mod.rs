@@ -2,9 +2,10 @@ mod block;
 mod uart;
 mod plic;
 use core::any::Any;
+use crate::sync::WaitQueue;
 use k210_pac::Interrupt;
 
-pub use block::BLOCK_DEVICE;
+pub use block::{RamDisk, BLOCK_DEVICE, RAM_DISK};
 pub use plic::PLIC_DRIVE;
 pub use uart::UART_DEVICE;
 pub trait BlockDevice: Send + Sync + Any {
@@ -18,6 +19,14 @@ pub trait UartDevice: Send + Sync + Any {
     fn getchar(&self) -> Option<u8>;
     fn putchar(&self, ch: u8);
     fn handler_interrupt(&self);
+    /// Whether Ctrl-C arrived since the last call.
+    fn take_interrupt(&self) -> bool {
+        false
+    }
+    /// Bytes received and not read yet.
+    fn pending(&self) -> usize;
+    /// Woken whenever a byte arrives.
+    fn waiters(&self) -> &WaitQueue;
 }
 
 pub trait PlicDevice: Send + Sync + Any {
@@ -1,7 +1,10 @@
-use crate::sync::UPSafeCell;
+use crate::sync::{SpinMutex, WaitQueue};
 
 use super::UartDevice;
 use alloc::{collections::VecDeque, sync::Arc};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+const CTRL_C: u8 = 0x03;
 
 lazy_static::lazy_static! {
     pub static ref UART_DEVICE: Arc<dyn UartDevice> = Arc::new(UartHs::new());
@@ -9,20 +12,24 @@ lazy_static::lazy_static! {
 
 pub struct UartHs {
     #[allow(unused)]
-    buffer: UPSafeCell<VecDeque<u8>>,
+    buffer: SpinMutex<VecDeque<u8>>,
+    interrupted: AtomicBool,
+    waiters: WaitQueue,
 }
 
 impl UartHs {
     pub fn new() -> Self {
         Self {
-            buffer: unsafe { UPSafeCell::new(VecDeque::new()) },
+            buffer: SpinMutex::new(VecDeque::new()),
+            interrupted: AtomicBool::new(false),
+            waiters: WaitQueue::new(),
         }
     }
 }
 
 impl UartDevice for UartHs {
     fn getchar(&self) -> Option<u8> {
-        self.buffer.exclusive_access().pop_front()
+        self.buffer.lock().pop_front()
     }
 
     fn putchar(&self, ch: u8) {
@@ -43,9 +50,27 @@ impl UartDevice for UartHs {
                 true => panic!("Not char"),
                 false => {
                     let ch = recv.data().bits();
-                    self.buffer.exclusive_access().push_back(ch);
+                    if ch == CTRL_C {
+                        // readers pass the interrupt on as they wake up
+                        self.interrupted.store(true, Ordering::Release);
+                    } else {
+                        self.buffer.lock().push_back(ch);
+                    }
+                    self.waiters.wake_all();
                 }
             }
         }
     }
+
+    fn take_interrupt(&self) -> bool {
+        self.interrupted.swap(false, Ordering::AcqRel)
+    }
+
+    fn pending(&self) -> usize {
+        self.buffer.lock().len()
+    }
+
+    fn waiters(&self) -> &WaitQueue {
+        &self.waiters
+    }
 }
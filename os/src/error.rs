@@ -0,0 +1,57 @@
+/// Errors of the kernel, numbered as the Linux errno values. A syscall
+/// returns `-errno` for them.
+#[repr(isize)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SysError {
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EINTR = 4,
+    EIO = 5,
+    ENXIO = 6,
+    E2BIG = 7,
+    ENOEXEC = 8,
+    EBADF = 9,
+    ECHILD = 10,
+    EAGAIN = 11,
+    ENOMEM = 12,
+    EACCES = 13,
+    EFAULT = 14,
+    EBUSY = 16,
+    EEXIST = 17,
+    ENODEV = 19,
+    ENOTDIR = 20,
+    EISDIR = 21,
+    EINVAL = 22,
+    EMFILE = 24,
+    ENOTTY = 25,
+    EFBIG = 27,
+    ENOSPC = 28,
+    ESPIPE = 29,
+    EROFS = 30,
+    EPIPE = 32,
+    ERANGE = 34,
+    EDEADLK = 35,
+    ENAMETOOLONG = 36,
+    ENOSYS = 38,
+    ENOTEMPTY = 39,
+    ENOTSOCK = 88,
+    EMSGSIZE = 90,
+    EPROTOTYPE = 91,
+    EOPNOTSUPP = 95,
+    EAFNOSUPPORT = 97,
+    EADDRINUSE = 98,
+    EISCONN = 106,
+    ENOTCONN = 107,
+    ETIMEDOUT = 110,
+    ECONNREFUSED = 111,
+}
+
+impl SysError {
+    /// The value handed back to user space in `a0`.
+    pub fn errno(self) -> isize {
+        -(self as isize)
+    }
+}
+
+pub type SysResult<T = isize> = Result<T, SysError>;
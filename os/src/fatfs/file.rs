@@ -116,6 +116,9 @@ impl Seek for FileEntry {
 
 impl Read for FileEntry {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.pos >= self.size() {
+            return Ok(0);
+        }
         let mut disk = self.disk.inner.borrow_mut();
         let off = self.size() - self.pos;
         disk.seek(SeekFrom::Start(
@@ -264,7 +267,7 @@ impl Inode {
         }
     }
 
-    pub fn write(&mut self, offset: usize, buf: &mut [u8]) -> usize {
+    pub fn write(&mut self, offset: usize, buf: &[u8]) -> usize {
         match self {
             Inode::File(file) => {
                 file.seek(SeekFrom::Start(offset as u64)).unwrap();
@@ -274,6 +277,20 @@ impl Inode {
         }
     }
 
+    /// Empty a file. Its clusters stay allocated and are written over.
+    pub fn truncate(&mut self) -> bool {
+        match self {
+            Inode::File(file) => {
+                file.pos = 0;
+                file.current_cluster = None;
+                file.entry.set_size(0);
+                file.entry.flush();
+                true
+            }
+            Inode::Dir(_) => false,
+        }
+    }
+
     pub fn read_all(&mut self, offset: usize) -> Vec<u8> {
         match self {
             Inode::File(file) => {
@@ -1,16 +1,17 @@
 use super::io::{IoBase, Read, Seek, SeekFrom, Write};
+use crate::config::BLOCK_CACHE_SIZE;
 use crate::drivers::BlockDevice;
 use crate::drivers::BLOCK_DEVICE;
-use crate::sync::UPSafeCell;
+use crate::sync::{SpinMutex, UPSafeCell};
 use alloc::collections::BTreeMap;
-use alloc::{collections::VecDeque, sync::Arc};
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
 use core::cmp::{self, min};
 use core::convert::TryFrom;
 use k210_pac::dmac::id;
 use k210_pac::gpiohs::fall_ie;
 
 lazy_static::lazy_static!(
-    pub static ref BLK_MANAGER: Arc<UPSafeCell<BlkManager>> = Arc::new(unsafe{ UPSafeCell::new(BlkManager::new()) });
+    pub static ref BLK_MANAGER: SpinMutex<BlkManager> = SpinMutex::new(BlkManager::new());
 );
 
 #[derive(Debug)]
@@ -45,13 +46,17 @@ impl Write for BlockCache {
         for i in 0..min {
             self.cache[self.pos + i] = buf[i];
         }
-        BLOCK_DEVICE.write_block(self.block_id, &mut self.cache);
+        self.dirty = true;
         self.pos += min;
         Ok(min)
     }
 
     fn flush(&mut self) -> Result<(), Self::Error> {
-        todo!()
+        if self.dirty {
+            BLOCK_DEVICE.write_block(self.block_id, &self.cache);
+            self.dirty = false;
+        }
+        Ok(())
     }
 }
 
@@ -102,8 +107,7 @@ impl Read for BlkCacheManager {
             let offset = self.pos % 512;
             let blk_id = self.pos / 512;
             let n = BLK_MANAGER
-                .inner
-                .borrow_mut()
+                .lock()
                 .read_block(blk_id, &mut buf, &|blk, buf| {
                     let len = cmp::min(buf.len(), 512 - offset);
                     for idx in 0..len {
@@ -126,8 +130,7 @@ impl Write for BlkCacheManager {
             let offset = self.pos % 512;
             let blk_id = self.pos / 512;
             let n = BLK_MANAGER
-                .inner
-                .borrow_mut()
+                .lock()
                 .write_block(blk_id, &mut buf, &|blk, buf| {
                     let len = cmp::min(buf.len(), 512 - offset);
                     for idx in 0..len {
@@ -142,11 +145,13 @@ impl Write for BlkCacheManager {
         Ok(self.pos - start_pos)
     }
 
+    /// Write back the dirty blocks of the range this manager was made for.
     fn flush(&mut self) -> Result<(), Self::Error> {
         let start = self.start / 512;
-        let end = (self.start + self.size) / 512;
+        let end = (self.start + self.size + 511) / 512;
+        let mut manager = BLK_MANAGER.lock();
         for blk in start..end {
-            BLK_MANAGER.exclusive_access().sync_block(blk);
+            manager.sync_block(blk);
         }
         Ok(())
     }
@@ -164,9 +169,26 @@ impl Seek for BlkCacheManager {
     }
 }
 
+/// Counters of the block cache since boot.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// Dirty blocks written to the device, on eviction or sync.
+    pub writebacks: usize,
+    pub cached: usize,
+    pub dirty: usize,
+}
+
+/// A write-back cache of at most `BLOCK_CACHE_SIZE` blocks. Writes only
+/// touch the cached copy; a dirty block reaches the device when it is
+/// evicted as the least recently used one, or when it is synced.
 pub struct BlkManager {
     driver: Arc<dyn BlockDevice>,
     blocks: BTreeMap<usize, BlockCache>,
+    /// Ids of the cached blocks, the least recently used first.
+    lru: VecDeque<usize>,
+    stats: CacheStats,
 }
 
 impl BlkManager {
@@ -174,9 +196,11 @@ impl BlkManager {
         Self {
             driver: BLOCK_DEVICE.clone(),
             blocks: BTreeMap::new(),
+            lru: VecDeque::new(),
+            stats: CacheStats::default(),
         }
     }
-    pub fn read_block_from_disk(&mut self, blk_id: usize) {
+    fn read_block_from_disk(&mut self, blk_id: usize) {
         let mut buf = [0; 512];
         self.driver.read_block(blk_id, &mut buf);
         let blk = BlockCache {
@@ -187,15 +211,58 @@ impl BlkManager {
         };
         self.blocks.insert(blk_id, blk);
     }
-    pub fn write_block_to_disk(&mut self, blk_id: usize) {
-        let mut blk = self.blocks.get_mut(&blk_id).unwrap();
-        self.driver.write_block(blk_id, &mut blk.cache);
+    fn write_block_to_disk(&mut self, blk_id: usize) {
+        if let Some(blk) = self.blocks.get_mut(&blk_id) {
+            if blk.dirty {
+                self.driver.write_block(blk_id, &blk.cache);
+                blk.dirty = false;
+                self.stats.writebacks += 1;
+            }
+        }
     }
 
+    /// Make `blk_id` cached and the most recently used block.
+    fn load(&mut self, blk_id: usize) -> &mut BlockCache {
+        if self.blocks.contains_key(&blk_id) {
+            self.stats.hits += 1;
+            let idx = self.lru.iter().position(|&id| id == blk_id).unwrap();
+            self.lru.remove(idx);
+        } else {
+            self.stats.misses += 1;
+            if self.lru.len() >= BLOCK_CACHE_SIZE {
+                let victim = self.lru.pop_front().unwrap();
+                self.write_block_to_disk(victim);
+                self.blocks.remove(&victim);
+            }
+            self.read_block_from_disk(blk_id);
+        }
+        self.lru.push_back(blk_id);
+        self.blocks.get_mut(&blk_id).unwrap()
+    }
+
+    /// Write `blk` back if it is dirty, it stays cached.
     pub fn sync_block(&mut self, blk: usize) {
-        match self.blocks.remove(&blk) {
-            Some(_) => {}
-            None => {}
+        self.write_block_to_disk(blk);
+    }
+
+    /// Write back every dirty block.
+    pub fn sync_all(&mut self) {
+        let dirty: Vec<usize> = self
+            .blocks
+            .values()
+            .filter(|blk| blk.dirty)
+            .map(|blk| blk.block_id)
+            .collect();
+        for blk_id in dirty {
+            self.write_block_to_disk(blk_id);
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            cached: self.blocks.len(),
+            dirty: self.blocks.values().filter(|blk| blk.dirty).count(),
+            ..self.stats
         }
     }
 
@@ -205,14 +272,8 @@ impl BlkManager {
         buf: &mut [u8],
         func: &dyn Fn(&BlockCache, &mut [u8]) -> usize,
     ) -> usize {
-        let opt = self.blocks.get(&blk_id);
-        let blk = if let Some(blk) = opt {
-            blk
-        } else {
-            self.read_block_from_disk(blk_id);
-            self.blocks.get(&blk_id).unwrap()
-        };
-        func.call_once((blk, buf))
+        let blk = self.load(blk_id);
+        func.call_once((&*blk, buf))
     }
     pub fn write_block(
         &mut self,
@@ -220,15 +281,9 @@ impl BlkManager {
         buf: &[u8],
         func: &dyn Fn(&mut BlockCache, &[u8]) -> usize,
     ) -> usize {
-        let opt = self.blocks.get_mut(&blk_id);
-        let mut blk = if let Some(blk) = opt {
-            blk
-        } else {
-            self.read_block_from_disk(blk_id);
-            self.blocks.get_mut(&blk_id).unwrap()
-        };
-        let len = func.call_once((&mut blk, buf));
-        self.write_block_to_disk(blk_id);
+        let blk = self.load(blk_id);
+        let len = func.call_once((&mut *blk, buf));
+        blk.dirty = true;
         len
     }
 }
@@ -0,0 +1,126 @@
+use super::mount::mounted_at;
+use super::vfs::{Inode, InodeType};
+use crate::sync::SpinMutex;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+/// A name in the directory tree, caching the inodes which were looked up
+/// below it. The root of a mounted filesystem takes the name and parent of
+/// its mount point, so that ".." and `path` work across filesystems.
+pub struct Dentry {
+    name: String,
+    parent: Option<Weak<Dentry>>,
+    inode: Arc<dyn Inode>,
+    children: SpinMutex<BTreeMap<String, Arc<Dentry>>>,
+}
+
+impl Dentry {
+    pub fn new(name: &str, parent: Option<Weak<Dentry>>, inode: Arc<dyn Inode>) -> Arc<Self> {
+        Arc::new(Self {
+            name: String::from(name),
+            parent,
+            inode,
+            children: SpinMutex::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn inode(&self) -> &Arc<dyn Inode> {
+        &self.inode
+    }
+
+    pub fn parent(&self) -> Option<Arc<Dentry>> {
+        self.parent.as_ref().and_then(|parent| parent.upgrade())
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.inode.inode_type() == InodeType::Dir
+    }
+
+    /// Absolute path from the root of the tree.
+    pub fn path(&self) -> String {
+        let mut names = Vec::new();
+        if self.parent.is_some() {
+            names.push(String::clone(&self.name));
+        }
+        let mut dentry = self.parent();
+        while let Some(current) = dentry {
+            if current.parent.is_some() {
+                names.push(String::clone(&current.name));
+            }
+            dentry = current.parent();
+        }
+        let mut path = String::new();
+        for name in names.iter().rev() {
+            path.push('/');
+            path.push_str(name);
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        path
+    }
+
+    fn child_path(&self, name: &str) -> String {
+        let mut path = self.path();
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+        path.push_str(name);
+        path
+    }
+
+    /// Find the entry `name` of this directory, a filesystem mounted there
+    /// hides the entry itself.
+    pub fn lookup(self: &Arc<Self>, name: &str) -> Option<Arc<Dentry>> {
+        match name {
+            "" | "." => return Some(Arc::clone(self)),
+            ".." => return Some(self.parent().unwrap_or_else(|| Arc::clone(self))),
+            _ => {}
+        }
+        if !self.is_dir() {
+            return None;
+        }
+        if let Some(root) = mounted_at(&self.child_path(name)) {
+            return Some(root);
+        }
+        if let Some(child) = self.children.lock().get(name) {
+            return Some(Arc::clone(child));
+        }
+        // no lock is held across the filesystem, which may sleep on the disk
+        let inode = self.inode.lookup(name)?;
+        let child = Dentry::new(name, Some(Arc::downgrade(self)), inode);
+        if !self.inode.cache_entries() {
+            return Some(child);
+        }
+        let mut children = self.children.lock();
+        Some(Arc::clone(
+            children.entry(String::from(name)).or_insert(child),
+        ))
+    }
+
+    pub fn create(self: &Arc<Self>, name: &str, inode_type: InodeType) -> Option<Arc<Dentry>> {
+        if !self.is_dir() {
+            return None;
+        }
+        let inode = self.inode.create(name, inode_type)?;
+        let child = Dentry::new(name, Some(Arc::downgrade(self)), inode);
+        self.children
+            .lock()
+            .insert(String::from(name), Arc::clone(&child));
+        Some(child)
+    }
+
+    pub fn unlink(&self, name: &str) -> bool {
+        if !self.inode.unlink(name) {
+            return false;
+        }
+        self.children.lock().remove(name);
+        true
+    }
+}
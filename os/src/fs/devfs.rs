@@ -0,0 +1,464 @@
+use super::dentry::Dentry;
+use super::vfs::{FileSystem, Inode, InodeType};
+use super::{File, Kstat, OpenFlags, PollEvents};
+use crate::console::write_bytes;
+use crate::drivers::{RAM_DISK, UART_DEVICE};
+use crate::error::{SysError, SysResult};
+use crate::fatfs::io::SeekFrom;
+use crate::fatfs::sdcard::BLK_MANAGER;
+use crate::fatfs::FATFS;
+use crate::mm::UserBuffer;
+use crate::sync::{SpinMutex, WaitQueue};
+use crate::task::{
+    block_current_interruptible, cancel_current_blocked, current_task, mark_current_blocked,
+};
+use crate::timer::get_time;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// A device behind a node of /dev. Opening the node gives a `DeviceFile`
+/// which calls the device directly, without holding any lock across it.
+pub trait Device: Send + Sync {
+    fn inode_type(&self) -> InodeType {
+        InodeType::CharDevice
+    }
+    /// Linux major and minor numbers for `st_rdev`.
+    fn rdev(&self) -> (u64, u64);
+    /// Terminals have no offset to move.
+    fn seekable(&self) -> bool {
+        true
+    }
+    /// Bytes the device holds, where SEEK_END goes. Only block devices
+    /// have any.
+    fn size(&self) -> usize {
+        0
+    }
+    /// Only block devices care about `offset`.
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize;
+    /// Like `read_at`, but for `read`, which may wait for input. A signal
+    /// cuts the wait short with EINTR.
+    fn read(&self, offset: usize, buf: &mut [u8]) -> SysResult<usize> {
+        Ok(self.read_at(offset, buf))
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize;
+    /// Most devices never make a reader or writer wait.
+    fn poll(&self) -> PollEvents {
+        PollEvents::IN | PollEvents::OUT
+    }
+    fn wait_queue(&self) -> Option<&WaitQueue> {
+        None
+    }
+}
+
+/// Reads nothing and swallows everything.
+struct Null;
+
+impl Device for Null {
+    fn rdev(&self) -> (u64, u64) {
+        (1, 3)
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        buf.len()
+    }
+}
+
+/// Reads zeros and swallows everything.
+struct Zero;
+
+impl Device for Zero {
+    fn rdev(&self) -> (u64, u64) {
+        (1, 5)
+    }
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        buf.fill(0);
+        buf.len()
+    }
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        buf.len()
+    }
+}
+
+/// The console on `UART_DEVICE`.
+struct Tty;
+
+impl Device for Tty {
+    fn rdev(&self) -> (u64, u64) {
+        (5, 0)
+    }
+    fn seekable(&self) -> bool {
+        false
+    }
+    /// Take what has already arrived.
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        while len < buf.len() {
+            match UART_DEVICE.getchar() {
+                Some(ch) => buf[len] = ch,
+                None => break,
+            }
+            len += 1;
+        }
+        len
+    }
+    /// Wait for the first byte, then take what has already arrived.
+    fn read(&self, offset: usize, buf: &mut [u8]) -> SysResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let task = current_task().unwrap();
+        let waiters = UART_DEVICE.waiters();
+        let result = loop {
+            mark_current_blocked();
+            waiters.register(&task);
+            if UART_DEVICE.pending() > 0 {
+                cancel_current_blocked();
+                break Ok(());
+            }
+            if let Err(err) = block_current_interruptible() {
+                break Err(err);
+            }
+        };
+        waiters.unregister(&task);
+        result?;
+        Ok(self.read_at(offset, buf))
+    }
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        write_bytes(buf);
+        buf.len()
+    }
+    fn poll(&self) -> PollEvents {
+        let mut events = PollEvents::OUT;
+        events.set(PollEvents::IN, UART_DEVICE.pending() > 0);
+        events
+    }
+    fn wait_queue(&self) -> Option<&WaitQueue> {
+        Some(UART_DEVICE.waiters())
+    }
+}
+
+lazy_static! {
+    /// State of the xorshift64* generator, seeded on first use.
+    static ref RANDOM_STATE: SpinMutex<u64> = SpinMutex::new(get_time() as u64 | 1);
+}
+
+/// Pseudo-random bytes, not fit for cryptography.
+struct Random;
+
+impl Device for Random {
+    fn rdev(&self) -> (u64, u64) {
+        (1, 8)
+    }
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        let mut state = RANDOM_STATE.lock();
+        for chunk in buf.chunks_mut(8) {
+            *state ^= *state >> 12;
+            *state ^= *state << 25;
+            *state ^= *state >> 27;
+            let value = state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+            chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+        }
+        buf.len()
+    }
+    /// Writing mixes the data into the state.
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        let mut state = RANDOM_STATE.lock();
+        for &byte in buf {
+            *state = (*state ^ byte as u64).rotate_left(8) | 1;
+        }
+        buf.len()
+    }
+}
+
+const SECTOR_SIZE: usize = 512;
+
+/// Raw bytes of the root block device. It goes through the block cache of
+/// the FAT volume, so that both see the same data.
+struct RawBlock;
+
+impl Device for RawBlock {
+    fn inode_type(&self) -> InodeType {
+        InodeType::BlockDevice
+    }
+    fn rdev(&self) -> (u64, u64) {
+        (8, 0)
+    }
+    /// The whole disk is the FAT volume, so its boot sector tells the size.
+    fn size(&self) -> usize {
+        FATFS.bpb.total_sectors() as usize * FATFS.bpb.bytes_per_sector as usize
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let buf = &mut buf[..self.size().saturating_sub(offset).min(buf.len())];
+        let mut done = 0;
+        while done < buf.len() {
+            let start = (offset + done) % SECTOR_SIZE;
+            let len = BLK_MANAGER.lock().read_block(
+                (offset + done) / SECTOR_SIZE,
+                &mut buf[done..],
+                &|blk, buf| {
+                    let len = (SECTOR_SIZE - start).min(buf.len());
+                    buf[..len].copy_from_slice(&blk.cache[start..start + len]);
+                    len
+                },
+            );
+            done += len;
+        }
+        done
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let buf = &buf[..self.size().saturating_sub(offset).min(buf.len())];
+        let mut done = 0;
+        while done < buf.len() {
+            let start = (offset + done) % SECTOR_SIZE;
+            let len = BLK_MANAGER.lock().write_block(
+                (offset + done) / SECTOR_SIZE,
+                &buf[done..],
+                &|blk, buf| {
+                    let len = (SECTOR_SIZE - start).min(buf.len());
+                    blk.cache[start..start + len].copy_from_slice(&buf[..len]);
+                    len
+                },
+            );
+            done += len;
+        }
+        done
+    }
+}
+
+/// Raw bytes of `RAM_DISK`. easy-fs caches blocks, so what a mounted
+/// volume wrote shows here only after a sync.
+struct RamBlock;
+
+impl Device for RamBlock {
+    fn inode_type(&self) -> InodeType {
+        InodeType::BlockDevice
+    }
+    fn rdev(&self) -> (u64, u64) {
+        (1, 0)
+    }
+    fn size(&self) -> usize {
+        RAM_DISK.size()
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        RAM_DISK.read_at(offset, buf)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        RAM_DISK.write_at(offset, buf)
+    }
+}
+
+struct DevInode(Arc<dyn Device>);
+
+impl Inode for DevInode {
+    fn inode_type(&self) -> InodeType {
+        self.0.inode_type()
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.0.read_at(offset, buf)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.0.write_at(offset, buf)
+    }
+    fn stat(&self, stat: &mut Kstat) {
+        *stat = Kstat::default();
+        stat.st_mode = self.inode_type().mode();
+        stat.st_nlink = 1;
+        let (major, minor) = self.0.rdev();
+        stat.st_rdev = major << 8 | minor;
+        stat.st_size = self.0.size() as isize;
+        stat.st_blksize = SECTOR_SIZE as u32;
+    }
+    fn device(&self) -> Option<Arc<dyn Device>> {
+        Some(Arc::clone(&self.0))
+    }
+}
+
+/// The root of a devfs, a fixed set of device nodes.
+struct DevDir {
+    nodes: Vec<(&'static str, Arc<DevInode>)>,
+}
+
+impl Inode for DevDir {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Dir
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.nodes
+            .iter()
+            .find(|(node_name, _)| *node_name == name)
+            .map(|(_, node)| Arc::clone(node) as Arc<dyn Inode>)
+    }
+    fn list(&self) -> Vec<(String, InodeType)> {
+        self.nodes
+            .iter()
+            .map(|(name, node)| (String::from(*name), node.inode_type()))
+            .collect()
+    }
+}
+
+pub struct DevFs {
+    root: Arc<DevDir>,
+}
+
+impl DevFs {
+    pub fn new() -> Self {
+        let devices: [(&'static str, Arc<dyn Device>); 6] = [
+            ("null", Arc::new(Null)),
+            ("ram0", Arc::new(RamBlock)),
+            ("random", Arc::new(Random)),
+            ("sda", Arc::new(RawBlock)),
+            ("tty", Arc::new(Tty)),
+            ("zero", Arc::new(Zero)),
+        ];
+        let nodes = devices
+            .iter()
+            .map(|(name, device)| (*name, Arc::new(DevInode(Arc::clone(device)))))
+            .collect();
+        Self {
+            root: Arc::new(DevDir { nodes }),
+        }
+    }
+}
+
+impl FileSystem for DevFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::clone(&self.root) as Arc<dyn Inode>
+    }
+    fn fs_type(&self) -> &'static str {
+        "devfs"
+    }
+}
+
+/// An open device node.
+pub struct DeviceFile {
+    readable: bool,
+    writable: bool,
+    dentry: Arc<Dentry>,
+    device: Arc<dyn Device>,
+    offset: SpinMutex<usize>,
+    flags: SpinMutex<OpenFlags>,
+}
+
+impl DeviceFile {
+    pub fn new(
+        readable: bool,
+        writable: bool,
+        dentry: Arc<Dentry>,
+        device: Arc<dyn Device>,
+    ) -> Self {
+        Self {
+            readable,
+            writable,
+            dentry,
+            device,
+            offset: SpinMutex::new(0),
+            flags: SpinMutex::new(OpenFlags::empty()),
+        }
+    }
+}
+
+impl File for DeviceFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+    fn writable(&self) -> bool {
+        self.writable
+    }
+    fn seek(&self, offset: SeekFrom) -> SysResult<usize> {
+        if !self.device.seekable() {
+            return Err(SysError::ESPIPE);
+        }
+        let mut current = self.offset.lock();
+        let new_offset = match offset {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::Current(delta) => *current as isize + delta as isize,
+            SeekFrom::End(delta) => self.device.size() as isize + delta as isize,
+        };
+        if new_offset < 0 {
+            return Err(SysError::EINVAL);
+        }
+        *current = new_offset as usize;
+        Ok(*current)
+    }
+    fn read(&self, mut buf: UserBuffer) -> SysResult<usize> {
+        if self.flags.lock().contains(OpenFlags::NONBLOCK)
+            && !self.device.poll().contains(PollEvents::IN)
+        {
+            return Err(SysError::EAGAIN);
+        }
+        // the device may sleep, so the offset is not held across it
+        let offset = *self.offset.lock();
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let len = match self.device.read(offset + total_read_size, *slice) {
+                Ok(len) => len,
+                Err(err) if total_read_size == 0 => return Err(err),
+                Err(_) => break,
+            };
+            total_read_size += len;
+            if len < slice.len() {
+                break;
+            }
+        }
+        *self.offset.lock() += total_read_size;
+        Ok(total_read_size)
+    }
+    fn write(&self, buf: UserBuffer) -> SysResult<usize> {
+        if self.flags.lock().contains(OpenFlags::NONBLOCK)
+            && !self.device.poll().contains(PollEvents::OUT)
+        {
+            return Err(SysError::EAGAIN);
+        }
+        let offset = *self.offset.lock();
+        let mut total_write_size = 0usize;
+        for slice in buf.buffers.iter() {
+            let len = self.device.write_at(offset + total_write_size, *slice);
+            total_write_size += len;
+            if len < slice.len() {
+                break;
+            }
+        }
+        *self.offset.lock() += total_write_size;
+        Ok(total_write_size)
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.device.read_at(offset, buf)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.device.write_at(offset, buf)
+    }
+    fn kstat(&self, stat: &mut Kstat) {
+        self.dentry.inode().stat(stat)
+    }
+    fn status_flags(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        *self.flags.lock() = flags.status();
+    }
+    fn name(&self) -> String {
+        String::from(self.dentry.name())
+    }
+    fn dentry(&self) -> Option<Arc<Dentry>> {
+        Some(Arc::clone(&self.dentry))
+    }
+    fn poll(&self) -> PollEvents {
+        let mut events = self.device.poll();
+        events.set(
+            PollEvents::IN,
+            self.readable && events.contains(PollEvents::IN),
+        );
+        events.set(
+            PollEvents::OUT,
+            self.writable && events.contains(PollEvents::OUT),
+        );
+        events
+    }
+    fn wait_queue(&self) -> Option<&WaitQueue> {
+        self.device.wait_queue()
+    }
+}
@@ -0,0 +1,152 @@
+use super::vfs::{FileSystem, Inode, InodeType};
+use crate::drivers::BlockDevice;
+use crate::sync::SpinMutex;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::{block_cache_sync_all, EasyFileSystem, BLOCK_SZ};
+use lazy_static::*;
+
+const EFS_MAGIC: u32 = 0x3b800001;
+
+lazy_static! {
+    /// Held across every call into easy-fs. Its own locks leave interrupts
+    /// on, a task preempted while holding one could stall another which
+    /// spins on it with interrupts off.
+    static ref EASY_FS_LOCK: SpinMutex<()> = SpinMutex::new(());
+}
+
+/// Hands the blocks of one of our devices to the easy-fs crate.
+struct EasyBlockDevice(Arc<dyn BlockDevice>);
+
+impl easy_fs::BlockDevice for EasyBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.0.read_block(block_id, buf)
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.0.write_block(block_id, buf)
+    }
+}
+
+/// An easy-fs volume. It has a single directory, the root, holding files.
+pub struct EasyFs {
+    root: Arc<easy_fs::Inode>,
+}
+
+impl EasyFs {
+    /// Make an empty volume of `blocks` blocks on `device`.
+    pub fn format(device: Arc<dyn BlockDevice>, blocks: usize) {
+        let _guard = EASY_FS_LOCK.lock();
+        EasyFileSystem::create(Arc::new(EasyBlockDevice(device)), blocks as u32, 1);
+    }
+    /// Return None if `device` does not hold an easy-fs volume.
+    pub fn open(device: Arc<dyn BlockDevice>) -> Option<Self> {
+        let mut super_block = [0u8; BLOCK_SZ];
+        device.read_block(0, &mut super_block);
+        let magic = u32::from_le_bytes([
+            super_block[0],
+            super_block[1],
+            super_block[2],
+            super_block[3],
+        ]);
+        if magic != EFS_MAGIC {
+            return None;
+        }
+        let _guard = EASY_FS_LOCK.lock();
+        let efs = EasyFileSystem::open(Arc::new(EasyBlockDevice(device)));
+        Some(Self {
+            root: Arc::new(EasyFileSystem::root_inode(&efs)),
+        })
+    }
+}
+
+impl FileSystem for EasyFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(EasyInode {
+            inode: Arc::clone(&self.root),
+            is_dir: true,
+        })
+    }
+    fn fs_type(&self) -> &'static str {
+        "easyfs"
+    }
+    /// The block cache of easy-fs is shared by all of its volumes.
+    fn sync(&self) {
+        let _guard = EASY_FS_LOCK.lock();
+        block_cache_sync_all();
+    }
+}
+
+struct EasyInode {
+    inode: Arc<easy_fs::Inode>,
+    is_dir: bool,
+}
+
+impl Inode for EasyInode {
+    fn inode_type(&self) -> InodeType {
+        if self.is_dir {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        }
+    }
+    fn size(&self) -> usize {
+        let _guard = EASY_FS_LOCK.lock();
+        self.inode.size()
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        if self.is_dir {
+            return 0;
+        }
+        let _guard = EASY_FS_LOCK.lock();
+        self.inode.read_at(offset, buf)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        if self.is_dir {
+            return 0;
+        }
+        let _guard = EASY_FS_LOCK.lock();
+        self.inode.write_at(offset, buf)
+    }
+    fn truncate(&self) -> bool {
+        if self.is_dir {
+            return false;
+        }
+        let _guard = EASY_FS_LOCK.lock();
+        self.inode.clear();
+        true
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if !self.is_dir {
+            return None;
+        }
+        let _guard = EASY_FS_LOCK.lock();
+        let inode = self.inode.find(name)?;
+        Some(Arc::new(EasyInode {
+            inode,
+            is_dir: false,
+        }))
+    }
+    fn create(&self, name: &str, inode_type: InodeType) -> Option<Arc<dyn Inode>> {
+        if !self.is_dir || inode_type != InodeType::File {
+            return None;
+        }
+        let _guard = EASY_FS_LOCK.lock();
+        let inode = self.inode.create(name)?;
+        Some(Arc::new(EasyInode {
+            inode,
+            is_dir: false,
+        }))
+    }
+    fn list(&self) -> Vec<(String, InodeType)> {
+        if !self.is_dir {
+            return Vec::new();
+        }
+        let _guard = EASY_FS_LOCK.lock();
+        self.inode
+            .ls()
+            .into_iter()
+            .map(|name| (name, InodeType::File))
+            .collect()
+    }
+}
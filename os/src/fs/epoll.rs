@@ -0,0 +1,119 @@
+use super::{File, PollEvents};
+use crate::error::{SysError, SysResult};
+use crate::sync::SpinMutex;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+pub const EPOLL_CTL_ADD: usize = 1;
+pub const EPOLL_CTL_DEL: usize = 2;
+pub const EPOLL_CTL_MOD: usize = 3;
+
+/// `struct epoll_event`. The bits of `events` below 0x40 are those of
+/// `PollEvents`; `data` is handed back untouched.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+/// What an epoll instance watches on one fd.
+struct Interest {
+    file: Weak<dyn File>,
+    event: EpollEvent,
+}
+
+/// An epoll instance, level-triggered only. It holds the watched files
+/// weakly: once a file is closed everywhere it drops out of the set.
+pub struct EpollFile {
+    interests: SpinMutex<BTreeMap<usize, Interest>>,
+}
+
+impl EpollFile {
+    pub fn new() -> Self {
+        Self {
+            interests: SpinMutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Add, change or remove the interest in `file`, open as `fd`.
+    pub fn ctl(&self, op: usize, fd: usize, file: &Arc<dyn File>, event: EpollEvent) -> SysResult {
+        let mut interests = self.interests.lock();
+        // an fd closed and opened again is a new file
+        let watched = interests
+            .get(&fd)
+            .map_or(false, |interest| interest.file.upgrade().is_some());
+        match op {
+            EPOLL_CTL_ADD if watched => return Err(SysError::EEXIST),
+            EPOLL_CTL_ADD => {
+                interests.insert(
+                    fd,
+                    Interest {
+                        file: Arc::downgrade(file),
+                        event,
+                    },
+                );
+            }
+            EPOLL_CTL_DEL | EPOLL_CTL_MOD if !watched => return Err(SysError::ENOENT),
+            EPOLL_CTL_DEL => {
+                interests.remove(&fd);
+            }
+            EPOLL_CTL_MOD => interests.get_mut(&fd).unwrap().event = event,
+            _ => return Err(SysError::EINVAL),
+        }
+        Ok(0)
+    }
+
+    /// The files still open, to sleep on their wait queues.
+    pub fn files(&self) -> Vec<Arc<dyn File>> {
+        self.interests
+            .lock()
+            .values()
+            .filter_map(|interest| interest.file.upgrade())
+            .collect()
+    }
+
+    /// Up to `max` events which are ready now. Errors and hangups are
+    /// reported whether asked for or not.
+    pub fn ready(&self, max: usize) -> Vec<EpollEvent> {
+        let files: Vec<_> = self
+            .interests
+            .lock()
+            .values()
+            .filter_map(|interest| Some((interest.file.upgrade()?, interest.event)))
+            .collect();
+        // files are polled without holding the set
+        files
+            .iter()
+            .filter_map(|(file, event)| {
+                let wanted = event.events as u16 | (PollEvents::ERR | PollEvents::HUP).bits();
+                let events = file.poll().bits() & wanted;
+                if events == 0 {
+                    return None;
+                }
+                Some(EpollEvent {
+                    events: events as u32,
+                    data: event.data,
+                })
+            })
+            .take(max)
+            .collect()
+    }
+}
+
+impl File for EpollFile {
+    fn readable(&self) -> bool {
+        false
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn name(&self) -> String {
+        String::from("anon_inode:[eventpoll]")
+    }
+    fn epoll(&self) -> Option<&EpollFile> {
+        Some(self)
+    }
+}
@@ -0,0 +1,137 @@
+use super::vfs::{FileSystem, Inode, InodeType};
+use super::Kstat;
+use crate::fatfs::file::Inode as FatNode;
+use crate::fatfs::io::{Seek, SeekFrom};
+use crate::fatfs::root_dir;
+use crate::fatfs::sdcard::BLK_MANAGER;
+use crate::sync::SpinMutex;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// The FAT volume on `BLOCK_DEVICE`.
+pub struct FatFileSystem;
+
+impl FileSystem for FatFileSystem {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(FatInode::new(root_dir()))
+    }
+    fn fs_type(&self) -> &'static str {
+        "vfat"
+    }
+    fn sync(&self) {
+        BLK_MANAGER.lock().sync_all();
+    }
+}
+
+pub struct FatInode {
+    inner: SpinMutex<FatNode>,
+}
+
+impl FatInode {
+    pub fn new(node: FatNode) -> Self {
+        Self {
+            inner: SpinMutex::new(node),
+        }
+    }
+}
+
+impl Inode for FatInode {
+    fn inode_type(&self) -> InodeType {
+        if self.inner.lock().is_dir() {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        }
+    }
+    fn size(&self) -> usize {
+        match &*self.inner.lock() {
+            FatNode::File(file) => file.size() as usize,
+            FatNode::Dir(_) => 0,
+        }
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let mut node = self.inner.lock();
+        let mut total_read_size = 0usize;
+        while total_read_size < buf.len() {
+            let len = node.read(offset + total_read_size, &mut buf[total_read_size..]);
+            if len == 0 {
+                break;
+            }
+            total_read_size += len;
+        }
+        total_read_size
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut node = self.inner.lock();
+        let mut total_write_size = 0usize;
+        while total_write_size < buf.len() {
+            let len = node.write(offset + total_write_size, &buf[total_write_size..]);
+            if len == 0 {
+                break;
+            }
+            total_write_size += len;
+        }
+        total_write_size
+    }
+    fn truncate(&self) -> bool {
+        self.inner.lock().truncate()
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        let entry = match &mut *self.inner.lock() {
+            FatNode::Dir(dir) => dir.find_entry(name, None).ok()?,
+            FatNode::File(_) => return None,
+        };
+        let node = if entry.is_dir() {
+            FatNode::Dir(entry)
+        } else {
+            FatNode::File(entry.to_file())
+        };
+        Some(Arc::new(FatInode::new(node)))
+    }
+    fn create(&self, name: &str, inode_type: InodeType) -> Option<Arc<dyn Inode>> {
+        let is_dir = match inode_type {
+            InodeType::File => false,
+            InodeType::Dir => true,
+            _ => return None,
+        };
+        let node = self.inner.lock().create(name, is_dir)?;
+        Some(Arc::new(FatInode::new(node)))
+    }
+    fn unlink(&self, name: &str) -> bool {
+        self.inner.lock().remove(name)
+    }
+    fn list(&self) -> Vec<(String, InodeType)> {
+        let mut node = self.inner.lock();
+        let dir = match &mut *node {
+            FatNode::Dir(dir) => dir,
+            FatNode::File(_) => return Vec::new(),
+        };
+        dir.seek(SeekFrom::Start(0)).unwrap();
+        dir.by_ref()
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                if name == "." || name == ".." {
+                    return None;
+                }
+                let inode_type = if entry.is_dir() {
+                    InodeType::Dir
+                } else {
+                    InodeType::File
+                };
+                Some((name, inode_type))
+            })
+            .collect()
+    }
+    fn stat(&self, stat: &mut Kstat) {
+        let inode_type = self.inode_type();
+        let mut node = self.inner.lock();
+        *stat = Kstat::default();
+        // FAT only knows the times of regular files
+        node.stat(stat);
+        stat.st_mode = inode_type.mode();
+        stat.st_nlink = 1;
+        stat.st_blksize = 512;
+        stat.st_blocks = ((stat.st_size as u64) + 511) / 512;
+    }
+}
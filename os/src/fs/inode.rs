@@ -1,36 +1,48 @@
-use crate::fatfs::file::Inode;
+use super::dentry::Dentry;
+use crate::error::{SysError, SysResult};
 use crate::fatfs::io::SeekFrom;
-use crate::fatfs::root_dir;
 use crate::fs::File;
 use crate::mm::UserBuffer;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinMutex;
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 
+/// An open file or directory of the VFS.
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPSafeCell<OSInodeInner>,
+    dentry: Arc<Dentry>,
+    inner: SpinMutex<OSInodeInner>,
 }
 pub struct OSInodeInner {
+    /// Byte offset of a file, or index of the next entry of a directory.
     offset: usize,
-    inode: Arc<UPSafeCell<Inode>>,
+    /// O_APPEND and O_NONBLOCK.
+    flags: OpenFlags,
 }
 
 impl OSInode {
-    pub fn new(readable: bool, writable: bool, inode: Arc<UPSafeCell<Inode>>) -> Self {
+    pub fn new(readable: bool, writable: bool, dentry: Arc<Dentry>) -> Self {
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            dentry,
+            inner: SpinMutex::new(OSInodeInner {
+                offset: 0,
+                flags: OpenFlags::empty(),
+            }),
         }
     }
 
     pub fn read_all(&self) -> Vec<u8> {
-        let inner = self.inner.exclusive_access();
-        let offset = inner.offset;
-        let v = inner.inode.inner.borrow_mut().read_all(offset);
+        let mut inner = self.inner.lock();
+        let inode = self.dentry.inode();
+        let mut v = vec![0u8; inode.size().saturating_sub(inner.offset)];
+        let len = inode.read_at(inner.offset, &mut v);
+        v.truncate(len);
+        inner.offset += len;
         v
     }
 }
@@ -41,56 +53,36 @@ bitflags! {
         const WRONLY = 0x001;
         const RDWR = 0x002;
         const CREATE = 0x40;
-        const TRUNC = 1 << 10;
+        const EXCL = 0x80;
+        const TRUNC = 0x200;
+        const APPEND = 0x400;
+        const NONBLOCK = 0x800;
         const DIRECTORY = 0x0200000;
         const DIR = 0x040000;
         const FILE = 0x100000;
+        const CLOEXEC = 0x80000;
     }
 }
 
 impl OpenFlags {
-    /// Do not check validity for simplicity
-    /// Return (readable, writable)
+    /// Return (readable, writable) from the access mode bits
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
-            (true, false)
-        } else if self.contains(Self::WRONLY) {
-            (false, true)
-        } else {
-            (true, true)
+        match self.bits & 0b11 {
+            0 => (true, false),
+            1 => (false, true),
+            _ => (true, true),
         }
     }
+    /// The flags which stay with the open file and `fcntl` may change.
+    pub fn status(&self) -> OpenFlags {
+        *self & (OpenFlags::APPEND | OpenFlags::NONBLOCK)
+    }
 }
 
-pub fn root() -> Arc<OSInode> {
-    Arc::new(OSInode::new(
-        true,
-        true,
-        Arc::new(unsafe { UPSafeCell::new(root_dir()) }),
-    ))
-}
-
-pub fn open_file(path: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
-    println!("Open: {}", path);
-    let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        let file = root_dir().create(path, false).unwrap();
-        Some(Arc::new(OSInode::new(
-            readable,
-            writable,
-            Arc::new(unsafe { UPSafeCell::new(file) }),
-        )))
-    } else {
-        if let Some(file) = root_dir().open(path, false) {
-            Some(Arc::new(OSInode::new(
-                readable,
-                writable,
-                Arc::new(unsafe { UPSafeCell::new(file) }),
-            )))
-        } else {
-            None
-        }
-    }
+/// Size of a `linux_dirent64` record holding `name`, with the name
+/// starting at byte 19 and the record aligned to 8 bytes.
+fn dirent_size(name: &str) -> usize {
+    (19 + name.len() + 1 + 7) & !7
 }
 
 impl File for OSInode {
@@ -101,101 +93,99 @@ impl File for OSInode {
         self.writable
     }
 
-    fn open(&self, name: &str, read: bool, write: bool, isdir: bool) -> Option<Arc<OSInode>> {
-        let inner = self.inner.exclusive_access();
-        let mut inner = inner.inode.exclusive_access();
-        if let Some(inode) = inner.open(name, isdir) {
-            let os_inode = OSInode::new(read, write, Arc::new(unsafe { UPSafeCell::new(inode) }));
-            Some(Arc::new(os_inode))
-        } else {
-            None
+    fn seek(&self, offset: SeekFrom) -> SysResult<usize> {
+        let mut inner = self.inner.lock();
+        let new_offset = match offset {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::Current(delta) => inner.offset as isize + delta as isize,
+            SeekFrom::End(delta) => self.dentry.inode().size() as isize + delta as isize,
+        };
+        if new_offset < 0 {
+            return Err(SysError::EINVAL);
         }
+        inner.offset = new_offset as usize;
+        Ok(inner.offset)
     }
 
-    fn seek(&self, offset: SeekFrom) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let offset = inner.inode.exclusive_access().seek(offset);
-        inner.offset = offset;
-        offset
-    }
-
-    fn read(&self, mut buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
+    fn read(&self, mut buf: UserBuffer) -> SysResult<usize> {
+        let mut inner = self.inner.lock();
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
-            let offset = inner.offset;
-            let len = inner.inode.exclusive_access().read(offset, *slice);
-            if len == 0 {
-                break;
-            }
+            let len = self.dentry.inode().read_at(inner.offset, *slice);
             inner.offset += len;
             total_read_size += len;
+            if len < slice.len() {
+                break;
+            }
         }
-        total_read_size
+        Ok(total_read_size)
     }
-    fn write(&self, mut buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
-
+    fn write(&self, buf: UserBuffer) -> SysResult<usize> {
+        let mut inner = self.inner.lock();
+        if inner.flags.contains(OpenFlags::APPEND) {
+            inner.offset = self.dentry.inode().size();
+        }
         let mut total_write_size = 0usize;
-        for slice in buf.buffers.iter_mut() {
-            let offset = inner.offset;
-            let len = inner.inode.inner.borrow_mut().write(offset, *slice);
-            if len == 0 {
-                break;
-            }
+        for slice in buf.buffers.iter() {
+            let len = self.dentry.inode().write_at(inner.offset, *slice);
             inner.offset += len;
             total_write_size += len;
+            if len < slice.len() {
+                break;
+            }
         }
-        total_write_size
-    }
-    fn create(&self, name: &str, read: bool, write: bool, isdir: bool) -> Option<Arc<OSInode>> {
-        let inner = self.inner.exclusive_access();
-        let mut inner = inner.inode.exclusive_access();
-        if let Some(inode) = inner.create(name, isdir) {
-            let os_inode = OSInode::new(read, write, Arc::new(unsafe { UPSafeCell::new(inode) }));
-            Some(Arc::new(os_inode))
-        } else {
-            None
-        }
+        Ok(total_write_size)
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.dentry.inode().read_at(offset, buf)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.dentry.inode().write_at(offset, buf)
     }
-
     fn kstat(&self, stat: &mut Kstat) {
-        self.inner
-            .exclusive_access()
-            .inode
-            .inner
-            .borrow_mut()
-            .stat(stat)
+        self.dentry.inode().stat(stat)
     }
-
-    fn remove(&self, path: &str) -> bool {
-        self.inner
-            .exclusive_access()
-            .inode
-            .inner
-            .borrow_mut()
-            .remove(path)
+    fn status_flags(&self) -> OpenFlags {
+        self.inner.lock().flags
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        self.inner.lock().flags = flags.status();
     }
 
     fn name(&self) -> String {
-        self.inner
-            .inner
-            .borrow_mut()
-            .inode
-            .inner
-            .borrow_mut()
-            .file_name()
-    }
-    fn getdents(&self, dirent: &mut Dirent) -> isize {
-        self.inner
-            .exclusive_access()
-            .inode
-            .exclusive_access()
-            .getdents(dirent)
+        String::from(self.dentry.name())
+    }
+    fn dentry(&self) -> Option<Arc<Dentry>> {
+        Some(Arc::clone(&self.dentry))
+    }
+    fn getdents(&self, buf: &mut [u8]) -> isize {
+        if !self.dentry.is_dir() {
+            return -1;
+        }
+        let mut inner = self.inner.lock();
+        let entries = self.dentry.inode().list();
+        let mut written = 0usize;
+        for (name, inode_type) in entries.iter().skip(inner.offset) {
+            let reclen = dirent_size(name);
+            if written + reclen > buf.len() {
+                break;
+            }
+            let record = &mut buf[written..written + reclen];
+            record.fill(0);
+            inner.offset += 1;
+            record[0..8].copy_from_slice(&(inner.offset as u64).to_ne_bytes());
+            record[8..16].copy_from_slice(&(inner.offset as i64).to_ne_bytes());
+            record[16..18].copy_from_slice(&(reclen as u16).to_ne_bytes());
+            record[18] = inode_type.dirent_type();
+            record[19..19 + name.len()].copy_from_slice(name.as_bytes());
+            written += reclen;
+        }
+        written as isize
     }
 }
 
 #[repr(C)]
+#[derive(Default)]
 pub struct Kstat {
     pub st_dev: u64,
     pub sd_ino: u64,
@@ -1,42 +1,115 @@
 #![allow(unused)]
+mod dentry;
+mod devfs;
+mod easyfs;
+mod epoll;
+mod fat;
 mod inode;
+mod mount;
+mod path;
 mod pipe;
-mod stdio;
+mod poll;
+mod procfs;
+mod tmpfs;
+mod vfs;
 
+use crate::drivers::RAM_DISK;
+use crate::error::{SysError, SysResult};
+use crate::net::UnixSocket;
+use crate::sync::WaitQueue;
 use crate::{fatfs::io::SeekFrom, mm::UserBuffer};
 
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
-    fn seek(&self, _offset: SeekFrom) -> usize {
-        0
-    }
-    fn read(&self, buf: UserBuffer) -> usize {
-        0
+    /// Move the file offset and return it. Pipes and the like cannot seek.
+    fn seek(&self, _offset: SeekFrom) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
     }
-    fn write(&self, buf: UserBuffer) -> usize {
-        0
+    fn read(&self, buf: UserBuffer) -> SysResult<usize> {
+        Ok(0)
     }
-    fn create(&self, name: &str, read: bool, write: bool, isdir: bool) -> Option<Arc<OSInode>> {
-        None
+    fn write(&self, buf: UserBuffer) -> SysResult<usize> {
+        Ok(0)
     }
-    fn open(&self, name: &str, read: bool, write: bool, isdir: bool) -> Option<Arc<OSInode>> {
-        None
+    /// Read at `offset` without moving the file offset.
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        0
     }
-    fn remove(&self, path: &str) -> bool {
-        false
+    /// Write at `offset` without moving the file offset.
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
     }
     fn islink(&self) -> bool {
         false
     }
     fn kstat(&self, stat: &mut Kstat) {}
     fn name(&self) -> String;
-    fn getdents(&self, dirent: &mut Dirent) -> isize {
+    /// Where the file sits in the directory tree, if it does.
+    fn dentry(&self) -> Option<Arc<Dentry>> {
+        None
+    }
+    /// Fill `buf` with `linux_dirent64` records, return the bytes used.
+    fn getdents(&self, buf: &mut [u8]) -> isize {
         -1
     }
+    /// What the file is ready for without blocking. Files on disk always are.
+    fn poll(&self) -> PollEvents {
+        let mut events = PollEvents::empty();
+        events.set(PollEvents::IN, self.readable());
+        events.set(PollEvents::OUT, self.writable());
+        events
+    }
+    /// The queue woken whenever `poll` may give something new, None for
+    /// files which are always ready.
+    fn wait_queue(&self) -> Option<&WaitQueue> {
+        None
+    }
+    /// O_APPEND and O_NONBLOCK, the access mode is not kept here.
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::empty()
+    }
+    fn set_status_flags(&self, _flags: OpenFlags) {}
+    /// Downcast to an epoll instance.
+    fn epoll(&self) -> Option<&EpollFile> {
+        None
+    }
+    /// Downcast to a socket.
+    fn socket(&self) -> Option<&UnixSocket> {
+        None
+    }
 }
 
 use alloc::{string::String, sync::Arc};
-pub use inode::{open_file, root, Dirent, Kstat, OSInode, OpenFlags};
-pub use pipe::{make_pipe, Pipe};
-pub use stdio::{Stdin, Stdout};
+pub use dentry::Dentry;
+pub use epoll::{EpollEvent, EpollFile};
+pub use inode::{Dirent, Kstat, OSInode, OpenFlags};
+pub use mount::{
+    filesystem_of, mount, mounts, new_filesystem, root_dentry, sync_all, umount, ROOT_DEVICE,
+};
+pub use path::{lookup, lookup_parent, mkdir, mknod, open_file, unlink, AT_FDCWD};
+pub use pipe::{make_pipe, Fifo, Pipe};
+pub use poll::{wait_ready, PollEvents};
+pub use vfs::{FileSystem, Inode, InodeType};
+
+pub fn init() {
+    let root_fs = new_filesystem("vfat", ROOT_DEVICE).expect("no FAT volume on the root device");
+    mount(ROOT_DEVICE, None, root_fs).expect("cannot mount the root filesystem");
+    // an empty volume for whoever wants to mount it
+    easyfs::EasyFs::format(RAM_DISK.clone(), RAM_DISK.blocks());
+    mount_builtin("devfs", "/dev");
+    mount_builtin("proc", "/proc");
+    mount_builtin("tmpfs", "/tmp");
+}
+
+/// Mount a filesystem without a source device, creating the mount point
+/// on the root filesystem if it is missing.
+fn mount_builtin(fs_type: &str, path: &str) {
+    let root = root_dentry();
+    let target = match lookup(&root, path) {
+        Ok(target) => target,
+        Err(_) => mkdir(&root, path).expect("cannot create a mount point"),
+    };
+    let fs = new_filesystem(fs_type, fs_type).unwrap();
+    mount(fs_type, Some(target), fs).expect("cannot mount a builtin filesystem");
+}
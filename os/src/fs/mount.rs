@@ -0,0 +1,179 @@
+use super::dentry::Dentry;
+use super::devfs::DevFs;
+use super::easyfs::EasyFs;
+use super::fat::FatFileSystem;
+use super::procfs::ProcFs;
+use super::tmpfs::TmpFs;
+use super::vfs::FileSystem;
+use crate::drivers::RAM_DISK;
+use crate::error::{SysError, SysResult};
+use crate::sync::SpinMutex;
+use crate::task::process_list;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Name of `BLOCK_DEVICE` as a mount source.
+pub const ROOT_DEVICE: &str = "/dev/sda";
+/// Name of `RAM_DISK` as a mount source.
+pub const RAM_DEVICE: &str = "/dev/ram0";
+
+pub struct Mount {
+    pub source: String,
+    /// Absolute path of the mount point.
+    pub target: String,
+    pub fs: Arc<dyn FileSystem>,
+    pub root: Arc<Dentry>,
+}
+
+lazy_static! {
+    static ref MOUNT_TABLE: SpinMutex<Vec<Mount>> = SpinMutex::new(Vec::new());
+}
+
+/// Create a filesystem of type `fs_type` on `source`.
+pub fn new_filesystem(fs_type: &str, source: &str) -> Option<Arc<dyn FileSystem>> {
+    match fs_type {
+        "vfat" if source == ROOT_DEVICE => Some(Arc::new(FatFileSystem)),
+        "easyfs" if source == RAM_DEVICE => {
+            let fs = EasyFs::open(RAM_DISK.clone())?;
+            Some(Arc::new(fs))
+        }
+        "devfs" => Some(Arc::new(DevFs::new())),
+        "proc" => Some(Arc::new(ProcFs)),
+        "tmpfs" => Some(Arc::new(TmpFs::new())),
+        _ => None,
+    }
+}
+
+/// Mount `fs` on the directory `target`, or as the root of the tree if
+/// there is none yet. A device can be mounted only once.
+pub fn mount(source: &str, target: Option<Arc<Dentry>>, fs: Arc<dyn FileSystem>) -> SysResult<()> {
+    let (path, root) = match target {
+        Some(target) => {
+            if !target.is_dir() {
+                return Err(SysError::ENOTDIR);
+            }
+            let parent = target.parent().map(|parent| Arc::downgrade(&parent));
+            (target.path(), Dentry::new(target.name(), parent, fs.root()))
+        }
+        None => (String::from("/"), Dentry::new("/", None, fs.root())),
+    };
+    let mut table = MOUNT_TABLE.lock();
+    if table.iter().any(|mount| mount.target == path) {
+        return Err(SysError::EBUSY);
+    }
+    // the FAT volume has a single state, a second mount would alias it
+    if source == ROOT_DEVICE && table.iter().any(|mount| mount.source == source) {
+        return Err(SysError::EBUSY);
+    }
+    table.push(Mount {
+        source: String::from(source),
+        target: path,
+        fs,
+        root,
+    });
+    Ok(())
+}
+
+/// Detach the filesystem mounted on `target`. The root cannot be
+/// unmounted, nor a filesystem with another one mounted below it or with
+/// files open or working directories in it.
+pub fn umount(target: &str) -> SysResult<()> {
+    if target == "/" {
+        return Err(SysError::EBUSY);
+    }
+    // collected first, the mount table must not nest in a process
+    let mut in_use = Vec::new();
+    for process in process_list() {
+        let inner = process.inner_exclusive_access();
+        in_use.push(Arc::clone(&inner.cwd));
+        in_use.extend(
+            inner
+                .fd_table
+                .values()
+                .flatten()
+                .filter_map(|file| file.dentry()),
+        );
+    }
+    let mut table = MOUNT_TABLE.lock();
+    let idx = table
+        .iter()
+        .position(|mount| mount.target == target)
+        .ok_or(SysError::EINVAL)?;
+    let below = format!("{}/", target);
+    if table.iter().any(|mount| mount.target.starts_with(&below)) {
+        return Err(SysError::EBUSY);
+    }
+    let fs = &table[idx].fs;
+    if in_use
+        .iter()
+        .any(|dentry| mounted_fs(&table, dentry).map_or(false, |other| Arc::ptr_eq(&other, fs)))
+    {
+        return Err(SysError::EBUSY);
+    }
+    let mount = table.remove(idx);
+    drop(table);
+    mount.fs.sync();
+    Ok(())
+}
+
+/// Root of the filesystem mounted exactly on `path`.
+pub fn mounted_at(path: &str) -> Option<Arc<Dentry>> {
+    MOUNT_TABLE
+        .lock()
+        .iter()
+        .find(|mount| mount.target == path)
+        .map(|mount| Arc::clone(&mount.root))
+}
+
+/// The filesystem `dentry` belongs to, found by walking up to the root of
+/// its mount.
+pub fn filesystem_of(dentry: &Arc<Dentry>) -> Option<Arc<dyn FileSystem>> {
+    mounted_fs(&MOUNT_TABLE.lock(), dentry)
+}
+
+fn mounted_fs(table: &[Mount], dentry: &Arc<Dentry>) -> Option<Arc<dyn FileSystem>> {
+    let mut current = Arc::clone(dentry);
+    loop {
+        if let Some(mount) = table
+            .iter()
+            .find(|mount| Arc::ptr_eq(&mount.root, &current))
+        {
+            return Some(Arc::clone(&mount.fs));
+        }
+        current = current.parent()?;
+    }
+}
+
+/// Write back the cached data of every mounted filesystem.
+pub fn sync_all() {
+    let filesystems: Vec<_> = MOUNT_TABLE
+        .lock()
+        .iter()
+        .map(|mount| Arc::clone(&mount.fs))
+        .collect();
+    for fs in filesystems {
+        fs.sync();
+    }
+}
+
+pub fn root_dentry() -> Arc<Dentry> {
+    mounted_at("/").expect("no root filesystem")
+}
+
+/// (source, target, filesystem type) of every mount.
+pub fn mounts() -> Vec<(String, String, &'static str)> {
+    MOUNT_TABLE
+        .lock()
+        .iter()
+        .map(|mount| {
+            (
+                String::clone(&mount.source),
+                String::clone(&mount.target),
+                mount.fs.fs_type(),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,132 @@
+use super::dentry::Dentry;
+use super::devfs::DeviceFile;
+use super::inode::{OSInode, OpenFlags};
+use super::mount::{mounted_at, root_dentry};
+use super::vfs::InodeType;
+use super::File;
+use crate::error::{SysError, SysResult};
+use alloc::sync::Arc;
+
+/// The `dirfd` of the `*at` syscalls meaning the working directory.
+pub const AT_FDCWD: isize = -100;
+
+/// Follow the components of `path` from `start`, or from the root if the
+/// path is absolute. Empty components and "." stay in place, ".." goes up.
+pub fn lookup(start: &Arc<Dentry>, path: &str) -> SysResult<Arc<Dentry>> {
+    let mut dentry = if path.starts_with('/') {
+        root_dentry()
+    } else {
+        Arc::clone(start)
+    };
+    for name in path.split('/') {
+        if name.is_empty() || name == "." {
+            continue;
+        }
+        if !dentry.is_dir() {
+            return Err(SysError::ENOTDIR);
+        }
+        dentry = dentry.lookup(name).ok_or(SysError::ENOENT)?;
+    }
+    if path.ends_with('/') && !dentry.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    Ok(dentry)
+}
+
+/// Resolve the directory holding the last component of `path`, return it
+/// with the last name. The name is empty for "/", "." is kept as it is.
+pub fn lookup_parent<'a>(start: &Arc<Dentry>, path: &'a str) -> SysResult<(Arc<Dentry>, &'a str)> {
+    let trimmed = path.trim_end_matches('/');
+    let (dir_path, name) = match trimmed.rfind('/') {
+        Some(idx) => (&path[..idx + 1], &trimmed[idx + 1..]),
+        None => ("", trimmed),
+    };
+    let dir = lookup(start, dir_path)?;
+    if !dir.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    Ok((dir, name))
+}
+
+/// Open `path` relative to `start`, creating a regular file if it is
+/// missing and `flags` asks for it. Device nodes open as their device,
+/// FIFOs as an end of their pipe. Sockets cannot be opened.
+pub fn open_file(start: &Arc<Dentry>, path: &str, flags: OpenFlags) -> SysResult<Arc<dyn File>> {
+    let (readable, writable) = flags.read_write();
+    let dentry = match lookup(start, path) {
+        Ok(_) if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) => {
+            return Err(SysError::EEXIST)
+        }
+        Ok(dentry) => dentry,
+        Err(SysError::ENOENT) if flags.contains(OpenFlags::CREATE) => {
+            let (dir, name) = lookup_parent(start, path)?;
+            dir.create(name, InodeType::File).ok_or(SysError::EPERM)?
+        }
+        Err(err) => return Err(err),
+    };
+    if flags.contains(OpenFlags::DIRECTORY) && !dentry.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    if dentry.is_dir() && writable {
+        return Err(SysError::EISDIR);
+    }
+    let inode = Arc::clone(dentry.inode());
+    if inode.inode_type() == InodeType::Socket {
+        return Err(SysError::ENXIO);
+    }
+    if let Some(fifo) = inode.fifo() {
+        return fifo.open(dentry, flags).map(|end| end as Arc<dyn File>);
+    }
+    let file: Arc<dyn File> = match inode.device() {
+        Some(device) => Arc::new(DeviceFile::new(readable, writable, dentry, device)),
+        None => {
+            if flags.contains(OpenFlags::TRUNC)
+                && writable
+                && inode.inode_type() == InodeType::File
+                && !inode.truncate()
+            {
+                return Err(SysError::EPERM);
+            }
+            Arc::new(OSInode::new(readable, writable, dentry))
+        }
+    };
+    file.set_status_flags(flags);
+    Ok(file)
+}
+
+pub fn mkdir(start: &Arc<Dentry>, path: &str) -> SysResult<Arc<Dentry>> {
+    mknod(start, path, InodeType::Dir)
+}
+
+/// Create a new entry of any type the filesystem can hold at `path`.
+pub fn mknod(start: &Arc<Dentry>, path: &str, inode_type: InodeType) -> SysResult<Arc<Dentry>> {
+    let (dir, name) = lookup_parent(start, path)?;
+    if name.is_empty() || name == "." || name == ".." || dir.lookup(name).is_some() {
+        return Err(SysError::EEXIST);
+    }
+    dir.create(name, inode_type).ok_or(SysError::EPERM)
+}
+
+/// Remove the entry `path`, which has to be a directory if `rmdir` is set
+/// and must not be one otherwise.
+pub fn unlink(start: &Arc<Dentry>, path: &str, rmdir: bool) -> SysResult<()> {
+    let (dir, name) = lookup_parent(start, path)?;
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(SysError::EINVAL);
+    }
+    let dentry = dir.lookup(name).ok_or(SysError::ENOENT)?;
+    if mounted_at(&dentry.path()).is_some() {
+        return Err(SysError::EBUSY);
+    }
+    match (dentry.is_dir(), rmdir) {
+        (true, false) => return Err(SysError::EISDIR),
+        (false, true) => return Err(SysError::ENOTDIR),
+        (true, true) if !dentry.inode().list().is_empty() => return Err(SysError::ENOTEMPTY),
+        _ => {}
+    }
+    if dir.unlink(name) {
+        Ok(())
+    } else {
+        Err(SysError::EPERM)
+    }
+}
@@ -1,112 +1,235 @@
-use super::File;
+use super::{Dentry, File, InodeType, Kstat, OpenFlags, PollEvents};
+use crate::config::PAGE_SIZE;
+use crate::error::{SysError, SysResult};
 use crate::mm::UserBuffer;
-use crate::sync::UPSafeCell;
-use crate::task::suspend_current_and_run_next;
-use alloc::{
-    string::String,
-    sync::{Arc, Weak},
+use crate::sync::{SpinMutex, SpinMutexGuard, WaitQueue};
+use crate::task::{
+    block_current_interruptible, current_add_signal, current_task, mark_current_blocked,
+    SignalFlags,
 };
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
 
+/// A pipe buffer starts with one page and grows while writers outpace
+/// the readers, up to `PIPE_MAX_SIZE`.
+const PIPE_MIN_SIZE: usize = PAGE_SIZE;
+const PIPE_MAX_SIZE: usize = 16 * PAGE_SIZE;
+/// Writes of at most this many bytes are never interleaved with others.
+const PIPE_BUF: usize = PAGE_SIZE;
+
+/// One end of a pipe, or a FIFO opened for reading, writing or both.
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    shared: Arc<PipeShared>,
+    flags: SpinMutex<OpenFlags>,
+    /// The FIFO this end was opened from, None for anonymous pipes.
+    dentry: Option<Arc<Dentry>>,
 }
 
-impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: true,
-            writable: false,
-            buffer,
+/// What the two ends of a pipe share.
+struct PipeShared {
+    buffer: SpinMutex<PipeBuffer>,
+    /// Woken when data arrives or a write end comes or goes.
+    readers: WaitQueue,
+    /// Woken when data leaves or a read end comes or goes.
+    writers: WaitQueue,
+}
+
+struct PipeBuffer {
+    data: VecDeque<u8>,
+    /// Bytes the buffer may hold before it has to grow.
+    capacity: usize,
+    /// Open ends of each kind, a FIFO may have several.
+    read_ends: usize,
+    write_ends: usize,
+    /// How often an end of each kind was opened, so that an open waiting
+    /// for a peer notices one which came and went at once.
+    read_opens: usize,
+    write_opens: usize,
+}
+
+impl PipeBuffer {
+    fn room(&self) -> usize {
+        self.capacity - self.data.len()
+    }
+    /// Make room for `wanted` bytes if the size limit allows it.
+    fn grow(&mut self, wanted: usize) {
+        while self.room() < wanted && self.capacity < PIPE_MAX_SIZE {
+            self.capacity *= 2;
         }
     }
-    pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: false,
-            writable: true,
-            buffer,
+    /// Move as much of `src` as fits into the buffer.
+    fn push(&mut self, src: &[u8]) -> usize {
+        let len = src.len().min(self.room());
+        self.data.extend(&src[..len]);
+        len
+    }
+    /// Move as much of the buffer as fits into `dst`.
+    fn pop(&mut self, dst: &mut [u8]) -> usize {
+        let len = dst.len().min(self.data.len());
+        let (front, back) = self.data.as_slices();
+        let first = len.min(front.len());
+        dst[..first].copy_from_slice(&front[..first]);
+        dst[first..len].copy_from_slice(&back[..len - first]);
+        self.data.drain(..len);
+        // give back what a burst of writes took
+        if self.data.is_empty() && self.capacity > PIPE_MIN_SIZE {
+            self.capacity = PIPE_MIN_SIZE;
+            self.data = VecDeque::new();
         }
+        len
+    }
+    fn all_read_ends_closed(&self) -> bool {
+        self.read_ends == 0
+    }
+    fn all_write_ends_closed(&self) -> bool {
+        self.write_ends == 0
     }
 }
 
-const RING_BUFFER_SIZE: usize = 32;
+impl PipeShared {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            buffer: SpinMutex::new(PipeBuffer {
+                data: VecDeque::new(),
+                capacity: PIPE_MIN_SIZE,
+                read_ends: 0,
+                write_ends: 0,
+                read_opens: 0,
+                write_opens: 0,
+            }),
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
+        })
+    }
+}
 
-#[derive(Copy, Clone, PartialEq)]
-enum RingBufferStatus {
-    FULL,
-    EMPTY,
-    NORMAL,
+/// Return (read_end, write_end)
+pub fn make_pipe(flags: OpenFlags) -> (Arc<Pipe>, Arc<Pipe>) {
+    let shared = PipeShared::new();
+    let read_end = Pipe::new(&shared, true, false, flags, None);
+    let write_end = Pipe::new(&shared, false, true, flags, None);
+    (read_end, write_end)
 }
 
-pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
-    head: usize,
-    tail: usize,
-    status: RingBufferStatus,
-    write_end: Option<Weak<Pipe>>,
+/// The buffer behind a named pipe. Every open of the node gives a new end
+/// of it, the data goes once no end is left.
+pub struct Fifo {
+    shared: Arc<PipeShared>,
 }
 
-impl PipeRingBuffer {
+impl Fifo {
     pub fn new() -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
-            head: 0,
-            tail: 0,
-            status: RingBufferStatus::EMPTY,
-            write_end: None,
+            shared: PipeShared::new(),
         }
     }
-    pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
-    }
-    pub fn write_byte(&mut self, byte: u8) {
-        self.status = RingBufferStatus::NORMAL;
-        self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
-        if self.tail == self.head {
-            self.status = RingBufferStatus::FULL;
+
+    /// Open an end of the FIFO at `dentry`. A reader waits for a writer and
+    /// the other way round, unless O_NONBLOCK is given or the end is both.
+    pub fn open(&self, dentry: Arc<Dentry>, flags: OpenFlags) -> SysResult<Arc<Pipe>> {
+        let (readable, writable) = flags.read_write();
+        let nonblocking = flags.contains(OpenFlags::NONBLOCK);
+        let buffer = self.shared.buffer.lock();
+        if writable && !readable && nonblocking && buffer.all_read_ends_closed() {
+            return Err(SysError::ENXIO);
         }
-    }
-    pub fn read_byte(&mut self) -> u8 {
-        self.status = RingBufferStatus::NORMAL;
-        let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
-        if self.head == self.tail {
-            self.status = RingBufferStatus::EMPTY;
+        let (read_opens, write_opens) = (buffer.read_opens, buffer.write_opens);
+        drop(buffer);
+        let end = Pipe::new(&self.shared, readable, writable, flags, Some(dentry));
+        if readable == writable || nonblocking {
+            return Ok(end);
         }
-        c
-    }
-    pub fn available_read(&self) -> usize {
-        if self.status == RingBufferStatus::EMPTY {
-            0
+        let mut buffer = end.shared.buffer.lock();
+        if readable {
+            while buffer.all_write_ends_closed() && buffer.write_opens == write_opens {
+                buffer = end.sleep_on(&end.shared.readers, buffer)?;
+            }
         } else {
-            if self.tail > self.head {
-                self.tail - self.head
-            } else {
-                self.tail + RING_BUFFER_SIZE - self.head
+            while buffer.all_read_ends_closed() && buffer.read_opens == read_opens {
+                buffer = end.sleep_on(&end.shared.writers, buffer)?;
             }
         }
+        drop(buffer);
+        Ok(end)
     }
-    pub fn available_write(&self) -> usize {
-        if self.status == RingBufferStatus::FULL {
-            0
-        } else {
-            RING_BUFFER_SIZE - self.available_read()
+}
+
+impl Pipe {
+    fn new(
+        shared: &Arc<PipeShared>,
+        readable: bool,
+        writable: bool,
+        flags: OpenFlags,
+        dentry: Option<Arc<Dentry>>,
+    ) -> Arc<Self> {
+        let mut buffer = shared.buffer.lock();
+        if readable {
+            buffer.read_ends += 1;
+            buffer.read_opens += 1;
         }
+        if writable {
+            buffer.write_ends += 1;
+            buffer.write_opens += 1;
+        }
+        drop(buffer);
+        // peers may be waiting in `Fifo::open`
+        shared.readers.wake_all();
+        shared.writers.wake_all();
+        Arc::new(Self {
+            readable,
+            writable,
+            shared: Arc::clone(shared),
+            flags: SpinMutex::new(flags.status()),
+            dentry,
+        })
     }
-    pub fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
+
+    fn nonblocking(&self) -> bool {
+        self.flags.lock().contains(OpenFlags::NONBLOCK)
+    }
+
+    /// Sleep on `queue` with `buffer` unlocked, then lock it again. The
+    /// task is queued before the lock goes, so no wakeup is missed.
+    /// A signal makes it EINTR.
+    fn sleep_on<'a>(
+        &'a self,
+        queue: &WaitQueue,
+        buffer: SpinMutexGuard<'a, PipeBuffer>,
+    ) -> SysResult<SpinMutexGuard<'a, PipeBuffer>> {
+        let task = current_task().unwrap();
+        mark_current_blocked();
+        queue.register(&task);
+        drop(buffer);
+        if let Err(err) = block_current_interruptible() {
+            queue.unregister(&task);
+            return Err(err);
+        }
+        Ok(self.shared.buffer.lock())
     }
 }
 
-/// Return (read_end, write_end)
-pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
-    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
-    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
-    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.inner.borrow_mut().set_write_end(&write_end);
-    (read_end, write_end)
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut buffer = self.shared.buffer.lock();
+        if self.readable {
+            buffer.read_ends -= 1;
+        }
+        if self.writable {
+            buffer.write_ends -= 1;
+        }
+        if buffer.all_read_ends_closed() && buffer.all_write_ends_closed() {
+            buffer.data = VecDeque::new();
+            buffer.capacity = PIPE_MIN_SIZE;
+        }
+        // a peer which saw this end open has queued itself by the time
+        // the lock is free
+        drop(buffer);
+        self.shared.readers.wake_all();
+        self.shared.writers.wake_all();
+    }
 }
 
 impl File for Pipe {
@@ -116,58 +239,126 @@ impl File for Pipe {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, buf: UserBuffer) -> usize {
+    /// Wait for data, then take what is there. Read nothing once all write
+    /// ends are closed.
+    fn read(&self, buf: UserBuffer) -> SysResult<usize> {
         assert_eq!(self.readable(), true);
-        let mut buf_iter = buf.into_iter();
-        let mut read_size = 0usize;
-        loop {
-            let mut ring_buffer = self.buffer.inner.borrow_mut();
-            let loop_read = ring_buffer.available_read();
-            if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
-                    return read_size;
-                }
-                drop(ring_buffer);
-                suspend_current_and_run_next();
-                continue;
+        let mut buffer = self.shared.buffer.lock();
+        while buffer.data.is_empty() && !buffer.all_write_ends_closed() && buf.len() > 0 {
+            if self.nonblocking() {
+                return Err(SysError::EAGAIN);
             }
-            for _ in 0..loop_read {
-                if let Some(byte_ref) = buf_iter.next() {
-                    unsafe {
-                        *byte_ref = ring_buffer.read_byte();
-                    }
-                    read_size += 1;
-                } else {
-                    return read_size;
-                }
+            buffer = self.sleep_on(&self.shared.readers, buffer)?;
+        }
+        let mut read_size = 0usize;
+        for slice in buf.buffers {
+            let len = buffer.pop(slice);
+            read_size += len;
+            if len < slice.len() {
+                break;
             }
         }
+        drop(buffer);
+        self.shared.writers.wake_all();
+        Ok(read_size)
     }
-
-    fn write(&self, buf: UserBuffer) -> usize {
+    /// Write all of `buf`, waiting for room as needed. Writes of at most
+    /// `PIPE_BUF` bytes wait until they fit as a whole.
+    fn write(&self, buf: UserBuffer) -> SysResult<usize> {
         assert_eq!(self.writable(), true);
-        let mut buf_iter = buf.into_iter();
+        let total = buf.len();
         let mut write_size = 0usize;
-        loop {
-            let mut ring_buffer = self.buffer.inner.borrow_mut();
-            let loop_write = ring_buffer.available_write();
-            if loop_write == 0 {
-                drop(ring_buffer);
-                suspend_current_and_run_next();
-                continue;
-            }
-            // write at most loop_write bytes
-            for _ in 0..loop_write {
-                if let Some(byte_ref) = buf_iter.next() {
-                    ring_buffer.write_byte(unsafe { *byte_ref });
-                    write_size += 1;
+        let mut buffer = self.shared.buffer.lock();
+        for slice in buf.buffers.iter() {
+            let mut done = 0usize;
+            while done < slice.len() {
+                if buffer.all_read_ends_closed() {
+                    drop(buffer);
+                    current_add_signal(SignalFlags::SIGPIPE);
+                    return match write_size {
+                        0 => Err(SysError::EPIPE),
+                        _ => Ok(write_size),
+                    };
+                }
+                let needed = if total <= PIPE_BUF {
+                    total - write_size
                 } else {
-                    return write_size;
+                    1
+                };
+                buffer.grow(needed);
+                if buffer.room() < needed {
+                    // readers may be waiting for what was written so far
+                    self.shared.readers.wake_all();
+                    if self.nonblocking() {
+                        return match write_size {
+                            0 => Err(SysError::EAGAIN),
+                            _ => Ok(write_size),
+                        };
+                    }
+                    buffer = match self.sleep_on(&self.shared.writers, buffer) {
+                        Ok(buffer) => buffer,
+                        Err(err) => {
+                            return match write_size {
+                                0 => Err(err),
+                                _ => Ok(write_size),
+                            }
+                        }
+                    };
+                    continue;
                 }
+                let len = buffer.push(&slice[done..]);
+                done += len;
+                write_size += len;
+            }
+        }
+        drop(buffer);
+        self.shared.readers.wake_all();
+        Ok(write_size)
+    }
+    fn poll(&self) -> PollEvents {
+        let buffer = self.shared.buffer.lock();
+        let mut events = PollEvents::empty();
+        if self.readable {
+            events.set(PollEvents::IN, !buffer.data.is_empty());
+            events.set(PollEvents::HUP, buffer.all_write_ends_closed());
+        }
+        if self.writable {
+            events.set(PollEvents::OUT, buffer.data.len() < PIPE_MAX_SIZE);
+            events.set(PollEvents::ERR, buffer.all_read_ends_closed());
+        }
+        events
+    }
+    /// An end open for both waits as a reader.
+    fn wait_queue(&self) -> Option<&WaitQueue> {
+        match self.readable {
+            true => Some(&self.shared.readers),
+            false => Some(&self.shared.writers),
+        }
+    }
+    fn status_flags(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        *self.flags.lock() = flags.status();
+    }
+    fn kstat(&self, stat: &mut Kstat) {
+        match &self.dentry {
+            Some(dentry) => dentry.inode().stat(stat),
+            None => {
+                *stat = Kstat::default();
+                stat.st_mode = InodeType::Fifo.mode();
+                stat.st_nlink = 1;
+                stat.st_blksize = PIPE_BUF as u32;
             }
         }
     }
     fn name(&self) -> String {
-        String::from("pipe")
+        match &self.dentry {
+            Some(dentry) => String::from(dentry.name()),
+            None => String::from("pipe"),
+        }
+    }
+    fn dentry(&self) -> Option<Arc<Dentry>> {
+        self.dentry.clone()
     }
 }
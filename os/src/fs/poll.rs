@@ -0,0 +1,64 @@
+use super::File;
+use crate::error::SysResult;
+use crate::sync::WaitQueue;
+use crate::task::{
+    block_current_interruptible, cancel_current_blocked, current_task, mark_current_blocked,
+};
+use crate::timer::{add_timer, get_time_ms, remove_timer};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+bitflags! {
+    /// Readiness of a file, the `events` of `struct pollfd`.
+    pub struct PollEvents: u16 {
+        const IN = 0x001;
+        const PRI = 0x002;
+        const OUT = 0x004;
+        const ERR = 0x008;
+        /// The peer is gone.
+        const HUP = 0x010;
+        /// Not an open fd.
+        const NVAL = 0x020;
+    }
+}
+
+/// Sleep until `check` finds something ready, i.e. returns more than 0,
+/// or until the time reaches `deadline_ms`. Return what `check` found
+/// last, or EINTR if a signal comes first. The task is queued on the wait
+/// queues of `files` before each check, so a file becoming ready right
+/// after it cannot be missed.
+pub fn wait_ready(
+    files: &[Arc<dyn File>],
+    deadline_ms: Option<usize>,
+    mut check: impl FnMut() -> usize,
+) -> SysResult<usize> {
+    let task = current_task().unwrap();
+    let queues: Vec<&WaitQueue> = files.iter().filter_map(|file| file.wait_queue()).collect();
+    let mut timer_armed = false;
+    let ready = loop {
+        mark_current_blocked();
+        for queue in queues.iter() {
+            queue.register(&task);
+        }
+        let ready = check();
+        let expired = deadline_ms.map_or(false, |deadline| get_time_ms() >= deadline);
+        if ready > 0 || expired {
+            cancel_current_blocked();
+            break Ok(ready);
+        }
+        if let (Some(deadline), false) = (deadline_ms, timer_armed) {
+            add_timer(deadline, Arc::clone(&task));
+            timer_armed = true;
+        }
+        if let Err(err) = block_current_interruptible() {
+            break Err(err);
+        }
+    };
+    for queue in queues.iter() {
+        queue.unregister(&task);
+    }
+    if timer_armed {
+        remove_timer(&task);
+    }
+    ready
+}
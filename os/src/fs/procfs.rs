@@ -0,0 +1,307 @@
+use super::mount::mounts;
+use super::vfs::{FileSystem, Inode, InodeType};
+use crate::config::{MAX_HARTID, PAGE_SIZE};
+use crate::fatfs::sdcard::BLK_MANAGER;
+use crate::mm::{frame_stats, MapPermission};
+use crate::task::{current_process, pid2process, process_list, ProcessControlBlock, TaskStatus};
+use crate::timer::get_time_ms;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// A file whose text is generated again on every read, so that it is never
+/// stale. Files of a process which has gone read as empty.
+struct ProcFile(Box<dyn Fn() -> String + Send + Sync>);
+
+impl ProcFile {
+    fn new(generate: impl Fn() -> String + Send + Sync + 'static) -> Arc<dyn Inode> {
+        Arc::new(Self(Box::new(generate)))
+    }
+}
+
+impl Inode for ProcFile {
+    fn inode_type(&self) -> InodeType {
+        InodeType::File
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let text = (self.0)();
+        let bytes = text.as_bytes();
+        if offset >= bytes.len() {
+            return 0;
+        }
+        let len = buf.len().min(bytes.len() - offset);
+        buf[..len].copy_from_slice(&bytes[offset..offset + len]);
+        len
+    }
+}
+
+const GLOBAL_FILES: [&str; 5] = ["blockcache", "cpuinfo", "meminfo", "mounts", "uptime"];
+
+/// Counters of the FAT block cache.
+fn blockcache() -> String {
+    let stats = BLK_MANAGER.lock().stats();
+    let mut text = String::new();
+    writeln!(text, "hits\t{}", stats.hits).unwrap();
+    writeln!(text, "misses\t{}", stats.misses).unwrap();
+    writeln!(text, "writebacks\t{}", stats.writebacks).unwrap();
+    writeln!(text, "cached\t{}", stats.cached).unwrap();
+    writeln!(text, "dirty\t{}", stats.dirty).unwrap();
+    text
+}
+
+fn cpuinfo() -> String {
+    let mut text = String::new();
+    for hartid in 0..MAX_HARTID {
+        writeln!(text, "processor\t: {}", hartid).unwrap();
+        writeln!(text, "hart\t\t: {}", hartid).unwrap();
+        writeln!(text, "isa\t\t: rv64imafdc").unwrap();
+        writeln!(text, "mmu\t\t: sv39").unwrap();
+        writeln!(text).unwrap();
+    }
+    text
+}
+
+fn meminfo() -> String {
+    let (total, free) = frame_stats();
+    let kb = |frames: usize| frames * PAGE_SIZE / 1024;
+    let mut text = String::new();
+    writeln!(text, "MemTotal:\t{} kB", kb(total)).unwrap();
+    writeln!(text, "MemFree:\t{} kB", kb(free)).unwrap();
+    writeln!(text, "MemUsed:\t{} kB", kb(total - free)).unwrap();
+    text
+}
+
+fn mounts_text() -> String {
+    let mut text = String::new();
+    for (source, target, fs_type) in mounts() {
+        writeln!(text, "{} {} {} rw 0 0", source, target, fs_type).unwrap();
+    }
+    text
+}
+
+/// Seconds since boot, the idle time is not accounted.
+fn uptime() -> String {
+    let ms = get_time_ms();
+    format!("{}.{:02} 0.00\n", ms / 1000, ms % 1000 / 10)
+}
+
+/// Name, state and sizes of a process, in the format of Linux.
+fn status(process: &Arc<ProcessControlBlock>) -> String {
+    let inner = process.inner_exclusive_access();
+    let threads: Vec<_> = inner.tasks.iter().flatten().collect();
+    let state = if inner.is_zombie {
+        "Z (zombie)"
+    } else if inner.frozen {
+        "T (stopped)"
+    } else if threads.iter().any(|task| {
+        let status = task.inner_lock_access().task_status;
+        status == TaskStatus::Running || status == TaskStatus::Ready
+    }) {
+        "R (running)"
+    } else {
+        "S (sleeping)"
+    };
+    let ppid = inner
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+        .map_or(0, |parent| parent.getpid());
+    let areas = inner.memory_set.user_areas();
+    let size: usize = areas.iter().map(|area| area.end.0 - area.start.0).sum();
+    let resident: usize = areas.iter().map(|area| area.resident).sum();
+    let mut text = String::new();
+    writeln!(text, "Name:\t{}", inner.name).unwrap();
+    writeln!(text, "State:\t{}", state).unwrap();
+    writeln!(text, "Pid:\t{}", process.getpid()).unwrap();
+    writeln!(text, "PPid:\t{}", ppid).unwrap();
+    writeln!(text, "Threads:\t{}", threads.len()).unwrap();
+    writeln!(text, "VmSize:\t{} kB", size / 1024).unwrap();
+    writeln!(text, "VmRSS:\t{} kB", resident * PAGE_SIZE / 1024).unwrap();
+    writeln!(text, "SigPnd:\t{:016x}", inner.signals.bits()).unwrap();
+    writeln!(text, "SigBlk:\t{:016x}", inner.signal_mask.bits()).unwrap();
+    text
+}
+
+/// Arguments separated and ended by NUL bytes.
+fn cmdline(process: &Arc<ProcessControlBlock>) -> String {
+    let mut text = String::new();
+    for arg in process.inner_exclusive_access().cmdline.iter() {
+        text.push_str(arg);
+        text.push('\0');
+    }
+    text
+}
+
+fn maps(process: &Arc<ProcessControlBlock>) -> String {
+    let inner = process.inner_exclusive_access();
+    let mut text = String::new();
+    for area in inner.memory_set.user_areas() {
+        let perm = area.perm;
+        let flag = |bit: MapPermission, ch: char| {
+            if perm.contains(bit) {
+                ch
+            } else {
+                '-'
+            }
+        };
+        let (name, offset) = match area.file {
+            Some((path, offset)) => (path, offset),
+            None if area.start.0 == inner.heap_bottom => (String::from("[heap]"), 0),
+            None => (String::new(), 0),
+        };
+        writeln!(
+            text,
+            "{:08x}-{:08x} {}{}{}{} {:08x} {}",
+            area.start.0,
+            area.end.0,
+            flag(MapPermission::R, 'r'),
+            flag(MapPermission::W, 'w'),
+            flag(MapPermission::X, 'x'),
+            if area.shared { 's' } else { 'p' },
+            offset,
+            name
+        )
+        .unwrap();
+    }
+    text
+}
+
+/// A file of the directory of a process.
+fn process_file(
+    process: &Weak<ProcessControlBlock>,
+    generate: fn(&Arc<ProcessControlBlock>) -> String,
+) -> Arc<dyn Inode> {
+    let process = Weak::clone(process);
+    ProcFile::new(move || process.upgrade().map_or_else(String::new, |p| generate(&p)))
+}
+
+const PROCESS_FILES: [&str; 3] = ["cmdline", "maps", "status"];
+
+/// /proc/<pid>, it holds the process weakly and empties once it is gone.
+struct ProcessDir(Weak<ProcessControlBlock>);
+
+impl Inode for ProcessDir {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Dir
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        match name {
+            "cmdline" => Some(process_file(&self.0, cmdline)),
+            "maps" => Some(process_file(&self.0, maps)),
+            "status" => Some(process_file(&self.0, status)),
+            "fd" => Some(Arc::new(FdDir(Weak::clone(&self.0)))),
+            _ => None,
+        }
+    }
+    fn list(&self) -> Vec<(String, InodeType)> {
+        let mut entries: Vec<_> = PROCESS_FILES
+            .iter()
+            .map(|name| (String::from(*name), InodeType::File))
+            .collect();
+        entries.push((String::from("fd"), InodeType::Dir));
+        entries
+    }
+}
+
+/// /proc/<pid>/fd, one file per open fd holding the path of what is open.
+struct FdDir(Weak<ProcessControlBlock>);
+
+impl Inode for FdDir {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Dir
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        let fd: usize = name.parse().ok()?;
+        let process = self.0.upgrade()?;
+        let open = matches!(
+            process.inner_exclusive_access().fd_table.get(&fd),
+            Some(Some(_))
+        );
+        if !open {
+            return None;
+        }
+        let process = Weak::clone(&self.0);
+        Some(ProcFile::new(move || {
+            let process = match process.upgrade() {
+                Some(process) => process,
+                None => return String::new(),
+            };
+            let file = match process.inner_exclusive_access().fd_table.get(&fd) {
+                Some(Some(file)) => Arc::clone(file),
+                _ => return String::new(),
+            };
+            let mut path = match file.dentry() {
+                Some(dentry) => dentry.path(),
+                None => file.name(),
+            };
+            path.push('\n');
+            path
+        }))
+    }
+    fn list(&self) -> Vec<(String, InodeType)> {
+        let process = match self.0.upgrade() {
+            Some(process) => process,
+            None => return Vec::new(),
+        };
+        let inner = process.inner_exclusive_access();
+        inner
+            .fd_table
+            .iter()
+            .filter(|(_, file)| file.is_some())
+            .map(|(fd, _)| (fd.to_string(), InodeType::File))
+            .collect()
+    }
+    fn cache_entries(&self) -> bool {
+        false
+    }
+}
+
+/// The root of a procfs: the global files, "self" and one directory per
+/// live process.
+struct ProcRoot;
+
+impl Inode for ProcRoot {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Dir
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        let process = match name {
+            "blockcache" => return Some(ProcFile::new(blockcache)),
+            "cpuinfo" => return Some(ProcFile::new(cpuinfo)),
+            "meminfo" => return Some(ProcFile::new(meminfo)),
+            "mounts" => return Some(ProcFile::new(mounts_text)),
+            "uptime" => return Some(ProcFile::new(uptime)),
+            "self" => current_process(),
+            _ => pid2process(name.parse().ok()?)?,
+        };
+        Some(Arc::new(ProcessDir(Arc::downgrade(&process))))
+    }
+    fn list(&self) -> Vec<(String, InodeType)> {
+        let mut entries: Vec<_> = GLOBAL_FILES
+            .iter()
+            .map(|name| (String::from(*name), InodeType::File))
+            .collect();
+        entries.push((String::from("self"), InodeType::Dir));
+        for process in process_list() {
+            entries.push((process.getpid().to_string(), InodeType::Dir));
+        }
+        entries
+    }
+    fn cache_entries(&self) -> bool {
+        false
+    }
+}
+
+pub struct ProcFs;
+
+impl FileSystem for ProcFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(ProcRoot)
+    }
+    fn fs_type(&self) -> &'static str {
+        "proc"
+    }
+}
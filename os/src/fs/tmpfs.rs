@@ -0,0 +1,222 @@
+use super::pipe::Fifo;
+use super::vfs::{FileSystem, Inode, InodeType};
+use crate::config::{PAGE_SIZE, TMPFS_MAX_PAGES};
+use crate::mm::{frame_alloc, FrameTracker};
+use crate::net::SocketNode;
+use crate::sync::SpinMutex;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Pages a tmpfs may still take, shared by all of its files.
+type PageBudget = Arc<AtomicUsize>;
+
+/// A regular file of a tmpfs, its data lives in whole frames taken from
+/// the frame allocator rather than in the kernel heap.
+struct TmpFile {
+    budget: PageBudget,
+    inner: SpinMutex<TmpFileInner>,
+}
+
+struct TmpFileInner {
+    size: usize,
+    pages: Vec<FrameTracker>,
+}
+
+impl TmpFile {
+    fn new(budget: PageBudget) -> Self {
+        Self {
+            budget,
+            inner: SpinMutex::new(TmpFileInner {
+                size: 0,
+                pages: Vec::new(),
+            }),
+        }
+    }
+
+    /// Take a zeroed frame, if the budget and the allocator allow it.
+    fn alloc_page(&self) -> Option<FrameTracker> {
+        self.budget
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |left| {
+                left.checked_sub(1)
+            })
+            .ok()?;
+        let frame = frame_alloc();
+        if frame.is_none() {
+            self.budget.fetch_add(1, Ordering::AcqRel);
+        }
+        frame
+    }
+}
+
+impl Drop for TmpFile {
+    fn drop(&mut self) {
+        let pages = self.inner.lock().pages.len();
+        self.budget.fetch_add(pages, Ordering::AcqRel);
+    }
+}
+
+impl Inode for TmpFile {
+    fn inode_type(&self) -> InodeType {
+        InodeType::File
+    }
+    fn size(&self) -> usize {
+        self.inner.lock().size
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let inner = self.inner.lock();
+        let end = inner.size.min(offset.saturating_add(buf.len()));
+        let mut pos = offset;
+        while pos < end {
+            let page = inner.pages[pos / PAGE_SIZE].ppn.get_bytes_array();
+            let start = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - start).min(end - pos);
+            buf[pos - offset..pos - offset + len].copy_from_slice(&page[start..start + len]);
+            pos += len;
+        }
+        end.saturating_sub(offset)
+    }
+    /// Writing past the end fills the gap with zeros. The write is cut
+    /// short once no more pages can be had.
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut inner = self.inner.lock();
+        let end = match offset.checked_add(buf.len()) {
+            Some(end) => end,
+            None => return 0,
+        };
+        let mut pos = offset;
+        while pos < end {
+            while inner.pages.len() <= pos / PAGE_SIZE {
+                match self.alloc_page() {
+                    Some(frame) => inner.pages.push(frame),
+                    None => break,
+                }
+            }
+            if inner.pages.len() <= pos / PAGE_SIZE {
+                break;
+            }
+            let page = inner.pages[pos / PAGE_SIZE].ppn.get_bytes_array();
+            let start = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - start).min(end - pos);
+            page[start..start + len].copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        if pos > inner.size {
+            inner.size = pos;
+        }
+        pos.saturating_sub(offset)
+    }
+    fn truncate(&self) -> bool {
+        let mut inner = self.inner.lock();
+        self.budget.fetch_add(inner.pages.len(), Ordering::AcqRel);
+        inner.pages.clear();
+        inner.size = 0;
+        true
+    }
+}
+
+/// A named pipe, it keeps no data of its own.
+struct TmpFifo {
+    fifo: Fifo,
+}
+
+impl Inode for TmpFifo {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Fifo
+    }
+    fn fifo(&self) -> Option<&Fifo> {
+        Some(&self.fifo)
+    }
+}
+
+/// The name of a bound socket.
+struct TmpSocket {
+    node: SocketNode,
+}
+
+impl Inode for TmpSocket {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Socket
+    }
+    fn socket(&self) -> Option<&SocketNode> {
+        Some(&self.node)
+    }
+}
+
+/// A directory of a tmpfs, listed in name order.
+struct TmpDir {
+    budget: PageBudget,
+    entries: SpinMutex<BTreeMap<String, Arc<dyn Inode>>>,
+}
+
+impl TmpDir {
+    fn new(budget: PageBudget) -> Self {
+        Self {
+            budget,
+            entries: SpinMutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl Inode for TmpDir {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Dir
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.entries.lock().get(name).map(Arc::clone)
+    }
+    fn create(&self, name: &str, inode_type: InodeType) -> Option<Arc<dyn Inode>> {
+        let mut entries = self.entries.lock();
+        if entries.contains_key(name) {
+            return None;
+        }
+        let budget = Arc::clone(&self.budget);
+        let inode: Arc<dyn Inode> = match inode_type {
+            InodeType::File => Arc::new(TmpFile::new(budget)),
+            InodeType::Dir => Arc::new(TmpDir::new(budget)),
+            InodeType::Fifo => Arc::new(TmpFifo { fifo: Fifo::new() }),
+            InodeType::Socket => Arc::new(TmpSocket {
+                node: SocketNode::new(),
+            }),
+            _ => return None,
+        };
+        entries.insert(String::from(name), Arc::clone(&inode));
+        Some(inode)
+    }
+    /// The data of a file goes once the last open file of it is closed.
+    fn unlink(&self, name: &str) -> bool {
+        self.entries.lock().remove(name).is_some()
+    }
+    fn list(&self) -> Vec<(String, InodeType)> {
+        self.entries
+            .lock()
+            .iter()
+            .map(|(name, inode)| (String::clone(name), inode.inode_type()))
+            .collect()
+    }
+}
+
+/// A filesystem in memory, emptied when unmounted or on reboot.
+pub struct TmpFs {
+    root: Arc<TmpDir>,
+}
+
+impl TmpFs {
+    pub fn new() -> Self {
+        let budget = Arc::new(AtomicUsize::new(TMPFS_MAX_PAGES));
+        Self {
+            root: Arc::new(TmpDir::new(budget)),
+        }
+    }
+}
+
+impl FileSystem for TmpFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::clone(&self.root) as Arc<dyn Inode>
+    }
+    fn fs_type(&self) -> &'static str {
+        "tmpfs"
+    }
+}
@@ -0,0 +1,122 @@
+use super::devfs::Device;
+use super::pipe::Fifo;
+use super::Kstat;
+use crate::net::SocketNode;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InodeType {
+    File,
+    Dir,
+    CharDevice,
+    BlockDevice,
+    Fifo,
+    Socket,
+}
+
+impl InodeType {
+    /// The `S_IFMT` bits of `st_mode`.
+    pub fn mode(&self) -> u32 {
+        match self {
+            InodeType::File => 0o100000,
+            InodeType::Dir => 0o040000,
+            InodeType::CharDevice => 0o020000,
+            InodeType::BlockDevice => 0o060000,
+            InodeType::Fifo => 0o010000,
+            InodeType::Socket => 0o140000,
+        }
+    }
+    /// The type named by the `S_IFMT` bits of `mode`.
+    pub fn from_mode(mode: u32) -> Option<Self> {
+        [
+            InodeType::File,
+            InodeType::Dir,
+            InodeType::CharDevice,
+            InodeType::BlockDevice,
+            InodeType::Fifo,
+            InodeType::Socket,
+        ]
+        .iter()
+        .copied()
+        .find(|inode_type| inode_type.mode() == mode & 0o170000)
+    }
+    /// `d_type` of a directory entry.
+    pub fn dirent_type(&self) -> u8 {
+        match self {
+            InodeType::File => 8,
+            InodeType::Dir => 4,
+            InodeType::CharDevice => 2,
+            InodeType::BlockDevice => 6,
+            InodeType::Fifo => 1,
+            InodeType::Socket => 12,
+        }
+    }
+}
+
+/// A file or directory of some filesystem. Paths are resolved by the
+/// dentry cache above, so directories only deal with single names.
+pub trait Inode: Send + Sync {
+    fn inode_type(&self) -> InodeType;
+    fn size(&self) -> usize {
+        0
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    /// Drop all data of a regular file, for O_TRUNC.
+    fn truncate(&self) -> bool {
+        false
+    }
+    /// Find the entry `name` of a directory.
+    fn lookup(&self, _name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    fn create(&self, _name: &str, _inode_type: InodeType) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    fn unlink(&self, _name: &str) -> bool {
+        false
+    }
+    /// Entries of a directory without "." and "..".
+    fn list(&self) -> Vec<(String, InodeType)> {
+        Vec::new()
+    }
+    fn stat(&self, stat: &mut Kstat) {
+        *stat = Kstat::default();
+        stat.st_mode = self.inode_type().mode();
+        stat.st_nlink = 1;
+        stat.st_size = self.size() as isize;
+        stat.st_blksize = 512;
+        stat.st_blocks = ((self.size() + 511) / 512) as u64;
+    }
+    /// The device a device node stands for, it is opened instead of the node.
+    fn device(&self) -> Option<Arc<dyn Device>> {
+        None
+    }
+    /// The pipe behind a FIFO node, it is opened instead of the node.
+    fn fifo(&self) -> Option<&Fifo> {
+        None
+    }
+    /// What a socket bound to this node left for `connect` to find.
+    fn socket(&self) -> Option<&SocketNode> {
+        None
+    }
+    /// Whether the dentry cache may keep the entries looked up in this
+    /// directory, which is wrong when they come and go on their own.
+    fn cache_entries(&self) -> bool {
+        true
+    }
+}
+
+pub trait FileSystem: Send + Sync {
+    fn root(&self) -> Arc<dyn Inode>;
+    /// Name of the filesystem type as passed to `mount`.
+    fn fs_type(&self) -> &'static str;
+    /// Write cached data back to the device.
+    fn sync(&self) {}
+}
@@ -8,7 +8,8 @@
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
 
-use crate::{drivers::BLOCK_DEVICE, sbi::send_ipi};
+use crate::{config::MAX_HARTID, drivers::BLOCK_DEVICE};
+use core::sync::atomic::{AtomicBool, Ordering};
 extern crate alloc;
 #[macro_use]
 extern crate bitflags;
@@ -16,11 +17,13 @@ extern crate bitflags;
 mod console;
 mod config;
 mod drivers;
+mod error;
 mod fatfs;
 mod fs;
 mod irq;
 mod lang_items;
 mod mm;
+mod net;
 mod sbi;
 mod sync;
 mod syscall;
@@ -40,6 +43,22 @@ fn clear_bss() {
     }
 }
 
+/// Set by the boot hart once the kernel is initialized. Kept out of .bss,
+/// which holds garbage until the boot hart clears it.
+#[link_section = ".data"]
+static SMP_READY: AtomicBool = AtomicBool::new(false);
+
+/// Wake up the other harts. An SBI without the HSM extension has started
+/// them already, they wait for `SMP_READY` in any case.
+fn start_secondary_harts(boot_hartid: usize) {
+    extern "C" {
+        fn _start();
+    }
+    for hartid in (0..MAX_HARTID).filter(|&id| id != boot_hartid) {
+        sbi::hart_start(hartid, _start as usize, 0);
+    }
+}
+
 #[no_mangle]
 pub fn rust_main(hartid: usize) -> ! {
     unsafe { asm!("mv tp, {}", in(reg)hartid) }
@@ -49,24 +68,27 @@ pub fn rust_main(hartid: usize) -> ! {
         mm::remap_test();
         console::logger_init();
         trap::init();
-        // trap::enable_timer_interrupt();
-        // timer::set_next_trigger();
+        trap::enable_timer_interrupt();
+        timer::set_next_trigger();
         println!("[kernel] Lotus core {}", hartid);
         println!("{}", include_str!("banner"));
         irq::irq_init(hartid);
         fatfs::fs_init();
+        fs::init();
         task::add_initproc();
-
-        // send_ipi(1);
+        BLOCK_DEVICE.change_mode();
+        SMP_READY.store(true, Ordering::Release);
+        start_secondary_harts(hartid);
     } else {
-        // mm::activate();
-        // trap::init();
-        // trap::enable_timer_interrupt();
-        // timer::set_next_trigger();
-        // println!("Init hart 1 ");
-        loop {}
+        while !SMP_READY.load(Ordering::Acquire) {
+            core::hint::spin_loop();
+        }
+        mm::activate();
+        trap::init();
+        trap::enable_timer_interrupt();
+        timer::set_next_trigger();
+        println!("[kernel] Lotus core {}", hartid);
     }
-    BLOCK_DEVICE.change_mode();
     task::run_tasks(hartid);
     panic!("Unreachable in rust_main!");
 }
@@ -39,6 +39,7 @@ trait FrameAllocator {
 }
 
 pub struct StackFrameAllocator {
+    start: usize,
     current: usize,
     end: usize,
     recycled: Vec<usize>,
@@ -46,14 +47,21 @@ pub struct StackFrameAllocator {
 
 impl StackFrameAllocator {
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.start = l.0;
         self.current = l.0;
         self.end = r.0;
         println!("last {} Physical Frames.", self.end - self.current);
     }
+    /// Frames managed in total and frames not handed out.
+    pub fn stats(&self) -> (usize, usize) {
+        let free = self.end - self.current + self.recycled.len();
+        (self.end - self.start, free)
+    }
 }
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
         Self {
+            start: 0,
             current: 0,
             end: 0,
             recycled: Vec::new(),
@@ -110,6 +118,11 @@ pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.lock().dealloc(ppn);
 }
 
+/// Total and free physical frames.
+pub fn frame_stats() -> (usize, usize) {
+    FRAME_ALLOCATOR.lock().stats()
+}
+
 #[allow(unused)]
 pub fn frame_allocator_test() {
     let mut v: Vec<FrameTracker> = Vec::new();
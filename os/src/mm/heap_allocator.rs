@@ -1,8 +1,38 @@
 use crate::config::KERNEL_HEAP_SIZE;
+use crate::sync::{intr_off, intr_on};
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use riscv::register::sstatus;
+
+/// `LockedHeap` spins on a lock which leaves interrupts on. A task
+/// preempted while holding it would stall every allocation on its hart, so
+/// interrupts are kept off around it. `push_off` cannot serve here, the
+/// processors are allocated before they exist.
+struct KernelHeap(LockedHeap);
+
+impl KernelHeap {
+    fn without_interrupts<T>(&self, f: impl FnOnce(&LockedHeap) -> T) -> T {
+        let sie = sstatus::read().sie();
+        intr_off();
+        let ret = f(&self.0);
+        if sie {
+            intr_on();
+        }
+        ret
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.without_interrupts(|heap| heap.alloc(layout))
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.without_interrupts(|heap| heap.dealloc(ptr, layout))
+    }
+}
 
 #[global_allocator]
-static HEAP_ALLOCATOR: LockedHeap = LockedHeap::empty();
+static HEAP_ALLOCATOR: KernelHeap = KernelHeap(LockedHeap::empty());
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
@@ -14,6 +44,7 @@ static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 pub fn init_heap() {
     unsafe {
         HEAP_ALLOCATOR
+            .0
             .lock()
             .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
     }
@@ -2,10 +2,17 @@ use super::{frame_alloc, FrameTracker};
 use super::{PTEFlags, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE};
-use crate::sync::UPSafeCell;
+use crate::config::{
+    MAX_HARTID, MEMORY_END, MMAP_BASE, MMIO, PAGE_SIZE, TRAMPOLINE, USER_SPACE_END, USER_STACK_BASE,
+};
+use crate::fs::{File, OSInode};
+use crate::sbi::remote_sfence_vma;
+use crate::sync::SpinMutex;
+use crate::task::current_hartid;
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use lazy_static::*;
 use riscv::register::satp;
@@ -24,12 +31,24 @@ extern "C" {
 }
 
 lazy_static! {
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinMutex<MemorySet>> =
+        Arc::new(SpinMutex::new(MemorySet::new_kernel()));
 }
 
 pub fn kernel_token() -> usize {
-    KERNEL_SPACE.exclusive_access().token()
+    KERNEL_SPACE.lock().token()
+}
+
+/// Drop stale translations after mappings were removed or downgraded.
+/// The other harts may be running threads of the same address space.
+fn flush_tlb() {
+    unsafe {
+        asm!("sfence.vma");
+    }
+    let other_harts = ((1 << MAX_HARTID) - 1) & !(1 << current_hartid());
+    if other_harts != 0 {
+        remote_sfence_vma(other_harts, 0, usize::MAX);
+    }
 }
 
 pub struct MemorySet {
@@ -68,6 +87,7 @@ impl MemorySet {
         {
             area.unmap(&mut self.page_table);
             self.areas.remove(idx);
+            flush_tlb();
         }
     }
     fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
@@ -162,14 +182,26 @@ impl MemorySet {
         }
         memory_set
     }
-    /// Include sections in elf and trampoline,
-    /// also returns user_sp_base and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// Include sections in elf and trampoline and an empty heap,
+    /// also returns user_heap_bottom, user_sp_base and entry point.
+    /// Segments are only read from `elf_file` when their pages are touched.
+    pub fn from_elf(elf_file: Arc<OSInode>) -> (Self, usize, usize, usize) {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
+        // read the elf header and program headers only
+        let mut elf_data = vec![0u8; PAGE_SIZE];
+        let read_size = elf_file.read_at(0, &mut elf_data);
+        elf_data.truncate(read_size);
+        let header = xmas_elf::header::parse_header(&elf_data).unwrap();
+        let ph_end = header.pt2.ph_offset() as usize
+            + header.pt2.ph_count() as usize * header.pt2.ph_entry_size() as usize;
+        if ph_end > elf_data.len() {
+            elf_data.resize(ph_end, 0);
+            elf_file.read_at(0, &mut elf_data);
+        }
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        let elf = xmas_elf::ElfFile::new(&elf_data).unwrap();
         let elf_header = elf.header;
         let magic = elf_header.pt1.magic;
         assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
@@ -191,37 +223,60 @@ impl MemorySet {
                 if ph_flags.is_execute() {
                     map_perm |= MapPermission::X;
                 }
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
+                let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
                 max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                // the file is mapped from the start of the first page,
+                // which is fine as offsets and addresses agree modulo the page size
+                let page_offset = start_va.page_offset();
+                map_area.file = Some(MapFile {
+                    file: elf_file.clone(),
+                    offset: ph.offset() as usize - page_offset,
+                    len: page_offset + ph.file_size() as usize,
+                });
+                memory_set.push(map_area, None);
             }
         }
 
-        let map_perm = MapPermission::R | MapPermission::W | MapPermission::U;
-        let start_va: VirtAddr = max_end_vpn.into();
-        let end_va: VirtAddr = (start_va.0 + 2 * PAGE_SIZE).into();
-        let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-        memory_set.push(map_area, None);
-
+        // the heap starts empty right after the elf, brk makes it grow
         let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_base: usize = max_end_va.into();
-        user_stack_base += 2 * PAGE_SIZE;
+        memory_set.push(
+            MapArea::new(
+                max_end_va,
+                max_end_va,
+                MapType::Framed,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
         (
             memory_set,
-            user_stack_base,
+            max_end_va.into(),
+            USER_STACK_BASE,
             elf.header.pt2.entry_point() as usize,
         )
     }
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    /// Frames of user-accessible areas are shared copy-on-write: both sides
+    /// lose the W bit until one of them stores to the page.
+    /// Kernel-only areas (trap contexts) are still copied eagerly, since the
+    /// kernel writes them through physical addresses.
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
-        // copy data sections/trap_context/user_stack
+        // share data sections/user_stack, copy trap_context
         for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
+            let mut new_area = MapArea::from_another(area);
+            if area.map_type == MapType::Framed && area.map_perm.contains(MapPermission::U) {
+                // MAP_SHARED areas keep their flags, so both sides see each other's stores
+                let pte_flags = area.shared_pte_flags();
+                for (vpn, frame) in area.data_frames.iter() {
+                    memory_set.page_table.map(*vpn, frame.ppn, pte_flags);
+                    user_space.page_table.set_flags(*vpn, pte_flags);
+                    new_area.data_frames.insert(*vpn, Arc::clone(frame));
+                }
+                memory_set.areas.push(new_area);
+                continue;
+            }
             memory_set.push(new_area, None);
             // copy data from another space
             for vpn in area.vpn_range {
@@ -232,8 +287,294 @@ impl MemorySet {
                     .copy_from_slice(src_ppn.get_bytes_array());
             }
         }
+        // the parent's pages are read only now
+        flush_tlb();
         memory_set
     }
+    /// Resolve a page fault on `vpn`. Pages of lazy areas get a zeroed frame,
+    /// and a store to a page shared copy-on-write gets a private copy.
+    /// File pages have to be read in before, see `pending_file_page`.
+    /// `access` is one of R, W and X.
+    /// Return false if the access is not allowed at all, or if there is no
+    /// frame left for the page.
+    fn handle_page_fault(&mut self, vpn: VirtPageNum, access: MapPermission) -> bool {
+        let page_table = &mut self.page_table;
+        let area = match self.areas.iter_mut().find(|area| area.contains(vpn)) {
+            Some(area) => area,
+            None => return false,
+        };
+        if area.map_type != MapType::Framed || !area.map_perm.contains(access | MapPermission::U) {
+            return false;
+        }
+        let pte = match page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() => pte,
+            _ => {
+                if area.file_page_offset(vpn).is_some() {
+                    return false;
+                }
+                return area.map_one(page_table, vpn);
+            }
+        };
+        if access != MapPermission::W || pte.writable() {
+            // another hart has just resolved it, or the TLB entry was stale
+            // and is flushed on the way back to user mode
+            return true;
+        }
+        let pte_flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+        let frame = area.data_frames.get(&vpn).unwrap();
+        if Arc::strong_count(frame) == 1 {
+            // the other sharers are gone, take the frame over
+            page_table.set_flags(vpn, pte_flags);
+        } else {
+            let new_frame = match frame_alloc() {
+                Some(frame) => frame,
+                None => return false,
+            };
+            new_frame
+                .ppn
+                .get_bytes_array()
+                .copy_from_slice(pte.ppn().get_bytes_array());
+            page_table.remap(vpn, new_frame.ppn, pte_flags);
+            area.data_frames.insert(vpn, Arc::new(new_frame));
+        }
+        flush_tlb();
+        true
+    }
+    /// Where to read untouched page `vpn` from if it is backed by a file and
+    /// allows `access`: the file and the offset of the page in the area.
+    /// Reading is file I/O, so it is left to the caller, which must release
+    /// the process for it, see `MapFile::read_page` and `map_file_page`.
+    pub fn pending_file_page(
+        &self,
+        vpn: VirtPageNum,
+        access: MapPermission,
+    ) -> Option<(MapFile, usize)> {
+        if self
+            .page_table
+            .translate(vpn)
+            .map_or(false, |pte| pte.is_valid())
+        {
+            return None;
+        }
+        let area = self.areas.iter().find(|area| area.contains(vpn))?;
+        if !area.accessible() || !area.map_perm.contains(access | MapPermission::U) {
+            return None;
+        }
+        let page_offset = area.file_page_offset(vpn)?;
+        Some((area.file.clone()?, page_offset))
+    }
+    /// Map `frame`, read by `map_file.read_page(page_offset)` for `vpn`,
+    /// unless the page was mapped or the area replaced in the meantime.
+    pub fn map_file_page(
+        &mut self,
+        vpn: VirtPageNum,
+        map_file: &MapFile,
+        page_offset: usize,
+        frame: FrameTracker,
+    ) {
+        if self
+            .page_table
+            .translate(vpn)
+            .map_or(false, |pte| pte.is_valid())
+        {
+            return;
+        }
+        let area = match self.areas.iter_mut().find(|area| area.contains(vpn)) {
+            Some(area) => area,
+            None => return,
+        };
+        let same_page = match (area.file.as_ref(), area.file_page_offset(vpn)) {
+            (Some(area_file), Some(area_offset)) => {
+                area_file.same_file(map_file)
+                    && area_file.offset + area_offset == map_file.offset + page_offset
+            }
+            _ => false,
+        };
+        if same_page {
+            area.map_frame(&mut self.page_table, vpn, frame);
+        }
+    }
+    /// The frame of user page `vpn` if it allows `access`, faulted in first
+    /// if it was never touched or is still shared copy-on-write. The frame
+    /// stays valid for whoever holds it, even after the page is unmapped.
+    pub fn user_frame(
+        &mut self,
+        vpn: VirtPageNum,
+        access: MapPermission,
+    ) -> Option<Arc<FrameTracker>> {
+        let present = self.page_table.translate(vpn).map_or(false, |pte| {
+            pte.is_valid()
+                && pte.flags().contains(PTEFlags::U)
+                && pte.readable()
+                && (access != MapPermission::W || pte.writable())
+        });
+        if !present && !self.handle_page_fault(vpn, access) {
+            return None;
+        }
+        let area = self.areas.iter().find(|area| area.contains(vpn))?;
+        area.data_frames.get(&vpn).cloned()
+    }
+    /// Grow the area starting at `start_va` so that it ends at `new_end`.
+    /// Fail if the new pages would run into another area, e.g. a user stack.
+    pub fn append_to(&mut self, start_va: VirtAddr, new_end: VirtAddr) -> bool {
+        let start_vpn = start_va.floor();
+        let new_end_vpn = new_end.ceil();
+        let end_vpn = match self
+            .areas
+            .iter()
+            .find(|area| area.vpn_range.get_start() == start_vpn)
+        {
+            Some(area) => area.vpn_range.get_end(),
+            None => return false,
+        };
+        if new_end_vpn <= end_vpn {
+            return true;
+        }
+        if !self.is_free(end_vpn, new_end_vpn.0 - end_vpn.0) {
+            return false;
+        }
+        let page_table = &mut self.page_table;
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() == start_vpn)
+            .unwrap();
+        area.append_to(page_table, new_end_vpn);
+        true
+    }
+    /// Shrink the area starting at `start_va` so that it ends at `new_end`.
+    pub fn shrink_to(&mut self, start_va: VirtAddr, new_end: VirtAddr) -> bool {
+        let start_vpn = start_va.floor();
+        let page_table = &mut self.page_table;
+        match self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() == start_vpn)
+        {
+            Some(area) => {
+                area.shrink_to(page_table, new_end.ceil());
+                flush_tlb();
+                true
+            }
+            None => false,
+        }
+    }
+    /// Map `len` bytes of fresh memory, backed by `file` if given together
+    /// with its pages, see `MapFile::load`. Fail without changing anything
+    /// if out of memory.
+    /// `start` is only a hint unless `fixed` is set, in which case whatever
+    /// was mapped there before is unmapped first.
+    /// Return the start address of the new area and the areas it replaced,
+    /// see `munmap`.
+    pub fn mmap(
+        &mut self,
+        start: usize,
+        len: usize,
+        perm: MapPermission,
+        shared: bool,
+        file: Option<(MapFile, Vec<FrameTracker>)>,
+        fixed: bool,
+    ) -> Option<(usize, Vec<MapArea>)> {
+        if len == 0 || start % PAGE_SIZE != 0 {
+            return None;
+        }
+        let page_count = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+        let (file, pages) = match file {
+            Some((map_file, pages)) => (Some(map_file), pages),
+            // shared pages must exist before a fork to be seen by both sides
+            None if shared => (
+                None,
+                (0..page_count)
+                    .map(|_| frame_alloc())
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+            None => (None, Vec::new()),
+        };
+        let mut unmapped = Vec::new();
+        let start_vpn = if fixed {
+            if start == 0 || start.checked_add(len)? > USER_SPACE_END {
+                return None;
+            }
+            let start_vpn = VirtAddr::from(start).floor();
+            unmapped = self.munmap(start_vpn, VirtPageNum(start_vpn.0 + page_count));
+            start_vpn
+        } else {
+            let hint = VirtAddr::from(start).floor();
+            if start != 0 && self.is_free(hint, page_count) {
+                hint
+            } else {
+                self.find_free_area(page_count)?
+            }
+        };
+        let end_vpn = VirtPageNum(start_vpn.0 + page_count);
+        let mut map_area = MapArea::new(start_vpn.into(), end_vpn.into(), MapType::Framed, perm);
+        map_area.shared = shared;
+        map_area.file = file;
+        for (vpn, frame) in VPNRange::new(start_vpn, end_vpn).into_iter().zip(pages) {
+            map_area.map_frame(&mut self.page_table, vpn, frame);
+        }
+        self.areas.push(map_area);
+        Some((VirtAddr::from(start_vpn).into(), unmapped))
+    }
+    /// Unmap user pages in `[start_vpn, end_vpn)`, splitting the areas
+    /// which are only partly covered. The areas taken out keep their frames
+    /// until dropped, which writes MAP_SHARED file pages back and so has to
+    /// wait until the process is released.
+    pub fn munmap(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> Vec<MapArea> {
+        let mut unmapped = Vec::new();
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            let area = &self.areas[idx];
+            let area_start = area.vpn_range.get_start();
+            let area_end = area.vpn_range.get_end();
+            if !area.map_perm.contains(MapPermission::U)
+                || area_end <= start_vpn
+                || end_vpn <= area_start
+            {
+                idx += 1;
+                continue;
+            }
+            let mut area = self.areas.remove(idx);
+            if area_start < start_vpn {
+                let tail = area.split_off(start_vpn);
+                self.areas.insert(idx, area);
+                idx += 1;
+                area = tail;
+            }
+            if end_vpn < area_end {
+                let tail = area.split_off(end_vpn);
+                self.areas.insert(idx, tail);
+                idx += 1;
+            }
+            area.detach(&mut self.page_table);
+            unmapped.push(area);
+        }
+        flush_tlb();
+        unmapped
+    }
+    fn is_free(&self, start_vpn: VirtPageNum, page_count: usize) -> bool {
+        let end_vpn = VirtPageNum(start_vpn.0 + page_count);
+        VirtAddr::from(end_vpn).0 <= USER_SPACE_END
+            && self.areas.iter().all(|area| {
+                area.vpn_range.get_end() <= start_vpn || end_vpn <= area.vpn_range.get_start()
+            })
+    }
+    /// First fit above `MMAP_BASE`.
+    fn find_free_area(&self, page_count: usize) -> Option<VirtPageNum> {
+        let mut start_vpn = VirtAddr::from(MMAP_BASE).floor();
+        loop {
+            let end_vpn = VirtPageNum(start_vpn.0 + page_count);
+            if VirtAddr::from(end_vpn).0 > USER_SPACE_END {
+                return None;
+            }
+            match self.areas.iter().find(|area| {
+                area.vpn_range.get_start() < end_vpn && start_vpn < area.vpn_range.get_end()
+            }) {
+                Some(area) => start_vpn = area.vpn_range.get_end(),
+                None => return Some(start_vpn),
+            }
+        }
+    }
     pub fn activate(&self) {
         let satp = self.page_table.token();
         unsafe {
@@ -244,17 +585,114 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
-    pub fn recycle_data_pages(&mut self) {
+    /// Take all areas out, to be dropped once the process is released,
+    /// see `munmap`.
+    pub fn recycle_data_pages(&mut self) -> Vec<MapArea> {
         //*self = Self::new_bare();
-        self.areas.clear();
+        core::mem::take(&mut self.areas)
+    }
+    /// Areas user code can access, in the order they were mapped.
+    pub fn user_areas(&self) -> Vec<AreaInfo> {
+        self.areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+            .map(|area| AreaInfo {
+                start: area.vpn_range.get_start().into(),
+                end: area.vpn_range.get_end().into(),
+                perm: area.map_perm,
+                shared: area.shared,
+                file: area.file.as_ref().map(|map_file| {
+                    let name = match map_file.file.dentry() {
+                        Some(dentry) => dentry.path(),
+                        None => map_file.file.name(),
+                    };
+                    (name, map_file.offset)
+                }),
+                resident: area.data_frames.len(),
+            })
+            .collect()
+    }
+}
+
+/// A user area as shown in /proc/<pid>/maps.
+pub struct AreaInfo {
+    pub start: VirtAddr,
+    pub end: VirtAddr,
+    pub perm: MapPermission,
+    pub shared: bool,
+    /// Path of the file behind the area and the offset of its first page.
+    pub file: Option<(String, usize)>,
+    /// Pages which already have a frame.
+    pub resident: usize,
+}
+
+/// The file behind an mmap area.
+#[derive(Clone)]
+pub struct MapFile {
+    file: Arc<dyn File + Send + Sync>,
+    /// File offset of the first page of the area.
+    offset: usize,
+    /// Bytes of the area backed by the file, the rest is zero-filled
+    /// and never written back.
+    len: usize,
+}
+
+impl MapFile {
+    pub fn new(file: Arc<dyn File + Send + Sync>, offset: usize) -> Self {
+        Self {
+            file,
+            offset,
+            len: 0,
+        }
+    }
+    /// Read the page at `page_offset` of the area into a fresh frame,
+    /// zero-filled past the part the file backs. This is file I/O, so the
+    /// process must not be held. Return None if out of memory.
+    pub fn read_page(&self, page_offset: usize) -> Option<FrameTracker> {
+        let frame = frame_alloc()?;
+        let page_len = PAGE_SIZE.min(self.len.saturating_sub(page_offset));
+        self.file.read_at(
+            self.offset + page_offset,
+            &mut frame.ppn.get_bytes_array()[..page_len],
+        );
+        Some(frame)
+    }
+    fn same_file(&self, other: &MapFile) -> bool {
+        Arc::as_ptr(&self.file) as *const () == Arc::as_ptr(&other.file) as *const ()
+    }
+    /// Read the first `len` bytes of the area into fresh frames, one for
+    /// each page, zero-filled past the end of the file. This is file I/O,
+    /// so the process must not be held. Return None if out of memory.
+    pub fn load(&mut self, len: usize) -> Option<Vec<FrameTracker>> {
+        let page_count = len.checked_add(PAGE_SIZE - 1)? / PAGE_SIZE;
+        let mut pages = Vec::new();
+        let mut eof = false;
+        self.len = 0;
+        for page in 0..page_count {
+            let frame = frame_alloc()?;
+            if !eof {
+                let page_offset = page * PAGE_SIZE;
+                let page_len = PAGE_SIZE.min(len - page_offset);
+                let dst = &mut frame.ppn.get_bytes_array()[..page_len];
+                let read_size = self.file.read_at(self.offset + page_offset, dst);
+                self.len += read_size;
+                eof = read_size < page_len;
+            }
+            pages.push(frame);
+        }
+        Some(pages)
     }
 }
 
 pub struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// Frames are shared with forked children instead of copied on write,
+    /// and written back to `file` if there is one.
+    shared: bool,
+    file: Option<MapFile>,
 }
 
 impl MapArea {
@@ -271,6 +709,8 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            shared: false,
+            file: None,
         }
     }
     pub fn from_another(another: &MapArea) -> Self {
@@ -279,35 +719,151 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            shared: another.shared,
+            file: another.file.clone(),
+        }
+    }
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+    /// Flags for frames shared between address spaces, never writable
+    /// unless the area is MAP_SHARED.
+    fn shared_pte_flags(&self) -> PTEFlags {
+        if self.shared {
+            PTEFlags::from_bits(self.map_perm.bits).unwrap()
+        } else {
+            PTEFlags::from_bits((self.map_perm - MapPermission::W).bits).unwrap()
+        }
+    }
+    pub fn append_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        if !self.is_lazy() {
+            for vpn in VPNRange::new(self.vpn_range.get_end(), new_end) {
+                if !self.map_one(page_table, vpn) {
+                    panic!("out of memory for a kernel area");
+                }
+            }
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+    }
+    pub fn shrink_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        for vpn in VPNRange::new(new_end, self.vpn_range.get_end()) {
+            self.unmap_one(page_table, vpn);
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+    }
+    /// Split the area at `at`, keep `[start, at)` and return `[at, end)`.
+    pub fn split_off(&mut self, at: VirtPageNum) -> MapArea {
+        let start = self.vpn_range.get_start();
+        let end = self.vpn_range.get_end();
+        assert!(start < at && at < end);
+        let head_len = (at.0 - start.0) * PAGE_SIZE;
+        let file = self.file.as_mut().map(|head| {
+            let tail = MapFile {
+                file: Arc::clone(&head.file),
+                offset: head.offset + head_len,
+                len: head.len.saturating_sub(head_len),
+            };
+            head.len = head.len.min(head_len);
+            tail
+        });
+        self.vpn_range = VPNRange::new(start, at);
+        MapArea {
+            vpn_range: VPNRange::new(at, end),
+            data_frames: self.data_frames.split_off(&at),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            shared: self.shared,
+            file,
         }
     }
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+    /// Pages of user areas are only given frames when first touched,
+    /// see `MemorySet::handle_page_fault`.
+    fn is_lazy(&self) -> bool {
+        self.map_type == MapType::Framed && self.map_perm.contains(MapPermission::U)
+    }
+    /// Offset of `vpn` in the area if the file backs part of its page.
+    fn file_page_offset(&self, vpn: VirtPageNum) -> Option<usize> {
+        let page_offset = (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+        match self.file.as_ref() {
+            Some(map_file) if page_offset < map_file.len => Some(page_offset),
+            _ => None,
+        }
+    }
+    /// Write the frames of a MAP_SHARED file mapping back to the file.
+    fn sync_file(&self) {
+        let map_file = match self.file.as_ref() {
+            Some(map_file) if self.shared && self.map_perm.contains(MapPermission::W) => map_file,
+            _ => return,
+        };
+        for (vpn, frame) in self.data_frames.iter() {
+            let page_offset = (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            if page_offset >= map_file.len {
+                break;
+            }
+            let page_len = PAGE_SIZE.min(map_file.len - page_offset);
+            map_file.file.write_at(
+                map_file.offset + page_offset,
+                &frame.ppn.get_bytes_array()[..page_len],
+            );
+        }
+    }
+    /// PROT_NONE areas get no PTEs, one without RWX would be taken as a
+    /// pointer.
+    fn accessible(&self) -> bool {
+        self.map_perm
+            .intersects(MapPermission::R | MapPermission::W | MapPermission::X)
+    }
+    /// Return false if out of memory.
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if !self.accessible() {
+            return true;
+        }
         let ppn: PhysPageNum;
         match self.map_type {
             MapType::Identical => {
                 ppn = PhysPageNum(vpn.0);
             }
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
+                let frame = match frame_alloc() {
+                    Some(frame) => frame,
+                    None => return false,
+                };
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
             }
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         page_table.map(vpn, ppn, pte_flags);
+        true
+    }
+    /// Map `vpn` to `frame`, which holds the page already.
+    fn map_frame(&mut self, page_table: &mut PageTable, vpn: VirtPageNum, frame: FrameTracker) {
+        if !self.accessible() {
+            return;
+        }
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        page_table.map(vpn, frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, Arc::new(frame));
     }
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         match self.map_type {
             MapType::Framed => {
-                self.data_frames.remove(&vpn);
+                if self.data_frames.remove(&vpn).is_none() {
+                    return;
+                }
             }
             _ => {}
         }
         page_table.unmap(vpn);
     }
     pub fn map(&mut self, page_table: &mut PageTable) {
+        if self.is_lazy() {
+            return;
+        }
         for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+            if !self.map_one(page_table, vpn) {
+                panic!("out of memory for a kernel area");
+            }
         }
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
@@ -315,6 +871,13 @@ impl MapArea {
             self.unmap_one(page_table, vpn);
         }
     }
+    /// Take the pages out of `page_table` but keep the frames, a MAP_SHARED
+    /// file area still writes them back when dropped.
+    fn detach(&mut self, page_table: &mut PageTable) {
+        for vpn in self.data_frames.keys() {
+            page_table.unmap(*vpn);
+        }
+    }
     /// data: start-aligned but maybe with shorter length
     /// assume that all frames were cleared before
     pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
@@ -339,6 +902,14 @@ impl MapArea {
     }
 }
 
+/// Write MAP_SHARED file pages back. That is file I/O, so areas of a user
+/// space are only dropped with the process released.
+impl Drop for MapArea {
+    fn drop(&mut self) {
+        self.sync_file();
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum MapType {
     Identical,
@@ -356,7 +927,7 @@ bitflags! {
 
 #[allow(unused)]
 pub fn remap_test() {
-    let mut kernel_space = KERNEL_SPACE.inner.borrow_mut();
+    let mut kernel_space = KERNEL_SPACE.lock();
     let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
     let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
     let mid_data: VirtAddr = ((sdata as usize + edata as usize) / 2).into();
@@ -6,13 +6,13 @@ mod page_table;
 
 use address::VPNRange;
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
-pub use frame_allocator::{frame_alloc, frame_dealloc, FrameTracker};
+pub use frame_allocator::{frame_alloc, frame_dealloc, frame_stats, FrameTracker};
 pub use memory_set::remap_test;
-pub use memory_set::{kernel_token, MapPermission, MemorySet, KERNEL_SPACE};
+pub use memory_set::{kernel_token, AreaInfo, MapFile, MapPermission, MemorySet, KERNEL_SPACE};
 use page_table::PTEFlags;
 pub use page_table::{
-    translated_byte_buffer, translated_ref, translated_refmut, translated_str, PageTable,
-    PageTableEntry, UserBuffer, UserBufferIterator,
+    copy_bytes_from_user, copy_bytes_to_user, copy_from_user, copy_str_from_user, copy_to_user,
+    translate_user, user_byte_buffer, PageTable, PageTableEntry, UserBuffer, UserBufferIterator,
 };
 
 use crate::task;
@@ -21,8 +21,8 @@ pub fn init() {
     heap_allocator::init_heap();
     task::init_hart();
     frame_allocator::init_frame_allocator();
-    KERNEL_SPACE.inner.borrow_mut().activate();
+    KERNEL_SPACE.lock().activate();
 }
 pub fn activate() {
-    KERNEL_SPACE.inner.borrow_mut().activate();
+    KERNEL_SPACE.lock().activate();
 }
@@ -1,10 +1,18 @@
-use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use super::{
+    frame_alloc, FrameTracker, MapPermission, PhysAddr, PhysPageNum, StepByOne, VirtAddr,
+    VirtPageNum,
+};
 
+use crate::config::{PATH_MAX, USER_SPACE_END};
+use crate::error::{SysError, SysResult};
+use crate::task::current_process;
 use _core::ops::{Index, IndexMut};
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::*;
+use core::mem::{size_of, MaybeUninit};
 
 bitflags! {
     pub struct PTEFlags: u8 {
@@ -123,6 +131,22 @@ impl PageTable {
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
     }
+    /// Change the flags of a mapped page, keeping its frame.
+    pub fn set_flags(&mut self, vpn: VirtPageNum, flags: PTEFlags) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(
+            pte.is_valid(),
+            "vpn {:?} is invalid before setting flags",
+            vpn
+        );
+        *pte = PageTableEntry::new(pte.ppn(), flags | PTEFlags::V);
+    }
+    /// Point a mapped page to another frame.
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before remapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).map(|pte| pte.clone())
     }
@@ -139,71 +163,156 @@ impl PageTable {
     }
 }
 
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
-    let page_table = PageTable::from_token(token);
+/// The frame of user page `vpn` of the current process if `token` is its
+/// space, see `MemorySet::user_frame`. An untouched file page is read in
+/// first, with the process released. This waits for the process, so the
+/// caller must not hold any spin lock. Page faults of user code come here
+/// as well.
+pub fn translate_user(
+    token: usize,
+    vpn: VirtPageNum,
+    access: MapPermission,
+) -> Option<Arc<FrameTracker>> {
+    let process = current_process();
+    let mut read = None;
+    loop {
+        let mut inner = process.inner_exclusive_access();
+        if inner.memory_set.token() != token {
+            return None;
+        }
+        if let Some((map_file, page_offset, frame)) = read.take() {
+            inner
+                .memory_set
+                .map_file_page(vpn, &map_file, page_offset, frame);
+        }
+        let (map_file, page_offset) = match inner.memory_set.pending_file_page(vpn, access) {
+            Some(pending) => pending,
+            None => return inner.memory_set.user_frame(vpn, access),
+        };
+        drop(inner);
+        let frame = map_file.read_page(page_offset)?;
+        read = Some((map_file, page_offset, frame));
+    }
+}
+
+/// Slices of the user range `[ptr, ptr + len)` of the current process. Every
+/// page of it has to be user memory allowing `access`, otherwise it is EFAULT.
+/// The buffer holds the frames, so they stay valid while the caller sleeps
+/// even if another thread unmaps the range.
+pub fn user_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+    access: MapPermission,
+) -> SysResult<UserBuffer> {
     let mut start = ptr as usize;
-    let end = start + len;
-    let mut v = Vec::new();
+    let end = match start.checked_add(len) {
+        Some(end) if end <= USER_SPACE_END => end,
+        _ => return Err(SysError::EFAULT),
+    };
+    let mut buffer = UserBuffer::new(Vec::new());
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
+        let frame = translate_user(token, vpn, access).ok_or(SysError::EFAULT)?;
+        let ppn = frame.ppn;
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
         if end_va.page_offset() == 0 {
-            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..]);
+            buffer
+                .buffers
+                .push(&mut ppn.get_bytes_array()[start_va.page_offset()..]);
         } else {
-            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..end_va.page_offset()]);
+            buffer
+                .buffers
+                .push(&mut ppn.get_bytes_array()[start_va.page_offset()..end_va.page_offset()]);
         }
+        buffer.frames.push(frame);
         start = end_va.into();
     }
-    v
+    Ok(buffer)
 }
 
-/// Load a string from other address spaces into kernel space without an end `\0`.
-pub fn translated_str(token: usize, ptr: *const u8) -> String {
-    let page_table = PageTable::from_token(token);
-    let mut string = String::new();
-    let mut va = ptr as usize;
-    loop {
-        let ch: u8 = *(page_table
-            .translate_va(VirtAddr::from(va))
-            .unwrap()
-            .get_mut());
-        if ch == 0 {
-            break;
-        }
-        string.push(ch as char);
-        va += 1;
+pub fn copy_bytes_from_user(token: usize, src: *const u8, dst: &mut [u8]) -> SysResult<()> {
+    let mut copied = 0;
+    for slice in user_byte_buffer(token, src, dst.len(), MapPermission::R)?.buffers {
+        dst[copied..copied + slice.len()].copy_from_slice(slice);
+        copied += slice.len();
+    }
+    Ok(())
+}
+
+pub fn copy_bytes_to_user(token: usize, dst: *mut u8, src: &[u8]) -> SysResult<()> {
+    let mut copied = 0;
+    for slice in user_byte_buffer(token, dst, src.len(), MapPermission::W)?.buffers {
+        slice.copy_from_slice(&src[copied..copied + slice.len()]);
+        copied += slice.len();
     }
-    string
+    Ok(())
 }
 
-pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
-    let page_table = PageTable::from_token(token);
-    page_table
-        .translate_va(VirtAddr::from(ptr as usize))
-        .unwrap()
-        .get_ref()
+/// Read a `T` from user space, which need not be aligned.
+pub fn copy_from_user<T: Copy>(token: usize, src: *const T) -> SysResult<T> {
+    let mut value = MaybeUninit::<T>::uninit();
+    let dst =
+        unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    copy_bytes_from_user(token, src as *const u8, dst)?;
+    Ok(unsafe { value.assume_init() })
 }
 
-pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
-    let page_table = PageTable::from_token(token);
-    let va = ptr as usize;
-    page_table
-        .translate_va(VirtAddr::from(va))
-        .unwrap()
-        .get_mut()
+pub fn copy_to_user<T>(token: usize, dst: *mut T, value: &T) -> SysResult<()> {
+    let src =
+        unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    copy_bytes_to_user(token, dst as *mut u8, src)
+}
+
+/// Load a `\0` terminated string from user space without the `\0`. It has
+/// to end within `PATH_MAX` bytes.
+pub fn copy_str_from_user(token: usize, src: *const u8) -> SysResult<String> {
+    let mut bytes = Vec::new();
+    let mut va = src as usize;
+    loop {
+        if bytes.len() == PATH_MAX {
+            return Err(SysError::ENAMETOOLONG);
+        }
+        if va >= USER_SPACE_END {
+            return Err(SysError::EFAULT);
+        }
+        let page_va = VirtAddr::from(va);
+        let frame =
+            translate_user(token, page_va.floor(), MapPermission::R).ok_or(SysError::EFAULT)?;
+        let page = frame.ppn.get_bytes_array();
+        for &ch in &page[page_va.page_offset()..] {
+            if ch == 0 {
+                return String::from_utf8(bytes).map_err(|_| SysError::EINVAL);
+            }
+            bytes.push(ch);
+            va += 1;
+            if bytes.len() == PATH_MAX {
+                break;
+            }
+        }
+    }
 }
 
 pub struct UserBuffer {
     pub buffers: Vec<&'static mut [u8]>,
+    /// Frames behind `buffers` if they are user memory.
+    frames: Vec<Arc<FrameTracker>>,
 }
 
 impl UserBuffer {
     pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
-        Self { buffers }
+        Self {
+            buffers,
+            frames: Vec::new(),
+        }
+    }
+    /// Put the slices of `other` after those of `self`.
+    pub fn append(&mut self, mut other: UserBuffer) {
+        self.buffers.append(&mut other.buffers);
+        self.frames.append(&mut other.frames);
     }
     pub fn len(&self) -> usize {
         let mut total: usize = 0;
@@ -220,6 +329,7 @@ impl IntoIterator for UserBuffer {
     fn into_iter(self) -> Self::IntoIter {
         UserBufferIterator {
             buffers: self.buffers,
+            _frames: self.frames,
             current_buffer: 0,
             current_idx: 0,
         }
@@ -228,6 +338,7 @@ impl IntoIterator for UserBuffer {
 
 pub struct UserBufferIterator {
     buffers: Vec<&'static mut [u8]>,
+    _frames: Vec<Arc<FrameTracker>>,
     current_buffer: usize,
     current_idx: usize,
 }
@@ -0,0 +1,21 @@
+mod unix;
+
+pub use unix::{SocketNode, SocketType, UnixSocket};
+
+/// The only address family, sockets named by filesystem paths.
+pub const AF_UNIX: usize = 1;
+
+bitflags! {
+    /// The `flags` of `sendto` and `recvfrom`.
+    pub struct MsgFlags: u32 {
+        /// Fail with EAGAIN instead of blocking, as O_NONBLOCK does.
+        const DONTWAIT = 0x40;
+        /// Fail with EPIPE but raise no SIGPIPE.
+        const NOSIGNAL = 0x4000;
+    }
+}
+
+/// The `how` of `shutdown`.
+pub const SHUT_RD: usize = 0;
+pub const SHUT_WR: usize = 1;
+pub const SHUT_RDWR: usize = 2;
@@ -0,0 +1,591 @@
+use super::{MsgFlags, SHUT_RD, SHUT_RDWR, SHUT_WR};
+use crate::config::PAGE_SIZE;
+use crate::error::{SysError, SysResult};
+use crate::fs::{lookup, mknod, Dentry, File, InodeType, Kstat, OpenFlags, PollEvents};
+use crate::mm::UserBuffer;
+use crate::sync::{SpinMutex, SpinMutexGuard, WaitQueue};
+use crate::task::{
+    block_current_interruptible, current_add_signal, current_task, mark_current_blocked,
+    SignalFlags,
+};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::mem;
+
+/// Bytes a stream socket holds before its peer has to wait.
+const STREAM_BUF_SIZE: usize = 16 * PAGE_SIZE;
+/// Datagrams a socket holds before senders have to wait.
+const DGRAM_QUEUE_LEN: usize = 64;
+const DGRAM_MAX_SIZE: usize = STREAM_BUF_SIZE;
+/// The largest backlog of `listen`.
+const SOMAXCONN: usize = 128;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SocketType {
+    Stream,
+    Datagram,
+}
+
+enum State {
+    Unconnected,
+    /// Taking connections, their server ends wait in `pending` for `accept`.
+    Listening {
+        backlog: usize,
+        pending: VecDeque<Arc<UnixSocket>>,
+    },
+    /// A stream connected to its peer, or a datagram socket with a default
+    /// destination.
+    Connected(Arc<SocketShared>),
+    Closed,
+}
+
+struct SocketInner {
+    state: State,
+    /// The path bound to, which is the address of the socket.
+    path: Option<String>,
+    /// Bytes a stream socket received.
+    stream: VecDeque<u8>,
+    /// Datagrams received, each with the address of its sender.
+    datagrams: VecDeque<(Option<String>, Vec<u8>)>,
+    /// Nothing more arrives: the stream peer shut down writing or is gone.
+    eof: bool,
+    shut_read: bool,
+    shut_write: bool,
+}
+
+/// What peers and bound paths refer to. It outlives the open socket, which
+/// only leaves `State::Closed` behind.
+struct SocketShared {
+    socket_type: SocketType,
+    inner: SpinMutex<SocketInner>,
+    /// Woken when something arrives, a connection comes in, room frees up
+    /// or the peer goes. Senders wait here on the socket they send to.
+    wait: WaitQueue,
+}
+
+impl SocketShared {
+    fn new(socket_type: SocketType, state: State, path: Option<String>) -> Arc<Self> {
+        Arc::new(Self {
+            socket_type,
+            inner: SpinMutex::new(SocketInner {
+                state,
+                path,
+                stream: VecDeque::new(),
+                datagrams: VecDeque::new(),
+                eof: false,
+                shut_read: false,
+                shut_write: false,
+            }),
+            wait: WaitQueue::new(),
+        })
+    }
+
+    /// Sleep on `wait` with `inner` unlocked, then lock it again. The task
+    /// is queued before the lock goes, so no wakeup is missed. A signal
+    /// makes it EINTR.
+    fn sleep<'a>(
+        &'a self,
+        inner: SpinMutexGuard<'a, SocketInner>,
+    ) -> SysResult<SpinMutexGuard<'a, SocketInner>> {
+        let task = current_task().unwrap();
+        mark_current_blocked();
+        self.wait.register(&task);
+        drop(inner);
+        if let Err(err) = block_current_interruptible() {
+            self.wait.unregister(&task);
+            return Err(err);
+        }
+        Ok(self.inner.lock())
+    }
+
+    fn closed(inner: &SocketInner) -> bool {
+        inner.shut_read || matches!(inner.state, State::Closed)
+    }
+}
+
+/// The socket bound to a path, kept by its node in the filesystem.
+pub struct SocketNode {
+    socket: SpinMutex<Weak<SocketShared>>,
+}
+
+impl SocketNode {
+    pub fn new() -> Self {
+        Self {
+            socket: SpinMutex::new(Weak::new()),
+        }
+    }
+}
+
+/// The socket bound to `path`.
+fn lookup_socket(start: &Arc<Dentry>, path: &str) -> SysResult<Arc<SocketShared>> {
+    let dentry = lookup(start, path)?;
+    let node = dentry.inode().socket().ok_or(SysError::ECONNREFUSED)?;
+    let socket = node.socket.lock().upgrade();
+    socket.ok_or(SysError::ECONNREFUSED)
+}
+
+/// An AF_UNIX socket. Once it is closed its stream peer reads EOF and gets
+/// EPIPE for writes, others sending to it get ECONNREFUSED.
+pub struct UnixSocket {
+    shared: Arc<SocketShared>,
+    flags: SpinMutex<OpenFlags>,
+}
+
+impl UnixSocket {
+    pub fn new(socket_type: SocketType, flags: OpenFlags) -> Arc<Self> {
+        let shared = SocketShared::new(socket_type, State::Unconnected, None);
+        Self::with_shared(shared, flags)
+    }
+
+    fn with_shared(shared: Arc<SocketShared>, flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            shared,
+            flags: SpinMutex::new(flags.status()),
+        })
+    }
+
+    /// Two sockets connected to each other.
+    pub fn pair(socket_type: SocketType, flags: OpenFlags) -> (Arc<Self>, Arc<Self>) {
+        let first = SocketShared::new(socket_type, State::Unconnected, None);
+        let second = SocketShared::new(socket_type, State::Connected(Arc::clone(&first)), None);
+        first.inner.lock().state = State::Connected(Arc::clone(&second));
+        (
+            Self::with_shared(first, flags),
+            Self::with_shared(second, flags),
+        )
+    }
+
+    pub fn socket_type(&self) -> SocketType {
+        self.shared.socket_type
+    }
+
+    fn nonblocking(&self, flags: MsgFlags) -> bool {
+        flags.contains(MsgFlags::DONTWAIT) || self.flags.lock().contains(OpenFlags::NONBLOCK)
+    }
+
+    fn peer(&self) -> Option<Arc<SocketShared>> {
+        match &self.shared.inner.lock().state {
+            State::Connected(peer) => Some(Arc::clone(peer)),
+            _ => None,
+        }
+    }
+
+    /// The address of the socket, None while it is unbound.
+    pub fn path(&self) -> Option<String> {
+        self.shared.inner.lock().path.clone()
+    }
+
+    /// The address of the peer.
+    pub fn peer_path(&self) -> SysResult<Option<String>> {
+        let peer = self.peer().ok_or(SysError::ENOTCONN)?;
+        let path = peer.inner.lock().path.clone();
+        Ok(path)
+    }
+
+    /// Bind the socket to a new node at `path`, which stays after the
+    /// socket is closed until it is unlinked.
+    pub fn bind(&self, start: &Arc<Dentry>, path: &str) -> SysResult<()> {
+        if self.shared.inner.lock().path.is_some() {
+            return Err(SysError::EINVAL);
+        }
+        let dentry = mknod(start, path, InodeType::Socket).map_err(|err| match err {
+            SysError::EEXIST => SysError::EADDRINUSE,
+            err => err,
+        })?;
+        let node = dentry.inode().socket().ok_or(SysError::EPERM)?;
+        *node.socket.lock() = Arc::downgrade(&self.shared);
+        self.shared.inner.lock().path = Some(String::from(path));
+        Ok(())
+    }
+
+    /// Take connections on the bound path, `backlog` of them may wait.
+    pub fn listen(&self, backlog: usize) -> SysResult<()> {
+        if self.shared.socket_type != SocketType::Stream {
+            return Err(SysError::EOPNOTSUPP);
+        }
+        let backlog = backlog.clamp(1, SOMAXCONN);
+        let mut inner = self.shared.inner.lock();
+        if inner.path.is_none() {
+            return Err(SysError::EINVAL);
+        }
+        match &mut inner.state {
+            State::Listening {
+                backlog: current, ..
+            } => {
+                *current = backlog;
+                return Ok(());
+            }
+            State::Unconnected => {}
+            _ => return Err(SysError::EINVAL),
+        }
+        inner.state = State::Listening {
+            backlog,
+            pending: VecDeque::new(),
+        };
+        Ok(())
+    }
+
+    /// Take the next connection, waiting for one unless nonblocking.
+    pub fn accept(&self) -> SysResult<Arc<UnixSocket>> {
+        let mut inner = self.shared.inner.lock();
+        loop {
+            let pending = match &mut inner.state {
+                State::Listening { pending, .. } => pending,
+                _ => return Err(SysError::EINVAL),
+            };
+            if let Some(server) = pending.pop_front() {
+                drop(inner);
+                // connects may be waiting for room in the backlog
+                self.shared.wait.wake_all();
+                return Ok(server);
+            }
+            if self.nonblocking(MsgFlags::empty()) {
+                return Err(SysError::EAGAIN);
+            }
+            inner = self.shared.sleep(inner)?;
+        }
+    }
+
+    /// Connect to the socket bound to `path`. A stream waits while the
+    /// backlog of the listener is full, a datagram socket only takes the
+    /// path as the default destination.
+    pub fn connect(&self, start: &Arc<Dentry>, path: &str) -> SysResult<()> {
+        let target = lookup_socket(start, path)?;
+        if target.socket_type != self.shared.socket_type {
+            return Err(SysError::EPROTOTYPE);
+        }
+        if self.shared.socket_type == SocketType::Datagram {
+            self.shared.inner.lock().state = State::Connected(target);
+            return Ok(());
+        }
+        match self.shared.inner.lock().state {
+            State::Unconnected => {}
+            State::Connected(_) => return Err(SysError::EISCONN),
+            _ => return Err(SysError::EINVAL),
+        }
+        let mut target_inner = target.inner.lock();
+        let server = loop {
+            let path = target_inner.path.clone();
+            let (backlog, pending) = match &mut target_inner.state {
+                State::Listening { backlog, pending } => (*backlog, pending),
+                _ => return Err(SysError::ECONNREFUSED),
+            };
+            if pending.len() < backlog {
+                let state = State::Connected(Arc::clone(&self.shared));
+                let server = SocketShared::new(SocketType::Stream, state, path);
+                pending.push_back(Self::with_shared(Arc::clone(&server), OpenFlags::empty()));
+                break server;
+            }
+            if self.nonblocking(MsgFlags::empty()) {
+                return Err(SysError::EAGAIN);
+            }
+            target_inner = target.sleep(target_inner)?;
+        };
+        drop(target_inner);
+        target.wait.wake_all();
+        self.shared.inner.lock().state = State::Connected(server);
+        Ok(())
+    }
+
+    /// Send `buf` to the peer, or for a datagram socket to the one bound to
+    /// `dest` if given. A stream writes all of it unless nonblocking.
+    pub fn send(
+        &self,
+        buf: UserBuffer,
+        dest: Option<(&Arc<Dentry>, &str)>,
+        flags: MsgFlags,
+    ) -> SysResult<usize> {
+        let result = match self.shared.socket_type {
+            SocketType::Stream if dest.is_some() => Err(SysError::EISCONN),
+            SocketType::Stream => self.send_stream(buf, flags),
+            SocketType::Datagram => {
+                let target = match dest {
+                    Some((start, path)) => lookup_socket(start, path)?,
+                    None => self.peer().ok_or(SysError::ENOTCONN)?,
+                };
+                self.send_datagram(buf, &target, flags)
+            }
+        };
+        if result == Err(SysError::EPIPE) && !flags.contains(MsgFlags::NOSIGNAL) {
+            current_add_signal(SignalFlags::SIGPIPE);
+        }
+        result
+    }
+
+    fn send_stream(&self, buf: UserBuffer, flags: MsgFlags) -> SysResult<usize> {
+        if self.shared.inner.lock().shut_write {
+            return Err(SysError::EPIPE);
+        }
+        let peer = self.peer().ok_or(SysError::ENOTCONN)?;
+        let mut sent = 0usize;
+        let mut inner = peer.inner.lock();
+        for slice in buf.buffers.iter() {
+            let mut done = 0usize;
+            while done < slice.len() {
+                if SocketShared::closed(&inner) {
+                    return match sent {
+                        0 => Err(SysError::EPIPE),
+                        _ => Ok(sent),
+                    };
+                }
+                let room = STREAM_BUF_SIZE - inner.stream.len();
+                if room == 0 {
+                    // the peer may be waiting for what was sent so far
+                    peer.wait.wake_all();
+                    if self.nonblocking(flags) {
+                        return match sent {
+                            0 => Err(SysError::EAGAIN),
+                            _ => Ok(sent),
+                        };
+                    }
+                    inner = match peer.sleep(inner) {
+                        Ok(inner) => inner,
+                        Err(err) => {
+                            return match sent {
+                                0 => Err(err),
+                                _ => Ok(sent),
+                            }
+                        }
+                    };
+                    continue;
+                }
+                let len = room.min(slice.len() - done);
+                inner.stream.extend(&slice[done..done + len]);
+                done += len;
+                sent += len;
+            }
+        }
+        drop(inner);
+        peer.wait.wake_all();
+        Ok(sent)
+    }
+
+    fn send_datagram(
+        &self,
+        buf: UserBuffer,
+        target: &Arc<SocketShared>,
+        flags: MsgFlags,
+    ) -> SysResult<usize> {
+        if target.socket_type != SocketType::Datagram {
+            return Err(SysError::EPROTOTYPE);
+        }
+        if buf.len() > DGRAM_MAX_SIZE {
+            return Err(SysError::EMSGSIZE);
+        }
+        let sender = {
+            let inner = self.shared.inner.lock();
+            if inner.shut_write {
+                return Err(SysError::EPIPE);
+            }
+            inner.path.clone()
+        };
+        let mut data = Vec::with_capacity(buf.len());
+        for slice in buf.buffers.iter() {
+            data.extend_from_slice(slice);
+        }
+        let mut inner = target.inner.lock();
+        loop {
+            if SocketShared::closed(&inner) {
+                return Err(SysError::ECONNREFUSED);
+            }
+            if inner.datagrams.len() < DGRAM_QUEUE_LEN {
+                break;
+            }
+            if self.nonblocking(flags) {
+                return Err(SysError::EAGAIN);
+            }
+            inner = target.sleep(inner)?;
+        }
+        let len = data.len();
+        inner.datagrams.push_back((sender, data));
+        drop(inner);
+        target.wait.wake_all();
+        Ok(len)
+    }
+
+    /// Receive into `buf`, with the address of the sender for a datagram.
+    /// What does not fit of a datagram is dropped.
+    pub fn recv(&self, buf: UserBuffer, flags: MsgFlags) -> SysResult<(usize, Option<String>)> {
+        match self.shared.socket_type {
+            SocketType::Stream => Ok((self.recv_stream(buf, flags)?, None)),
+            SocketType::Datagram => self.recv_datagram(buf, flags),
+        }
+    }
+
+    fn recv_stream(&self, mut buf: UserBuffer, flags: MsgFlags) -> SysResult<usize> {
+        let mut inner = self.shared.inner.lock();
+        let peer = match &inner.state {
+            State::Connected(peer) => Arc::clone(peer),
+            _ => return Err(SysError::ENOTCONN),
+        };
+        while inner.stream.is_empty() && !inner.eof && !inner.shut_read && buf.len() > 0 {
+            if self.nonblocking(flags) {
+                return Err(SysError::EAGAIN);
+            }
+            inner = self.shared.sleep(inner)?;
+        }
+        let mut received = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let len = slice.len().min(inner.stream.len());
+            for (dst, src) in slice.iter_mut().zip(inner.stream.drain(..len)) {
+                *dst = src;
+            }
+            received += len;
+            if len < slice.len() {
+                break;
+            }
+        }
+        drop(inner);
+        // room for the senders waiting here, and for the poll of the peer
+        self.shared.wait.wake_all();
+        peer.wait.wake_all();
+        Ok(received)
+    }
+
+    fn recv_datagram(
+        &self,
+        mut buf: UserBuffer,
+        flags: MsgFlags,
+    ) -> SysResult<(usize, Option<String>)> {
+        let mut inner = self.shared.inner.lock();
+        while inner.datagrams.is_empty() && !inner.shut_read {
+            if self.nonblocking(flags) {
+                return Err(SysError::EAGAIN);
+            }
+            inner = self.shared.sleep(inner)?;
+        }
+        let (sender, data) = match inner.datagrams.pop_front() {
+            Some(datagram) => datagram,
+            None => return Ok((0, None)),
+        };
+        drop(inner);
+        self.shared.wait.wake_all();
+        let mut received = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let len = slice.len().min(data.len() - received);
+            slice[..len].copy_from_slice(&data[received..received + len]);
+            received += len;
+        }
+        Ok((received, sender))
+    }
+
+    /// Stop reading, writing or both. A stream peer reads EOF once writing
+    /// is shut down.
+    pub fn shutdown(&self, how: usize) -> SysResult<()> {
+        let (read, write) = match how {
+            SHUT_RD => (true, false),
+            SHUT_WR => (false, true),
+            SHUT_RDWR => (true, true),
+            _ => return Err(SysError::EINVAL),
+        };
+        let mut inner = self.shared.inner.lock();
+        let peer = match &inner.state {
+            State::Connected(peer) => Arc::clone(peer),
+            _ => return Err(SysError::ENOTCONN),
+        };
+        inner.shut_read |= read;
+        inner.shut_write |= write;
+        drop(inner);
+        self.shared.wait.wake_all();
+        if write && self.shared.socket_type == SocketType::Stream {
+            peer.inner.lock().eof = true;
+            peer.wait.wake_all();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let mut inner = self.shared.inner.lock();
+        let state = mem::replace(&mut inner.state, State::Closed);
+        inner.stream.clear();
+        inner.datagrams.clear();
+        drop(inner);
+        // senders waiting for room find the socket gone
+        self.shared.wait.wake_all();
+        if let State::Connected(peer) = &state {
+            if self.shared.socket_type == SocketType::Stream {
+                peer.inner.lock().eof = true;
+                peer.wait.wake_all();
+            }
+        }
+        // connections not accepted yet are closed along with it
+        drop(state);
+    }
+}
+
+impl File for UnixSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read(&self, buf: UserBuffer) -> SysResult<usize> {
+        self.recv(buf, MsgFlags::empty()).map(|(len, _)| len)
+    }
+    fn write(&self, buf: UserBuffer) -> SysResult<usize> {
+        self.send(buf, None, MsgFlags::empty())
+    }
+    fn kstat(&self, stat: &mut Kstat) {
+        *stat = Kstat::default();
+        stat.st_mode = InodeType::Socket.mode();
+        stat.st_nlink = 1;
+    }
+    fn name(&self) -> String {
+        String::from("socket:[unix]")
+    }
+    /// A listening socket is readable with a connection to accept, a
+    /// stream is writable while its peer has room.
+    fn poll(&self) -> PollEvents {
+        let inner = self.shared.inner.lock();
+        let mut events = PollEvents::empty();
+        let peer = match &inner.state {
+            State::Listening { pending, .. } => {
+                events.set(PollEvents::IN, !pending.is_empty());
+                return events;
+            }
+            State::Connected(peer) => Some(Arc::clone(peer)),
+            _ => None,
+        };
+        if self.shared.socket_type == SocketType::Datagram {
+            events.set(
+                PollEvents::IN,
+                !inner.datagrams.is_empty() || inner.shut_read,
+            );
+            events.set(PollEvents::OUT, !inner.shut_write);
+            return events;
+        }
+        events.set(
+            PollEvents::IN,
+            !inner.stream.is_empty() || inner.eof || inner.shut_read,
+        );
+        events.set(PollEvents::HUP, inner.eof);
+        let shut_write = inner.shut_write;
+        // never hold two sockets at once
+        drop(inner);
+        if let Some(peer) = peer {
+            let peer_inner = peer.inner.lock();
+            let closed = SocketShared::closed(&peer_inner);
+            let room = peer_inner.stream.len() < STREAM_BUF_SIZE;
+            events.set(PollEvents::OUT, !shut_write && !closed && room);
+            events.set(PollEvents::ERR, closed);
+        }
+        events
+    }
+    fn wait_queue(&self) -> Option<&WaitQueue> {
+        Some(&self.shared.wait)
+    }
+    fn status_flags(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        *self.flags.lock() = flags.status();
+    }
+    fn socket(&self) -> Option<&UnixSocket> {
+        Some(self)
+    }
+}
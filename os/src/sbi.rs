@@ -9,6 +9,8 @@ const SBI_REMOTE_SFENCE_VMA: usize = 6;
 const SBI_REMOTE_SFENCE_VMA_ASID: usize = 7;
 const SBI_SHUTDOWN: usize = 8;
 const LEGACY_SEND_IPI: usize = 4;
+const SBI_EXT_HSM: usize = 0x48534D;
+const SBI_HSM_HART_START: usize = 0;
 #[inline(always)]
 fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     let mut ret;
@@ -24,6 +26,23 @@ fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
+/// Call a function of an SBI extension, return the error code.
+#[inline(always)]
+fn sbi_call_ext(eid: usize, fid: usize, arg0: usize, arg1: usize, arg2: usize) -> isize {
+    let mut error: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") arg0 => error,
+            inlateout("x11") arg1 => _,
+            in("x12") arg2,
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    error
+}
+
 pub fn set_timer(timer: usize) {
     sbi_call(SBI_SET_TIMER, timer, 0, 0);
 }
@@ -46,6 +65,22 @@ pub fn send_ipi(hartid: usize) {
     sbi_call(LEGACY_SEND_IPI, &hartid_mask as *const usize as usize, 0, 0);
 }
 
+/// Flush the TLB of the harts in `hart_mask` for `[start, start + size)`.
+pub fn remote_sfence_vma(hart_mask: usize, start: usize, size: usize) {
+    sbi_call(
+        SBI_REMOTE_SFENCE_VMA,
+        &hart_mask as *const usize as usize,
+        start,
+        size,
+    );
+}
+
+/// Start a stopped hart at `start_addr` with `a0 = hartid` and `a1 = opaque`.
+/// Fails if the SBI has no HSM extension or the hart is running already.
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> isize {
+    sbi_call_ext(SBI_EXT_HSM, SBI_HSM_HART_START, hartid, start_addr, opaque)
+}
+
 // #[inline]
 // pub fn sbi_rustsbi_k210_sext() {
 //     sbi_call(0x0A000004, handler_ext as usize, 0, 0);
@@ -0,0 +1,73 @@
+use crate::error::{SysError, SysResult};
+use crate::sync::{Mutex, SpinMutex};
+use crate::task::{
+    block_current_interruptible, current_task, mark_current_blocked, wakeup_task, TaskControlBlock,
+};
+use alloc::{collections::VecDeque, sync::Arc};
+
+pub struct Condvar {
+    pub inner: SpinMutex<CondvarInner>,
+}
+
+pub struct CondvarInner {
+    pub wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinMutex::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Wake up one waiting task, if any.
+    pub fn signal(&self) {
+        let mut inner = self.inner.lock();
+        if let Some(task) = inner.wait_queue.pop_front() {
+            wakeup_task(task);
+        }
+    }
+
+    /// Wake up all waiting tasks.
+    pub fn broadcast(&self) {
+        let mut inner = self.inner.lock();
+        while let Some(task) = inner.wait_queue.pop_front() {
+            wakeup_task(task);
+        }
+    }
+
+    /// Release `mutex`, sleep until signaled and take `mutex` again.
+    /// The task waits before the mutex is released, so a signal sent by
+    /// the next owner of the mutex cannot be missed. A process signal
+    /// ends the wait early with EINTR, the mutex is taken again anyway.
+    pub fn wait(&self, mutex: Arc<dyn Mutex>) -> SysResult<()> {
+        let task = current_task().unwrap();
+        let mut inner = self.inner.lock();
+        mark_current_blocked();
+        inner.wait_queue.push_back(Arc::clone(&task));
+        drop(inner);
+        mutex.unlock();
+        let result = loop {
+            let interrupted = block_current_interruptible().is_err();
+            // still queued means that nobody signaled us
+            let mut inner = self.inner.lock();
+            let idx = match inner
+                .wait_queue
+                .iter()
+                .position(|waiting| Arc::ptr_eq(waiting, &task))
+            {
+                Some(idx) => idx,
+                None => break Ok(()),
+            };
+            if interrupted {
+                inner.wait_queue.remove(idx);
+                break Err(SysError::EINTR);
+            }
+            mark_current_blocked();
+        };
+        mutex.lock();
+        result
+    }
+}
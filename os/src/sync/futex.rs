@@ -0,0 +1,102 @@
+use crate::mm::PhysAddr;
+use crate::sync::SpinMutex;
+use crate::task::{
+    block_current_interruptible, current_task, mark_current_blocked, wakeup_task, TaskControlBlock,
+};
+use crate::timer::{add_timer, get_time_ms, remove_timer};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use lazy_static::*;
+
+lazy_static! {
+    /// Tasks waiting on a futex word, keyed by the physical address of the
+    /// word so that processes sharing the page also share the queue.
+    static ref FUTEX_QUEUES: SpinMutex<BTreeMap<usize, VecDeque<Arc<TaskControlBlock>>>> =
+        SpinMutex::new(BTreeMap::new());
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FutexWait {
+    Woken,
+    /// The word did not hold the expected value, nothing was waited for.
+    Mismatch,
+    TimedOut,
+    /// A signal came first.
+    Interrupted,
+}
+
+/// Sleep on the futex word at `pa` if it still holds `val`. The word is
+/// read under the queue lock, so a wakeup after a change of the word by
+/// another thread cannot be missed.
+pub fn futex_wait(pa: PhysAddr, val: u32, timeout_ms: Option<usize>) -> FutexWait {
+    let mut queues = FUTEX_QUEUES.lock();
+    if pa.get_ref::<AtomicU32>().load(Ordering::SeqCst) != val {
+        return FutexWait::Mismatch;
+    }
+    let task = current_task().unwrap();
+    mark_current_blocked();
+    queues
+        .entry(pa.0)
+        .or_insert_with(VecDeque::new)
+        .push_back(Arc::clone(&task));
+    let deadline_ms = timeout_ms.map(|timeout_ms| get_time_ms() + timeout_ms);
+    if let Some(deadline) = deadline_ms {
+        add_timer(deadline, Arc::clone(&task));
+    }
+    let result = loop {
+        drop(queues);
+        let interrupted = block_current_interruptible().is_err();
+        // still queued means that no `futex_wake` picked us
+        queues = FUTEX_QUEUES.lock();
+        let queue = match queues.get_mut(&pa.0) {
+            Some(queue) => queue,
+            None => break FutexWait::Woken,
+        };
+        let idx = match queue.iter().position(|waiting| Arc::ptr_eq(waiting, &task)) {
+            Some(idx) => idx,
+            None => break FutexWait::Woken,
+        };
+        let expired = deadline_ms.map_or(false, |deadline| get_time_ms() >= deadline);
+        if interrupted || expired {
+            queue.remove(idx);
+            if queue.is_empty() {
+                queues.remove(&pa.0);
+            }
+            break match interrupted {
+                true => FutexWait::Interrupted,
+                false => FutexWait::TimedOut,
+            };
+        }
+        mark_current_blocked();
+    };
+    drop(queues);
+    if deadline_ms.is_some() {
+        remove_timer(&task);
+    }
+    result
+}
+
+/// Wake up at most `count` tasks waiting on the futex word at `pa`,
+/// return how many were woken.
+pub fn futex_wake(pa: PhysAddr, count: usize) -> usize {
+    let mut queues = FUTEX_QUEUES.lock();
+    let queue = match queues.get_mut(&pa.0) {
+        Some(queue) => queue,
+        None => return 0,
+    };
+    let mut woken = 0;
+    while woken < count {
+        match queue.pop_front() {
+            Some(task) => {
+                wakeup_task(task);
+                woken += 1;
+            }
+            None => break,
+        }
+    }
+    if queue.is_empty() {
+        queues.remove(&pa.0);
+    }
+    woken
+}
@@ -1,9 +1,15 @@
+mod condvar;
+mod futex;
 mod mutex;
 mod semaphore;
 mod spin;
 mod up;
+mod wait_queue;
 
+pub use condvar::Condvar;
+pub use futex::{futex_wait, futex_wake, FutexWait};
 pub use mutex::{Mutex, MutexBlocking, MutexSpin};
 pub use semaphore::Semaphore;
-pub use spin::{intr_off, intr_on, SpinMutex, SpinMutexGuard};
+pub use spin::{intr_off, intr_on, pop_off, push_off, SpinMutex, SpinMutexGuard};
 pub use up::UPSafeCell;
+pub use wait_queue::WaitQueue;
@@ -1,7 +1,6 @@
-use super::UPSafeCell;
-use crate::task::TaskControlBlock;
-use crate::task::{add_task, current_task};
+use super::SpinMutex;
 use crate::task::{block_current_and_run_next, suspend_current_and_run_next};
+use crate::task::{current_task, mark_current_blocked, wakeup_task, TaskControlBlock};
 use alloc::{collections::VecDeque, sync::Arc};
 
 pub trait Mutex: Sync + Send {
@@ -10,13 +9,13 @@ pub trait Mutex: Sync + Send {
 }
 
 pub struct MutexSpin {
-    locked: UPSafeCell<bool>,
+    locked: SpinMutex<bool>,
 }
 
 impl MutexSpin {
     pub fn new() -> Self {
         Self {
-            locked: unsafe { UPSafeCell::new(false) },
+            locked: SpinMutex::new(false),
         }
     }
 }
@@ -24,7 +23,7 @@ impl MutexSpin {
 impl Mutex for MutexSpin {
     fn lock(&self) {
         loop {
-            let mut locked = self.locked.inner.borrow_mut();
+            let mut locked = self.locked.lock();
             if *locked {
                 drop(locked);
                 suspend_current_and_run_next();
@@ -37,13 +36,13 @@ impl Mutex for MutexSpin {
     }
 
     fn unlock(&self) {
-        let mut locked = self.locked.inner.borrow_mut();
+        let mut locked = self.locked.lock();
         *locked = false;
     }
 }
 
 pub struct MutexBlocking {
-    inner: UPSafeCell<MutexBlockingInner>,
+    inner: SpinMutex<MutexBlockingInner>,
 }
 
 pub struct MutexBlockingInner {
@@ -54,34 +53,44 @@ pub struct MutexBlockingInner {
 impl MutexBlocking {
     pub fn new() -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(MutexBlockingInner {
-                    locked: false,
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinMutex::new(MutexBlockingInner {
+                locked: false,
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
 }
 
 impl Mutex for MutexBlocking {
     fn lock(&self) {
-        let mut mutex_inner = self.inner.exclusive_access();
-        if mutex_inner.locked {
-            mutex_inner.wait_queue.push_back(current_task().unwrap());
+        let mut mutex_inner = self.inner.lock();
+        if !mutex_inner.locked {
+            mutex_inner.locked = true;
+            return;
+        }
+        let task = current_task().unwrap();
+        mutex_inner.wait_queue.push_back(Arc::clone(&task));
+        // a signal wakes the task up as well, only `unlock` dequeues it
+        while mutex_inner
+            .wait_queue
+            .iter()
+            .any(|waiting| Arc::ptr_eq(waiting, &task))
+        {
+            mark_current_blocked();
             drop(mutex_inner);
             block_current_and_run_next();
-        } else {
-            mutex_inner.locked = true;
+            mutex_inner = self.inner.lock();
         }
     }
 
     fn unlock(&self) {
-        let mut mutex_inner = self.inner.exclusive_access();
+        let mut mutex_inner = self.inner.lock();
         assert_eq!(mutex_inner.locked, true);
-        mutex_inner.locked = false;
         if let Some(waking_task) = mutex_inner.wait_queue.pop_front() {
-            add_task(waking_task);
+            // hand the mutex over, so that another hart cannot take it first
+            wakeup_task(waking_task);
+        } else {
+            mutex_inner.locked = false;
         }
     }
 }
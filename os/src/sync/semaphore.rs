@@ -1,10 +1,13 @@
-use crate::sync::UPSafeCell;
-use crate::task::{add_task, block_current_and_run_next, current_task, TaskControlBlock};
+use crate::error::{SysError, SysResult};
+use crate::sync::SpinMutex;
+use crate::task::{
+    block_current_interruptible, current_task, mark_current_blocked, wakeup_task, TaskControlBlock,
+};
+use crate::timer::{add_timer, get_time_ms, remove_timer};
 use alloc::{collections::VecDeque, sync::Arc};
-use riscv::register::sstatus;
 
 pub struct Semaphore {
-    pub inner: UPSafeCell<SemaphoreInner>,
+    pub inner: SpinMutex<SemaphoreInner>,
 }
 
 pub struct SemaphoreInner {
@@ -15,32 +18,85 @@ pub struct SemaphoreInner {
 impl Semaphore {
     pub fn new(res_count: usize) -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(SemaphoreInner {
-                    count: res_count as isize,
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinMutex::new(SemaphoreInner {
+                count: res_count as isize,
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
 
     pub fn up(&self) {
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         inner.count += 1;
         if inner.count <= 0 {
             if let Some(task) = inner.wait_queue.pop_front() {
-                add_task(task);
+                // the unit is handed over, even if a timeout woke the task first
+                wakeup_task(task);
             }
         }
     }
 
-    pub fn down(&self) {
-        let mut inner = self.inner.exclusive_access();
+    /// Take a unit, sleeping until there is one. Give up with EINTR if a
+    /// signal comes first.
+    pub fn down(&self) -> SysResult<()> {
+        self.down_until(None).map(|_| ())
+    }
+
+    /// Take a unit if there is one, never block.
+    pub fn try_down(&self) -> bool {
+        let mut inner = self.inner.lock();
+        if inner.count > 0 {
+            inner.count -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Like `down`, but give up after `timeout_ms`. Return false on timeout.
+    pub fn down_timeout(&self, timeout_ms: usize) -> SysResult<bool> {
+        self.down_until(Some(get_time_ms() + timeout_ms))
+    }
+
+    fn down_until(&self, deadline_ms: Option<usize>) -> SysResult<bool> {
+        let mut inner = self.inner.lock();
         inner.count -= 1;
-        if inner.count < 0 {
-            inner.wait_queue.push_back(current_task().unwrap());
+        if inner.count >= 0 {
+            return Ok(true);
+        }
+        let task = current_task().unwrap();
+        inner.wait_queue.push_back(Arc::clone(&task));
+        if let Some(deadline) = deadline_ms {
+            add_timer(deadline, Arc::clone(&task));
+        }
+        let result = loop {
+            mark_current_blocked();
             drop(inner);
-            //block_current_and_run_next();
+            let interrupted = block_current_interruptible().is_err();
+            inner = self.inner.lock();
+            // still waiting means that `up` did not pick us
+            let idx = match inner
+                .wait_queue
+                .iter()
+                .position(|waiting| Arc::ptr_eq(waiting, &task))
+            {
+                Some(idx) => idx,
+                None => break Ok(true),
+            };
+            let expired = deadline_ms.map_or(false, |deadline| get_time_ms() >= deadline);
+            if interrupted || expired {
+                inner.wait_queue.remove(idx);
+                inner.count += 1;
+                break match interrupted {
+                    true => Err(SysError::EINTR),
+                    false => Ok(false),
+                };
+            }
+        };
+        drop(inner);
+        if deadline_ms.is_some() {
+            remove_timer(&task);
         }
+        result
     }
 }
@@ -60,6 +60,8 @@ impl<T: ?Sized> SpinMutex<T> {
     }
     #[inline(always)]
     pub fn try_lock(&self) -> Option<SpinMutexGuard<T>> {
+        // balanced by the guard, or right below on failure
+        push_off();
         // The reason for using a strong compare_exchange is explained here:
         // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
         if self
@@ -72,6 +74,7 @@ impl<T: ?Sized> SpinMutex<T> {
                 data: unsafe { &mut *self.data.get() },
             })
         } else {
+            pop_off();
             None
         }
     }
@@ -132,9 +135,10 @@ pub fn intr_off() {
 }
 
 pub fn push_off() {
-    let processor = current_processor().unwrap();
     let old = sstatus::read().sie();
     intr_off();
+    // only now, a task preempted before may have moved to another hart
+    let processor = current_processor().unwrap();
     if processor.noff == 0 {
         processor.intena = old
     }
@@ -0,0 +1,41 @@
+use crate::sync::SpinMutex;
+use crate::task::{wakeup_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// Tasks waiting for something to change, e.g. for a file to become
+/// ready. A task may sit on several queues at once and is woken by the
+/// first one which fires; it then checks again what it waits for.
+pub struct WaitQueue {
+    tasks: SpinMutex<VecDeque<Arc<TaskControlBlock>>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            tasks: SpinMutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Queue `task` unless it is queued already.
+    pub fn register(&self, task: &Arc<TaskControlBlock>) {
+        let mut tasks = self.tasks.lock();
+        if !tasks.iter().any(|waiting| Arc::ptr_eq(waiting, task)) {
+            tasks.push_back(Arc::clone(task));
+        }
+    }
+
+    pub fn unregister(&self, task: &Arc<TaskControlBlock>) {
+        self.tasks
+            .lock()
+            .retain(|waiting| !Arc::ptr_eq(waiting, task));
+    }
+
+    /// Wake up all waiting tasks.
+    pub fn wake_all(&self) {
+        let mut tasks = self.tasks.lock();
+        while let Some(task) = tasks.pop_front() {
+            wakeup_task(task);
+        }
+    }
+}
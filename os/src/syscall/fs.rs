@@ -1,293 +1,809 @@
-use super::process::sys_brk;
+use super::sync::TimeSpec;
+use crate::config::PAGE_SIZE;
+use crate::error::{SysError, SysResult};
 use crate::fatfs::io::SeekFrom;
 use crate::fs::make_pipe;
-use crate::fs::Dirent;
 use crate::fs::Kstat;
 use crate::fs::OpenFlags;
-use crate::mm::{translated_byte_buffer, translated_refmut, translated_str, UserBuffer};
-use crate::task::{current_process, current_user_token};
-use alloc::string::ToString;
+use crate::fs::{
+    filesystem_of, lookup, mkdir, mknod, mount, new_filesystem, open_file, sync_all, umount,
+    unlink, wait_ready, Dentry, EpollEvent, EpollFile, File, InodeType, PollEvents, AT_FDCWD,
+};
+use crate::mm::{
+    copy_bytes_to_user, copy_from_user, copy_str_from_user, copy_to_user, user_byte_buffer,
+    MapFile, MapPermission, UserBuffer, VirtAddr,
+};
+use crate::task::{
+    current_process, current_user_token, set_wait_signal_mask, SignalFlags, FD_LIMIT,
+};
+use crate::timer::get_time_ms;
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+/// `sys_unlink` removes a directory.
+const AT_REMOVEDIR: usize = 0x200;
+
+const SEEK_SET: usize = 0;
+const SEEK_CUR: usize = 1;
+const SEEK_END: usize = 2;
+
+/// Most buffers `readv` and `writev` take at once.
+const IOV_MAX: usize = 1024;
+
+/// Most fds `ppoll` and `pselect6` watch at once.
+const FD_SETSIZE: usize = 1024;
+
+/// The only flag of `epoll_create1`.
+const EPOLL_CLOEXEC: usize = 0o2000000;
+
+const F_DUPFD: usize = 0;
+const F_GETFD: usize = 1;
+const F_SETFD: usize = 2;
+const F_GETFL: usize = 3;
+const F_SETFL: usize = 4;
+const F_DUPFD_CLOEXEC: usize = 1030;
+/// The only fd flag of F_GETFD and F_SETFD.
+const FD_CLOEXEC: usize = 1;
+
+/// One buffer of `readv` and `writev`, as `struct iovec`.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct IoVec {
+    base: *mut u8,
+    len: usize,
+}
+
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> SysResult {
     let token = current_user_token();
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if !inner.fd_table.contains_key(&fd) {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table.get(&fd).unwrap() {
-        if !file.writable() {
-            return -1;
-        }
-        let file = file.clone();
-        drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
-    } else {
-        -1
+    let file = fd_file(fd)?;
+    if !file.writable() {
+        return Err(SysError::EBADF);
     }
+    let buffer = user_byte_buffer(token, buf, len, MapPermission::R)?;
+    Ok(file.write(buffer)? as isize)
 }
 
-pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
+pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> SysResult {
     let token = current_user_token();
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if !inner.fd_table.contains_key(&fd) {
-        return -1;
+    let file = fd_file(fd)?;
+    if !file.readable() {
+        return Err(SysError::EBADF);
     }
+    let buffer = user_byte_buffer(token, buf, len, MapPermission::W)?;
+    Ok(file.read(buffer)? as isize)
+}
 
-    if let Some(file) = &inner.fd_table.get(&fd).unwrap() {
-        let file = file.clone();
-        if !file.readable() {
-            return -1;
-        }
-        // release current task TCB manually to avoid multi-borrow
-        drop(inner);
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
-    } else {
-        -1
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> SysResult {
+    let file = fd_file(fd)?;
+    let pos = match whence {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as u64),
+        SEEK_CUR => SeekFrom::Current(offset as i64),
+        SEEK_END => SeekFrom::End(offset as i64),
+        _ => return Err(SysError::EINVAL),
+    };
+    Ok(file.seek(pos)? as isize)
+}
+
+/// The file open as `fd`, if it can be read or written at `offset`
+/// without a file offset of its own.
+fn positional_file(fd: usize, offset: isize) -> SysResult<Arc<dyn File>> {
+    let file = fd_file(fd)?;
+    // files which cannot seek have no positions either
+    file.seek(SeekFrom::Current(0))?;
+    if offset < 0 {
+        return Err(SysError::EINVAL);
     }
+    if file.dentry().map_or(false, |dentry| dentry.is_dir()) {
+        return Err(SysError::EISDIR);
+    }
+    Ok(file)
 }
 
-pub fn sys_open(fd: isize, path: *const u8, flags: u32) -> isize {
+/// Read at `offset`, the file offset stays where it is.
+pub fn sys_pread64(fd: usize, buf: *const u8, len: usize, offset: isize) -> SysResult {
     let token = current_user_token();
-    let process = current_process();
-    let mut inner = process.inner_exclusive_access();
-    let path = translated_str(token, path).replace("./", "");
-    println!("[sys_open]: {}", path);
-    let dir = if fd >= 0 {
-        let fd = fd as usize;
-        if let Some(dir) = inner.fd_table.get(&fd).unwrap() {
-            dir
-        } else {
-            return -1;
+    let file = positional_file(fd, offset)?;
+    if !file.readable() {
+        return Err(SysError::EBADF);
+    }
+    let mut total_read_size = 0usize;
+    for slice in user_byte_buffer(token, buf, len, MapPermission::W)?.buffers {
+        let slice_len = slice.len();
+        let len = file.read_at(offset as usize + total_read_size, slice);
+        total_read_size += len;
+        if len < slice_len {
+            break;
         }
-    } else {
-        &inner.dir_entry.as_ref().unwrap()
-    };
-    if path == ".".to_string() {
-        let tmp = dir.clone();
-        drop(dir);
-        let fd = inner.alloc_fd();
-        inner.fd_table.insert(fd, Some(tmp));
-        return fd as isize;
-    }
-    let flag = OpenFlags::from_bits(flags).unwrap();
-    let (readable, writable) = flag.read_write();
-    let file = if flag.contains(OpenFlags::CREATE) {
-        dir.create(&path, readable, writable, false)
-    } else {
-        let directory = flag.contains(OpenFlags::DIRECTORY);
-        dir.open(&path, readable, writable, directory)
-    };
-    if let Some(file) = file {
-        let fd = inner.alloc_fd();
-        inner.fd_table.insert(fd, Some(file));
-        fd as isize
-    } else {
-        -1
     }
+    Ok(total_read_size as isize)
 }
 
-pub fn sys_unlink(dirfd: isize, path: *const u8, _flags: usize) -> isize {
+/// Write at `offset`, the file offset stays where it is.
+pub fn sys_pwrite64(fd: usize, buf: *const u8, len: usize, offset: isize) -> SysResult {
     let token = current_user_token();
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if dirfd < 0 {
-        let path = translated_str(token, path).replace("./", "");
-        if inner.dir_entry.as_ref().unwrap().remove(&path) {
-            return 0;
+    let file = positional_file(fd, offset)?;
+    if !file.writable() {
+        return Err(SysError::EBADF);
+    }
+    let mut total_write_size = 0usize;
+    for slice in user_byte_buffer(token, buf, len, MapPermission::R)?.buffers {
+        let len = file.write_at(offset as usize + total_write_size, slice);
+        total_write_size += len;
+        if len < slice.len() {
+            break;
         }
     }
-    1
+    Ok(total_write_size as isize)
+}
+
+/// The buffers of the `iovcnt` entries at `iov`, in order, as one list.
+fn iovec_buffers(
+    token: usize,
+    iov: *const IoVec,
+    iovcnt: usize,
+    access: MapPermission,
+) -> SysResult<UserBuffer> {
+    if iovcnt > IOV_MAX {
+        return Err(SysError::EINVAL);
+    }
+    let mut buffer = UserBuffer::new(Vec::new());
+    let mut total_len = 0usize;
+    for i in 0..iovcnt {
+        let iovec = copy_from_user(token, iov.wrapping_add(i))?;
+        total_len = total_len
+            .checked_add(iovec.len)
+            .filter(|&len| len <= isize::MAX as usize)
+            .ok_or(SysError::EINVAL)?;
+        buffer.append(user_byte_buffer(token, iovec.base, iovec.len, access)?);
+    }
+    Ok(buffer)
 }
 
-pub fn sys_mkdir(dirfd: isize, path: *const u8, _mode: usize) -> isize {
+/// Read into several buffers as one read.
+pub fn sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> SysResult {
     let token = current_user_token();
-    let process = current_process();
-    let mut inner = process.inner_exclusive_access();
-    let path = translated_str(token, path).replace("./", "");
-    let dir = if dirfd >= 0 {
-        if let Some(dir) = inner.fd_table.get(&(dirfd as usize)).unwrap() {
-            dir
-        } else {
-            return -1;
-        }
-    } else {
-        &inner.dir_entry.as_ref().unwrap()
-    };
+    let file = fd_file(fd)?;
+    if !file.readable() {
+        return Err(SysError::EBADF);
+    }
+    let buffer = iovec_buffers(token, iov, iovcnt, MapPermission::W)?;
+    Ok(file.read(buffer)? as isize)
+}
 
-    if let Some(file) = dir.create(&path, false, false, true) {
-        let fd = inner.alloc_fd();
-        inner.fd_table.insert(fd, Some(file));
-        fd as isize
-    } else {
-        -1
+/// Write several buffers as one write.
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> SysResult {
+    let token = current_user_token();
+    let file = fd_file(fd)?;
+    if !file.writable() {
+        return Err(SysError::EBADF);
     }
+    let buffer = iovec_buffers(token, iov, iovcnt, MapPermission::R)?;
+    Ok(file.write(buffer)? as isize)
 }
 
-pub fn sys_chdir(path: *const u8) -> isize {
+pub fn sys_open(dirfd: isize, path: *const u8, flags: u32) -> SysResult {
     let token = current_user_token();
+    let path = copy_str_from_user(token, path)?;
+    let flags = OpenFlags::from_bits_truncate(flags);
+    let start = dirfd_dentry(dirfd)?;
+    let file = open_file(&start, &path, flags)?;
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    let path = translated_str(token, path).replace("./", "");
-    let inode = inner
-        .dir_entry
-        .as_ref()
-        .unwrap()
-        .open(&path, true, true, true);
-    match inode {
-        Some(file) => {
-            inner.dir_entry = Some(file);
-            0
-        }
-        None => 1,
+    let fd = inner.alloc_fd()?;
+    inner.fd_table.insert(fd, Some(file));
+    inner.set_cloexec(fd, flags.contains(OpenFlags::CLOEXEC));
+    Ok(fd as isize)
+}
+
+/// Remove a file, or a directory if `flags` has AT_REMOVEDIR.
+pub fn sys_unlink(dirfd: isize, path: *const u8, flags: usize) -> SysResult {
+    let token = current_user_token();
+    let path = copy_str_from_user(token, path)?;
+    let start = dirfd_dentry(dirfd)?;
+    unlink(&start, &path, flags & AT_REMOVEDIR != 0)?;
+    Ok(0)
+}
+
+pub fn sys_mkdir(dirfd: isize, path: *const u8, _mode: usize) -> SysResult {
+    let token = current_user_token();
+    let path = copy_str_from_user(token, path)?;
+    let start = dirfd_dentry(dirfd)?;
+    mkdir(&start, &path)?;
+    Ok(0)
+}
+
+/// Create a regular file, a FIFO or a socket node, device nodes only come
+/// from devfs.
+pub fn sys_mknodat(dirfd: isize, path: *const u8, mode: u32, _dev: usize) -> SysResult {
+    let token = current_user_token();
+    let path = copy_str_from_user(token, path)?;
+    // a mode without a type is a regular file
+    let inode_type = match mode & 0o170000 {
+        0 => InodeType::File,
+        _ => InodeType::from_mode(mode).ok_or(SysError::EINVAL)?,
+    };
+    match inode_type {
+        InodeType::File | InodeType::Fifo | InodeType::Socket => {}
+        InodeType::CharDevice | InodeType::BlockDevice => return Err(SysError::EPERM),
+        InodeType::Dir => return Err(SysError::EINVAL),
     }
+    let start = dirfd_dentry(dirfd)?;
+    mknod(&start, &path, inode_type)?;
+    Ok(0)
 }
 
-pub fn sys_close(fd: usize) -> isize {
+pub fn sys_chdir(path: *const u8) -> SysResult {
+    let token = current_user_token();
+    let path = copy_str_from_user(token, path)?;
+    let start = dirfd_dentry(AT_FDCWD)?;
+    let dir = lookup(&start, &path)?;
+    if !dir.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    current_process().inner_exclusive_access().cwd = dir;
+    Ok(0)
+}
+
+pub fn sys_close(fd: usize) -> SysResult {
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    if !inner.fd_table.contains_key(&fd) {
-        return -1;
-    }
-    inner.fd_table.remove(&fd);
-    0
+    inner.set_cloexec(fd, false);
+    let file = match inner.fd_table.remove(&fd) {
+        Some(Some(file)) => file,
+        _ => return Err(SysError::EBADF),
+    };
+    drop(inner);
+    // the last reference may write back or wake a pipe's other end
+    drop(file);
+    Ok(0)
 }
 
-pub fn sys_pipe(pipe: *mut i32) -> isize {
+/// Store the fds of a new pipe at `pipe`, O_NONBLOCK and O_CLOEXEC are the
+/// only flags.
+pub fn sys_pipe2(pipe: *mut i32, flags: u32) -> SysResult {
+    let flags = OpenFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if !(flags - OpenFlags::NONBLOCK - OpenFlags::CLOEXEC).is_empty() {
+        return Err(SysError::EINVAL);
+    }
     let process = current_process();
     let token = current_user_token();
+    let (pipe_read, pipe_write) = make_pipe(flags);
     let mut inner = process.inner_exclusive_access();
-    let (pipe_read, pipe_write) = make_pipe();
-    let read_fd = inner.alloc_fd();
-    inner.fd_table.get_mut(&read_fd).unwrap().replace(pipe_read);
-    let write_fd = inner.alloc_fd();
-    inner
-        .fd_table
-        .get_mut(&write_fd)
-        .unwrap()
-        .replace(pipe_write);
-    *translated_refmut(token, pipe) = read_fd as i32;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd as i32;
-    0
-}
-
-pub fn sys_dup(fd: usize) -> isize {
+    let read_fd = inner.alloc_fd()?;
+    let write_fd = match inner.alloc_fd() {
+        Ok(fd) => fd,
+        Err(err) => {
+            inner.fd_table.remove(&read_fd);
+            return Err(err);
+        }
+    };
+    inner.fd_table.insert(read_fd, Some(pipe_read));
+    inner.fd_table.insert(write_fd, Some(pipe_write));
+    inner.set_cloexec(read_fd, flags.contains(OpenFlags::CLOEXEC));
+    inner.set_cloexec(write_fd, flags.contains(OpenFlags::CLOEXEC));
+    drop(inner);
+    let fds = [read_fd as i32, write_fd as i32];
+    if let Err(err) = copy_to_user(token, pipe as *mut [i32; 2], &fds) {
+        let mut inner = process.inner_exclusive_access();
+        inner.fd_table.remove(&read_fd);
+        inner.fd_table.remove(&write_fd);
+        inner.set_cloexec(read_fd, false);
+        inner.set_cloexec(write_fd, false);
+        return Err(err);
+    }
+    Ok(0)
+}
+
+pub fn sys_dup(fd: usize) -> SysResult {
+    let file = fd_file(fd)?;
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
+    let new_fd = inner.alloc_fd()?;
+    inner.fd_table.insert(new_fd, Some(file));
+    Ok(new_fd as isize)
+}
+
+/// Make `new` refer to the file of `old`, closing whatever `new` was.
+/// O_CLOEXEC is the only flag.
+pub fn sys_dup3(old: usize, new: usize, flags: u32) -> SysResult {
+    let file = fd_file(old)?;
+    let flags = OpenFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if old == new || !(flags - OpenFlags::CLOEXEC).is_empty() {
+        return Err(SysError::EINVAL);
     }
-    if inner.fd_table[&fd].is_none() {
-        return -1;
+    if new >= FD_LIMIT {
+        return Err(SysError::EBADF);
     }
-    let new_fd = inner.alloc_fd();
-    let value = Arc::clone(inner.fd_table[&fd].as_ref().unwrap());
-    inner.fd_table.get_mut(&new_fd).unwrap().replace(value);
-    new_fd as isize
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let replaced = inner.fd_table.insert(new, Some(file));
+    inner.set_cloexec(new, flags.contains(OpenFlags::CLOEXEC));
+    drop(inner);
+    // the last reference may write the file back, not under the process
+    drop(replaced);
+    Ok(new as isize)
 }
 
-pub fn sys_dup3(old: usize, new: usize) -> isize {
+/// Duplicate `fd`, or get or set the flags of the fd or of its file.
+/// F_SETFL only changes O_APPEND and O_NONBLOCK.
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> SysResult {
+    let file = fd_file(fd)?;
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    if inner.fd_table[&old].is_none() {
-        return -1;
-    }
-    if inner.fd_table.contains_key(&new) {
-        return -1;
+    match cmd {
+        // no fd could ever be that high, as opposed to all being in use
+        F_DUPFD | F_DUPFD_CLOEXEC if arg >= FD_LIMIT => Err(SysError::EINVAL),
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            let new_fd = inner.alloc_fd_from(arg)?;
+            inner.fd_table.insert(new_fd, Some(file));
+            inner.set_cloexec(new_fd, cmd == F_DUPFD_CLOEXEC);
+            Ok(new_fd as isize)
+        }
+        F_GETFD => Ok(match inner.cloexec_fds.contains(&fd) {
+            true => FD_CLOEXEC as isize,
+            false => 0,
+        }),
+        F_SETFD => {
+            inner.set_cloexec(fd, arg & FD_CLOEXEC != 0);
+            Ok(0)
+        }
+        F_GETFL => {
+            let access = match (file.readable(), file.writable()) {
+                (true, true) => OpenFlags::RDWR,
+                (false, true) => OpenFlags::WRONLY,
+                _ => OpenFlags::RDONLY,
+            };
+            Ok((access | file.status_flags()).bits() as isize)
+        }
+        F_SETFL => {
+            file.set_status_flags(OpenFlags::from_bits_truncate(arg as u32));
+            Ok(0)
+        }
+        _ => Err(SysError::EINVAL),
     }
-    let value = Arc::clone(inner.fd_table[&old].as_ref().unwrap());
-    inner.fd_table.insert(new, Some(value));
-    new as isize
 }
 
-pub fn sys_getcwd(buf: *const u8, len: usize) -> isize {
+/// Copy the absolute path of the working directory into `buf`.
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> SysResult {
     let token = current_user_token();
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    let name = inner.dir_entry.as_ref().unwrap().name();
-    let dir = name.as_bytes();
-    for b in UserBuffer::new(translated_byte_buffer(token, buf, len)).buffers {
-        b[0..dir.len()].copy_from_slice(dir);
+    let cwd = Arc::clone(&current_process().inner_exclusive_access().cwd);
+    let mut path = cwd.path().into_bytes();
+    path.push(0);
+    if path.len() > len {
+        return Err(SysError::ERANGE);
     }
-    1
+    copy_bytes_to_user(token, buf, &path)?;
+    Ok(buf as isize)
 }
 
-pub fn sys_fstat(fd: isize, ptr: *mut Kstat) -> isize {
+pub fn sys_fstat(fd: isize, ptr: *mut Kstat) -> SysResult {
     let token = current_user_token();
-    let stat = translated_refmut(token, ptr);
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if let Some(opt) = inner.fd_table.get(&(fd as usize)) {
-        if let Some(file) = opt {
-            file.kstat(stat);
-            return 0;
-        }
-    }
-    1
+    let file = fd_file(fd as usize)?;
+    let mut stat = Kstat::default();
+    file.kstat(&mut stat);
+    copy_to_user(token, ptr, &stat)?;
+    Ok(0)
 }
 
-pub fn sys_getdents(fd: isize, ptr: *mut Dirent, _len: usize) -> isize {
+pub fn sys_getdents(fd: isize, buf: *mut u8, len: usize) -> SysResult {
     let token = current_user_token();
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    let dirent = translated_refmut(token, ptr);
-    let mut name = translated_str(token, (ptr as usize + 19) as *const u8);
-    match inner.fd_table.get(&(fd as usize)) {
-        Some(Some(file)) => {
-            file.getdents(dirent);
-            name.clone_from(&file.name());
-            1
+    let file = fd_file(fd as usize)?;
+    // check the buffer before the directory offset moves on
+    let buffer = user_byte_buffer(token, buf, len, MapPermission::W)?;
+    // entries are gathered a page at a time, however much the caller asks for
+    let mut dirents = vec![0u8; len.min(PAGE_SIZE)];
+    let mut user_bytes = buffer.into_iter();
+    let mut total = 0usize;
+    while total < len {
+        let room = (len - total).min(dirents.len());
+        let size = file.getdents(&mut dirents[..room]);
+        if size < 0 {
+            return Err(SysError::ENOTDIR);
+        }
+        if size == 0 {
+            break;
+        }
+        for &byte in dirents[..size as usize].iter() {
+            unsafe {
+                *user_bytes.next().unwrap() = byte;
+            }
         }
-        Some(None) => -1,
-        None => -1,
+        total += size as usize;
     }
+    Ok(total as isize)
 }
 
-pub fn _link() -> isize {
-    1
+bitflags! {
+    pub struct MmapProt: u32 {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    pub struct MmapFlags: u32 {
+        const SHARED = 1 << 0;
+        const PRIVATE = 1 << 1;
+        const FIXED = 1 << 4;
+        const ANONYMOUS = 1 << 5;
+    }
 }
 
 pub fn sys_mmap(
     start: usize,
     len: usize,
-    _port: usize,
-    _flag: usize,
+    prot: usize,
+    flags: usize,
     fd: usize,
     off: usize,
-) -> isize {
-    let token = current_user_token();
+) -> SysResult {
+    let prot = MmapProt::from_bits(prot as u32).ok_or(SysError::EINVAL)?;
+    let flags = MmapFlags::from_bits_truncate(flags as u32);
+    let shared = flags.contains(MmapFlags::SHARED);
+    if shared == flags.contains(MmapFlags::PRIVATE) || off % PAGE_SIZE != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let mut perm = MapPermission::U;
+    if prot.contains(MmapProt::READ) {
+        perm |= MapPermission::R;
+    }
+    if prot.contains(MmapProt::WRITE) {
+        // there is no write-only page in Sv39
+        perm |= MapPermission::R | MapPermission::W;
+    }
+    if prot.contains(MmapProt::EXEC) {
+        perm |= MapPermission::X;
+    }
     let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if let Some(opt) = inner.fd_table.get(&fd) {
-        if let Some(file) = opt {
-            let start_addr = if start == 0 {
-                sys_brk(0) as usize
-            } else {
-                start
+    let file = if flags.contains(MmapFlags::ANONYMOUS) {
+        None
+    } else {
+        let file = match process.inner_exclusive_access().fd_table.get(&fd) {
+            Some(Some(file)) => Arc::clone(file),
+            _ => return Err(SysError::EBADF),
+        };
+        match file.dentry() {
+            Some(dentry) if dentry.inode().inode_type() == InodeType::File => {}
+            _ => return Err(SysError::ENODEV),
+        }
+        let writes_back = shared && prot.contains(MmapProt::WRITE);
+        if !file.readable() || (writes_back && !file.writable()) {
+            return Err(SysError::EACCES);
+        }
+        // the pages are read before the process is taken, reading may
+        // sleep or need the process itself
+        let mut map_file = MapFile::new(file, off);
+        let pages = map_file.load(len).ok_or(SysError::ENOMEM)?;
+        Some((map_file, pages))
+    };
+    let mut inner = process.inner_exclusive_access();
+    let mapped = inner.memory_set.mmap(
+        start,
+        len,
+        perm,
+        shared,
+        file,
+        flags.contains(MmapFlags::FIXED),
+    );
+    drop(inner);
+    match mapped {
+        // what was replaced is written back as it goes
+        Some((start, _unmapped)) => Ok(start as isize),
+        None => Err(SysError::ENOMEM),
+    }
+}
+
+pub fn sys_munmap(start: usize, len: usize) -> SysResult {
+    if start % PAGE_SIZE != 0 || len == 0 {
+        return Err(SysError::EINVAL);
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let unmapped = inner.memory_set.munmap(
+        VirtAddr::from(start).floor(),
+        VirtAddr::from(start + len).ceil(),
+    );
+    drop(inner);
+    // shared file pages are written back as the areas go
+    drop(unmapped);
+    Ok(0)
+}
+
+/// Mount the filesystem of type `fs_type` on `source` at the directory `target`.
+pub fn sys_mount(
+    source: *const u8,
+    target: *const u8,
+    fs_type: *const u8,
+    _flags: usize,
+    _data: *const u8,
+) -> SysResult {
+    let token = current_user_token();
+    let source = copy_str_from_user(token, source)?;
+    let target = copy_str_from_user(token, target)?;
+    let fs_type = copy_str_from_user(token, fs_type)?;
+    let fs = new_filesystem(&fs_type, &source).ok_or(SysError::ENODEV)?;
+    let start = dirfd_dentry(AT_FDCWD)?;
+    let target = lookup(&start, &target)?;
+    mount(&source, Some(target), fs)?;
+    Ok(0)
+}
+
+pub fn sys_umount(target: *const u8, _flags: usize) -> SysResult {
+    let token = current_user_token();
+    let target = copy_str_from_user(token, target)?;
+    let start = dirfd_dentry(AT_FDCWD)?;
+    let target = lookup(&start, &target)?;
+    umount(&target.path())?;
+    Ok(0)
+}
+
+pub fn sys_sync() -> SysResult {
+    sync_all();
+    Ok(0)
+}
+
+/// Write back the filesystem `fd` lives on. Data is cached per device
+/// rather than per file, so this serves both fsync and syncfs.
+pub fn sys_syncfs(fd: usize) -> SysResult {
+    let file = fd_file(fd)?;
+    let dentry = file.dentry().ok_or(SysError::EINVAL)?;
+    if let Some(fs) = filesystem_of(&dentry) {
+        fs.sync();
+    }
+    Ok(0)
+}
+
+/// One entry of `ppoll`, as `struct pollfd`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+/// When a wait for `timeout` from now ends, None for a null `timeout`.
+fn deadline_of(token: usize, timeout: *const TimeSpec) -> SysResult<Option<usize>> {
+    if timeout.is_null() {
+        return Ok(None);
+    }
+    let timeout = copy_from_user(token, timeout)?;
+    Ok(Some(
+        get_time_ms() + timeout.sec * 1000 + timeout.nsec / 1_000_000,
+    ))
+}
+
+/// Block the signals at `sigmask`, unless it is null, for the wait of ppoll
+/// and the like, until the return to user mode.
+fn wait_with_sigmask(token: usize, sigmask: *const u32) -> SysResult<()> {
+    if !sigmask.is_null() {
+        let mask = copy_from_user(token, sigmask)?;
+        set_wait_signal_mask(SignalFlags::from_bits_truncate(mask));
+    }
+    Ok(())
+}
+
+/// Wait for any of `nfds` fds to become ready and fill in `revents`. A
+/// signal which `sigmask`, or the signal mask if it is null, lets through
+/// ends the wait with EINTR.
+pub fn sys_ppoll(
+    fds: *mut PollFd,
+    nfds: usize,
+    timeout: *const TimeSpec,
+    sigmask: *const u32,
+) -> SysResult {
+    if nfds > FD_SETSIZE {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let mut poll_fds = Vec::with_capacity(nfds);
+    for i in 0..nfds {
+        poll_fds.push(copy_from_user(token, fds.wrapping_add(i))?);
+    }
+    let deadline = deadline_of(token, timeout)?;
+    // negative fds are skipped, closed ones are reported as NVAL
+    let files: Vec<Option<Arc<dyn File>>> = poll_fds
+        .iter()
+        .map(|poll_fd| match poll_fd.fd {
+            fd if fd < 0 => None,
+            fd => fd_file(fd as usize).ok(),
+        })
+        .collect();
+    let open_files: Vec<_> = files.iter().flatten().cloned().collect();
+    wait_with_sigmask(token, sigmask)?;
+    let ready = wait_ready(&open_files, deadline, || {
+        let mut ready = 0;
+        for (poll_fd, file) in poll_fds.iter_mut().zip(files.iter()) {
+            let revents = match file {
+                Some(file) => {
+                    let wanted = PollEvents::from_bits_truncate(poll_fd.events as u16);
+                    file.poll() & (wanted | PollEvents::ERR | PollEvents::HUP)
+                }
+                None if poll_fd.fd < 0 => PollEvents::empty(),
+                None => PollEvents::NVAL,
             };
-            sys_brk(start_addr + len);
-            file.seek(SeekFrom::Start(off as u64));
-            file.read(UserBuffer::new(translated_byte_buffer(
-                token,
-                start_addr as *const u8,
-                len,
-            )));
-            return start_addr as isize;
+            poll_fd.revents = revents.bits() as i16;
+            if !revents.is_empty() {
+                ready += 1;
+            }
         }
+        ready
+    })?;
+    for (i, poll_fd) in poll_fds.iter().enumerate() {
+        copy_to_user(token, fds.wrapping_add(i), poll_fd)?;
+    }
+    Ok(ready as isize)
+}
+
+/// The first `nfds` bits of the `fd_set` at `set`, in 64-bit words.
+fn load_fd_set(token: usize, set: *const u64, nfds: usize) -> SysResult<Vec<u64>> {
+    let words = (nfds + 63) / 64;
+    if set.is_null() {
+        return Ok(vec![0; words]);
+    }
+    (0..words)
+        .map(|i| copy_from_user(token, set.wrapping_add(i)))
+        .collect()
+}
+
+fn store_fd_set(token: usize, set: *mut u64, bits: &[u64]) -> SysResult<()> {
+    if set.is_null() {
+        return Ok(());
     }
-    -1
+    for (i, word) in bits.iter().enumerate() {
+        copy_to_user(token, set.wrapping_add(i), word)?;
+    }
+    Ok(())
 }
 
-pub fn sys_munmap(start: usize, _len: usize) -> isize {
-    sys_brk(start);
-    0
+/// Wait for any fd of the three sets to become ready, then keep only the
+/// ready ones in the sets and return how many bits are left. `sigmask`
+/// points to the address and size of the mask to wait with, as in Linux.
+pub fn sys_pselect6(
+    nfds: usize,
+    readfds: *mut u64,
+    writefds: *mut u64,
+    exceptfds: *mut u64,
+    timeout: *const TimeSpec,
+    sigmask: *const [usize; 2],
+) -> SysResult {
+    if nfds > FD_SETSIZE {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let sets = [
+        load_fd_set(token, readfds, nfds)?,
+        load_fd_set(token, writefds, nfds)?,
+        load_fd_set(token, exceptfds, nfds)?,
+    ];
+    let deadline = deadline_of(token, timeout)?;
+    // what makes an fd count as ready in each set
+    let readiness = [
+        PollEvents::IN | PollEvents::HUP | PollEvents::ERR,
+        PollEvents::OUT | PollEvents::ERR,
+        PollEvents::PRI,
+    ];
+    let mut files = Vec::new();
+    for fd in 0..nfds {
+        if sets.iter().any(|set| set[fd / 64] & 1 << (fd % 64) != 0) {
+            files.push((fd, fd_file(fd)?));
+        }
+    }
+    let open_files: Vec<_> = files.iter().map(|(_, file)| Arc::clone(file)).collect();
+    let mut ready_sets = sets.clone();
+    if !sigmask.is_null() {
+        let [mask, _size] = copy_from_user(token, sigmask)?;
+        wait_with_sigmask(token, mask as *const u32)?;
+    }
+    let ready = wait_ready(&open_files, deadline, || {
+        let mut ready = 0;
+        for ready_set in ready_sets.iter_mut() {
+            ready_set.fill(0);
+        }
+        for (fd, file) in files.iter() {
+            let events = file.poll();
+            let bit = 1u64 << (fd % 64);
+            for i in 0..3 {
+                if sets[i][fd / 64] & bit != 0 && events.intersects(readiness[i]) {
+                    ready_sets[i][fd / 64] |= bit;
+                    ready += 1;
+                }
+            }
+        }
+        ready
+    })?;
+    store_fd_set(token, readfds, &ready_sets[0])?;
+    store_fd_set(token, writefds, &ready_sets[1])?;
+    store_fd_set(token, exceptfds, &ready_sets[2])?;
+    Ok(ready as isize)
 }
 
-pub fn sys_mount() -> isize {
-    0
+pub fn sys_epoll_create1(flags: usize) -> SysResult {
+    if flags & !EPOLL_CLOEXEC != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let fd = inner.alloc_fd()?;
+    inner.fd_table.insert(fd, Some(Arc::new(EpollFile::new())));
+    inner.set_cloexec(fd, flags & EPOLL_CLOEXEC != 0);
+    Ok(fd as isize)
 }
-pub fn sys_umount() -> isize {
-    0
+
+/// Add, change or remove the interest of the epoll instance `epfd` in `fd`.
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: *const EpollEvent) -> SysResult {
+    let token = current_user_token();
+    let epoll_file = fd_file(epfd)?;
+    let file = fd_file(fd)?;
+    let epoll = epoll_file.epoll().ok_or(SysError::EINVAL)?;
+    if fd == epfd {
+        return Err(SysError::EINVAL);
+    }
+    // the event of a removal may be null
+    let event = match event.is_null() {
+        true => EpollEvent::default(),
+        false => copy_from_user(token, event)?,
+    };
+    epoll.ctl(op, fd, &file, event)
+}
+
+/// Wait for at most `timeout` ms, forever if it is negative, until one of
+/// the files watched by `epfd` is ready, then store the ready ones. The
+/// wait blocks the signals at `sigmask` unless it is null.
+pub fn sys_epoll_pwait(
+    epfd: usize,
+    events: *mut EpollEvent,
+    maxevents: isize,
+    timeout: isize,
+    sigmask: *const u32,
+) -> SysResult {
+    if maxevents <= 0 {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let epoll_file = fd_file(epfd)?;
+    let epoll = epoll_file.epoll().ok_or(SysError::EINVAL)?;
+    let deadline = match timeout {
+        timeout if timeout < 0 => None,
+        timeout => Some(get_time_ms() + timeout as usize),
+    };
+    let mut ready = Vec::new();
+    wait_with_sigmask(token, sigmask)?;
+    wait_ready(&epoll.files(), deadline, || {
+        ready = epoll.ready(maxevents as usize);
+        ready.len()
+    })?;
+    for (i, event) in ready.iter().enumerate() {
+        copy_to_user(token, events.wrapping_add(i), event)?;
+    }
+    Ok(ready.len() as isize)
+}
+
+/// Where relative paths of the `*at` syscalls start: the working directory
+/// for AT_FDCWD, otherwise the directory open as `dirfd`.
+pub(super) fn dirfd_dentry(dirfd: isize) -> SysResult<Arc<Dentry>> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    if dirfd == AT_FDCWD {
+        return Ok(Arc::clone(&inner.cwd));
+    }
+    match inner.fd_table.get(&(dirfd as usize)) {
+        Some(Some(file)) => match file.dentry() {
+            Some(dentry) if dentry.is_dir() => Ok(dentry),
+            _ => Err(SysError::ENOTDIR),
+        },
+        _ => Err(SysError::EBADF),
+    }
+}
+
+/// The file open as `fd`.
+pub(super) fn fd_file(fd: usize) -> SysResult<Arc<dyn File>> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    match inner.fd_table.get(&fd) {
+        Some(Some(file)) => Ok(Arc::clone(file)),
+        _ => Err(SysError::EBADF),
+    }
 }
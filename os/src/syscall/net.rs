@@ -0,0 +1,237 @@
+use super::fs::{dirfd_dentry, fd_file};
+use crate::error::{SysError, SysResult};
+use crate::fs::{File, OpenFlags, AT_FDCWD};
+use crate::mm::{
+    copy_bytes_from_user, copy_bytes_to_user, copy_from_user, copy_to_user, user_byte_buffer,
+    MapPermission,
+};
+use crate::net::{MsgFlags, SocketType, UnixSocket, AF_UNIX};
+use crate::task::{current_process, current_user_token};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+const SOCK_STREAM: usize = 1;
+const SOCK_DGRAM: usize = 2;
+/// Flags which may be or-ed into the type, the same bits as O_NONBLOCK and
+/// O_CLOEXEC.
+const SOCK_NONBLOCK: usize = 0o4000;
+const SOCK_CLOEXEC: usize = 0o2000000;
+
+/// Size of `struct sockaddr_un`: the family and a path of up to 108 bytes.
+const SOCKADDR_UN_SIZE: usize = 2 + 108;
+
+/// Split the `type` of `socket` into the socket type and the file flags.
+fn parse_type(ty: usize) -> SysResult<(SocketType, OpenFlags)> {
+    let flags = OpenFlags::from_bits_truncate((ty & (SOCK_NONBLOCK | SOCK_CLOEXEC)) as u32);
+    let socket_type = match ty & !(SOCK_NONBLOCK | SOCK_CLOEXEC) {
+        SOCK_STREAM => SocketType::Stream,
+        SOCK_DGRAM => SocketType::Datagram,
+        _ => return Err(SysError::EINVAL),
+    };
+    Ok((socket_type, flags))
+}
+
+/// The path of the `sockaddr_un` at `addr`. Abstract names, starting with
+/// a zero byte, are not supported.
+fn read_sockaddr(token: usize, addr: *const u8, addrlen: usize) -> SysResult<String> {
+    if addrlen <= 2 || addrlen > SOCKADDR_UN_SIZE {
+        return Err(SysError::EINVAL);
+    }
+    let mut raw = [0u8; SOCKADDR_UN_SIZE];
+    copy_bytes_from_user(token, addr, &mut raw[..addrlen])?;
+    if u16::from_ne_bytes([raw[0], raw[1]]) as usize != AF_UNIX {
+        return Err(SysError::EAFNOSUPPORT);
+    }
+    let path = &raw[2..addrlen];
+    let len = path.iter().position(|b| *b == 0).unwrap_or(path.len());
+    if len == 0 {
+        return Err(SysError::EINVAL);
+    }
+    String::from_utf8(path[..len].to_vec()).map_err(|_| SysError::EINVAL)
+}
+
+/// Store the address `path` at `addr`, cut to the room `*addrlen` gives,
+/// and its full length in `*addrlen`. An unbound socket has no path.
+fn write_sockaddr(
+    token: usize,
+    addr: *mut u8,
+    addrlen: *mut u32,
+    path: Option<String>,
+) -> SysResult<()> {
+    if addr.is_null() {
+        return Ok(());
+    }
+    let room = copy_from_user(token, addrlen as *const u32)? as usize;
+    let mut raw = Vec::from((AF_UNIX as u16).to_ne_bytes());
+    if let Some(path) = path {
+        raw.extend_from_slice(path.as_bytes());
+        raw.push(0);
+    }
+    copy_bytes_to_user(token, addr, &raw[..raw.len().min(room)])?;
+    copy_to_user(token, addrlen, &(raw.len() as u32))
+}
+
+/// Put `file` into the lowest free fd.
+fn install(file: Arc<dyn File>, flags: OpenFlags) -> SysResult<usize> {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let fd = inner.alloc_fd()?;
+    inner.fd_table.insert(fd, Some(file));
+    inner.set_cloexec(fd, flags.contains(OpenFlags::CLOEXEC));
+    Ok(fd)
+}
+
+/// The socket open as `fd`, kept alive by the returned file.
+fn fd_socket(fd: usize) -> SysResult<Arc<dyn File>> {
+    let file = fd_file(fd)?;
+    file.socket().ok_or(SysError::ENOTSOCK)?;
+    Ok(file)
+}
+
+pub fn sys_socket(domain: usize, ty: usize, protocol: usize) -> SysResult {
+    if domain != AF_UNIX {
+        return Err(SysError::EAFNOSUPPORT);
+    }
+    let (socket_type, flags) = parse_type(ty)?;
+    if protocol != 0 {
+        return Err(SysError::EINVAL);
+    }
+    Ok(install(UnixSocket::new(socket_type, flags), flags)? as isize)
+}
+
+/// Store the fds of two connected sockets at `sv`.
+pub fn sys_socketpair(domain: usize, ty: usize, protocol: usize, sv: *mut i32) -> SysResult {
+    if domain != AF_UNIX {
+        return Err(SysError::EAFNOSUPPORT);
+    }
+    let (socket_type, flags) = parse_type(ty)?;
+    if protocol != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let (first, second) = UnixSocket::pair(socket_type, flags);
+    let first = install(first, flags)?;
+    let second = match install(second, flags) {
+        Ok(fd) => fd,
+        Err(err) => {
+            let process = current_process();
+            let mut inner = process.inner_exclusive_access();
+            inner.fd_table.remove(&first);
+            inner.set_cloexec(first, false);
+            return Err(err);
+        }
+    };
+    let fds = [first as i32, second as i32];
+    if let Err(err) = copy_to_user(token, sv as *mut [i32; 2], &fds) {
+        let process = current_process();
+        let mut inner = process.inner_exclusive_access();
+        for fd in [first, second] {
+            inner.fd_table.remove(&fd);
+            inner.set_cloexec(fd, false);
+        }
+        return Err(err);
+    }
+    Ok(0)
+}
+
+pub fn sys_bind(fd: usize, addr: *const u8, addrlen: usize) -> SysResult {
+    let token = current_user_token();
+    let file = fd_socket(fd)?;
+    let path = read_sockaddr(token, addr, addrlen)?;
+    let start = dirfd_dentry(AT_FDCWD)?;
+    file.socket().unwrap().bind(&start, &path)?;
+    Ok(0)
+}
+
+pub fn sys_listen(fd: usize, backlog: usize) -> SysResult {
+    fd_socket(fd)?.socket().unwrap().listen(backlog)?;
+    Ok(0)
+}
+
+/// Take a connection as a new fd, storing the address of the peer at
+/// `addr`. Only SOCK_NONBLOCK and SOCK_CLOEXEC go into `flags`.
+pub fn sys_accept4(fd: usize, addr: *mut u8, addrlen: *mut u32, flags: usize) -> SysResult {
+    if flags & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let flags = OpenFlags::from_bits_truncate(flags as u32);
+    let token = current_user_token();
+    let file = fd_socket(fd)?;
+    let server = file.socket().unwrap().accept()?;
+    server.set_status_flags(flags);
+    write_sockaddr(token, addr, addrlen, server.peer_path()?)?;
+    Ok(install(server, flags)? as isize)
+}
+
+pub fn sys_connect(fd: usize, addr: *const u8, addrlen: usize) -> SysResult {
+    let token = current_user_token();
+    let file = fd_socket(fd)?;
+    let path = read_sockaddr(token, addr, addrlen)?;
+    let start = dirfd_dentry(AT_FDCWD)?;
+    file.socket().unwrap().connect(&start, &path)?;
+    Ok(0)
+}
+
+/// Send `len` bytes at `buf`, to the socket bound to `addr` if it is
+/// not null.
+pub fn sys_sendto(
+    fd: usize,
+    buf: *const u8,
+    len: usize,
+    flags: u32,
+    addr: *const u8,
+    addrlen: usize,
+) -> SysResult {
+    let token = current_user_token();
+    let file = fd_socket(fd)?;
+    let flags = MsgFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    let dest = match addr.is_null() {
+        true => None,
+        false => Some((
+            dirfd_dentry(AT_FDCWD)?,
+            read_sockaddr(token, addr, addrlen)?,
+        )),
+    };
+    let buffer = user_byte_buffer(token, buf, len, MapPermission::R)?;
+    let dest = dest.as_ref().map(|(start, path)| (start, path.as_str()));
+    Ok(file.socket().unwrap().send(buffer, dest, flags)? as isize)
+}
+
+/// Receive up to `len` bytes into `buf`, storing the address of the
+/// sender at `addr` if it is not null.
+pub fn sys_recvfrom(
+    fd: usize,
+    buf: *mut u8,
+    len: usize,
+    flags: u32,
+    addr: *mut u8,
+    addrlen: *mut u32,
+) -> SysResult {
+    let token = current_user_token();
+    let file = fd_socket(fd)?;
+    let flags = MsgFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    let buffer = user_byte_buffer(token, buf, len, MapPermission::W)?;
+    let (len, sender) = file.socket().unwrap().recv(buffer, flags)?;
+    write_sockaddr(token, addr, addrlen, sender)?;
+    Ok(len as isize)
+}
+
+pub fn sys_getsockname(fd: usize, addr: *mut u8, addrlen: *mut u32) -> SysResult {
+    let token = current_user_token();
+    let path = fd_socket(fd)?.socket().unwrap().path();
+    write_sockaddr(token, addr, addrlen, path)?;
+    Ok(0)
+}
+
+pub fn sys_getpeername(fd: usize, addr: *mut u8, addrlen: *mut u32) -> SysResult {
+    let token = current_user_token();
+    let path = fd_socket(fd)?.socket().unwrap().peer_path()?;
+    write_sockaddr(token, addr, addrlen, path)?;
+    Ok(0)
+}
+
+pub fn sys_shutdown(fd: usize, how: usize) -> SysResult {
+    fd_socket(fd)?.socket().unwrap().shutdown(how)?;
+    Ok(0)
+}
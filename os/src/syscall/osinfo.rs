@@ -1,5 +1,6 @@
 use crate::{
-    mm::translated_refmut,
+    error::SysResult,
+    mm::copy_to_user,
     task::current_user_token,
     timer::{get_time_sec, get_time_usec},
 };
@@ -19,16 +20,24 @@ pub struct Utsname {
     domainname: [u8; 65],
 }
 
-pub fn sys_uname(ptr: *mut Utsname) -> isize {
+pub fn sys_uname(ptr: *mut Utsname) -> SysResult {
     let token = current_user_token();
-    let uname = translated_refmut(token, ptr);
+    let mut uname = Utsname {
+        sysname: [0; 65],
+        nodename: [0; 65],
+        release: [0; 65],
+        version: [0; 65],
+        machine: [0; 65],
+        domainname: [0; 65],
+    };
     uname.sysname[0..SYSNAME.len()].copy_from_slice(SYSNAME);
     uname.nodename[0..NODENAME.len()].copy_from_slice(NODENAME);
     uname.release[0..RELEASE.len()].copy_from_slice(RELEASE);
     uname.machine[0..MACHINE.len()].copy_from_slice(MACHINE);
     uname.version[0..VERSION.len()].copy_from_slice(VERSION);
     uname.domainname[0..DOMAINNAME.len()].copy_from_slice(DOMAINNAME);
-    0
+    copy_to_user(token, ptr, &uname)?;
+    Ok(0)
 }
 
 #[repr(C)]
@@ -37,10 +46,12 @@ pub struct TimeVal {
     pub usec: usize,
 }
 
-pub fn sys_get_time(ptr: *mut TimeVal) -> isize {
+pub fn sys_get_time(ptr: *mut TimeVal) -> SysResult {
     let token = current_user_token();
-    let mut time = translated_refmut(token, ptr);
-    time.sec = get_time_sec();
-    time.usec = get_time_usec();
-    0
+    let time = TimeVal {
+        sec: get_time_sec(),
+        usec: get_time_usec(),
+    };
+    copy_to_user(token, ptr, &time)?;
+    Ok(0)
 }
@@ -1,8 +1,12 @@
-use crate::fs::{open_file, OpenFlags};
-use crate::mm::{translated_ref, translated_refmut, translated_str};
+use crate::config::{USER_SPACE_END, USER_STACK_SIZE};
+use crate::error::{SysError, SysResult};
+use crate::fs::{lookup, OSInode};
+use crate::mm::{copy_from_user, copy_str_from_user, copy_to_user, VirtAddr};
 use crate::task::{
-    current_process, current_task, current_user_token, exit_current_and_run_next,
-    suspend_current_and_run_next,
+    current_process, current_signal_pending, current_task, current_user_token,
+    exit_current_and_run_next, pid2process, restore_signal_frame, send_signal,
+    suspend_current_and_run_next, ProcessControlBlock, SignalAction, SignalFlags, MAX_SIG,
+    NICE_MAX, NICE_MIN, SIG_BLOCK, SIG_SETMASK, SIG_UNBLOCK,
 };
 use alloc::string::String;
 use alloc::sync::Arc;
@@ -14,20 +18,20 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("Unreachable in sys_exit!");
 }
 
-pub fn sys_yield() -> isize {
+pub fn sys_yield() -> SysResult {
     suspend_current_and_run_next();
-    0
+    Ok(0)
 }
 
-pub fn sys_getpid() -> isize {
-    current_task().unwrap().process.upgrade().unwrap().getpid() as isize
+pub fn sys_getpid() -> SysResult {
+    Ok(current_task().unwrap().process.upgrade().unwrap().getpid() as isize)
 }
 
-pub fn sys_getppid() -> isize {
-    current_task().unwrap().process.upgrade().unwrap().getppid() as isize
+pub fn sys_getppid() -> SysResult {
+    Ok(current_task().unwrap().process.upgrade().unwrap().getppid() as isize)
 }
 
-pub fn sys_fork() -> isize {
+pub fn sys_fork() -> SysResult {
     let current_process = current_process();
     let new_process = current_process.fork();
     let new_pid = new_process.getpid();
@@ -38,38 +42,49 @@ pub fn sys_fork() -> isize {
     // we do not have to move to next instruction since we have done it before
     // for child process, fork returns 0
     trap_cx.x[10] = 0;
-    new_pid as isize
+    Ok(new_pid as isize)
 }
 
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
+/// Arguments of `sys_exec` may take up at most this much of the new user stack.
+const ARG_MAX: usize = USER_STACK_SIZE / 2;
+
+pub fn sys_exec(path: *const u8, mut args: *const usize) -> SysResult {
     let token = current_user_token();
-    let path = translated_str(token, path);
+    let path = copy_str_from_user(token, path)?;
     let mut args_vec: Vec<String> = Vec::new();
+    // the argv array and its terminating null
+    let mut args_size = core::mem::size_of::<usize>();
     loop {
-        let arg_str_ptr = *translated_ref(token, args);
+        let arg_str_ptr = copy_from_user(token, args)?;
         if arg_str_ptr == 0 {
             break;
         }
-        args_vec.push(translated_str(token, arg_str_ptr as *const u8));
+        let arg = copy_str_from_user(token, arg_str_ptr as *const u8)?;
+        args_size += core::mem::size_of::<usize>() + arg.len() + 1;
+        if args_size > ARG_MAX {
+            return Err(SysError::E2BIG);
+        }
+        args_vec.push(arg);
         unsafe {
             args = args.add(1);
         }
     }
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        let process = current_process();
-        let argc = args_vec.len();
-        process.exec(all_data.as_slice(), args_vec);
-        // return argc because cx.x[10] will be covered with it later
-        argc as isize
-    } else {
-        -1
+    let process = current_process();
+    let cwd = Arc::clone(&process.inner_exclusive_access().cwd);
+    let dentry = lookup(&cwd, path.as_str())?;
+    if dentry.is_dir() {
+        return Err(SysError::EACCES);
     }
+    let app_inode = Arc::new(OSInode::new(true, false, dentry));
+    let argc = args_vec.len();
+    process.exec(app_inode, args_vec);
+    // return argc because cx.x[10] will be covered with it later
+    Ok(argc as isize)
 }
 
-/// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// Wait for the child `pid`, or any child if it is -1, to exit and return
+/// its pid. Fail with ECHILD if there is no such child.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> SysResult {
     let process = current_process();
     let inner = process.inner_exclusive_access();
     if inner
@@ -78,7 +93,7 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         .find(|p| pid == -1 || pid as usize == p.getpid())
         .is_none()
     {
-        return -1;
+        return Err(SysError::ECHILD);
     }
     drop(inner);
     loop {
@@ -90,30 +105,165 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
                 .iter()
                 .enumerate()
                 .find(|(_, p)| match p.try_inner_exclusive_access() {
-                    Ok(proc) => proc.is_zombie && (pid == -1 || pid as usize == p.getpid()),
-                    Err(_) => false,
+                    Some(proc) => {
+                        proc.is_zombie
+                            && proc.off_cpu()
+                            && (pid == -1 || pid as usize == p.getpid())
+                    }
+                    None => false,
                 })
         {
+            // procfs may still hold the child for a moment, it is freed
+            // with whoever drops it last
             let child = inner.children.remove(idx);
-            assert_eq!(Arc::strong_count(&child), 1);
             let found_pid = child.getpid();
             let exit_code = child.inner_exclusive_access().exit_code;
+            let token = inner.memory_set.token();
+            drop(inner);
             if exit_code_ptr as usize != 0 {
-                *translated_refmut(inner.memory_set.token(), exit_code_ptr) = 3 << 8 | exit_code;
+                copy_to_user(token, exit_code_ptr, &(3 << 8 | exit_code))?;
             }
-            return found_pid as isize;
+            return Ok(found_pid as isize);
         }
         drop(inner);
+        if current_signal_pending() {
+            return Err(SysError::EINTR);
+        }
         suspend_current_and_run_next()
     }
 }
 
-pub fn sys_brk(addr: usize) -> isize {
-    let task = current_task().unwrap();
-    let mut inner = task.inner_lock_access();
-    if let Some(res) = inner.res.as_mut() {
-        res.brk(addr) as isize
+/// Move the program break to `addr` and return the new break.
+/// On failure the break stays put and its current value is returned.
+pub fn sys_brk(addr: usize) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let heap_bottom = inner.heap_bottom;
+    if addr < heap_bottom || addr > USER_SPACE_END {
+        return Ok(inner.program_brk as isize);
+    }
+    let result = if addr < inner.program_brk {
+        inner
+            .memory_set
+            .shrink_to(VirtAddr(heap_bottom), VirtAddr(addr))
+    } else {
+        inner
+            .memory_set
+            .append_to(VirtAddr(heap_bottom), VirtAddr(addr))
+    };
+    if result {
+        inner.program_brk = addr;
+    }
+    Ok(inner.program_brk as isize)
+}
+
+pub fn sys_kill(pid: usize, signum: usize) -> SysResult {
+    let signal = if signum == 0 {
+        // only probe for existence
+        SignalFlags::empty()
     } else {
-        -1
+        SignalFlags::from_signum(signum).ok_or(SysError::EINVAL)?
+    };
+    if send_signal(pid, signal) {
+        Ok(0)
+    } else {
+        Err(SysError::ESRCH)
+    }
+}
+
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> SysResult {
+    let signal = SignalFlags::from_signum(signum).ok_or(SysError::EINVAL)?;
+    if signal.intersects(SignalFlags::unmaskable()) {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let new_action = if action as usize != 0 {
+        Some(copy_from_user(token, action)?)
+    } else {
+        None
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let prev_action = inner.signal_actions.table[signum];
+    if let Some(mut new_action) = new_action {
+        new_action.mask = SignalFlags::from_bits_truncate(new_action.mask.bits());
+        inner.signal_actions.table[signum] = new_action;
+    }
+    drop(inner);
+    if old_action as usize != 0 {
+        copy_to_user(token, old_action, &prev_action)?;
+    }
+    Ok(0)
+}
+
+pub fn sys_sigprocmask(how: usize, set: *const u32, old_set: *mut u32) -> SysResult {
+    let token = current_user_token();
+    let set = if set as usize != 0 {
+        Some(SignalFlags::from_bits_truncate(copy_from_user(token, set)?))
+    } else {
+        None
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    if let Some(set) = set {
+        inner.signal_mask = match how {
+            SIG_BLOCK => old_mask | set,
+            SIG_UNBLOCK => old_mask - set,
+            SIG_SETMASK => set,
+            _ => return Err(SysError::EINVAL),
+        } - SignalFlags::unmaskable();
+    }
+    drop(inner);
+    if old_set as usize != 0 {
+        copy_to_user(token, old_set, &old_mask.bits())?;
+    }
+    Ok(0)
+}
+
+pub fn sys_sigreturn() -> SysResult {
+    restore_signal_frame()
+}
+
+const PRIO_PROCESS: usize = 0;
+
+fn process_by_pid(pid: usize) -> SysResult<Arc<ProcessControlBlock>> {
+    if pid == 0 {
+        Ok(current_process())
+    } else {
+        pid2process(pid).ok_or(SysError::ESRCH)
+    }
+}
+
+/// Set the nice value of every thread of a process, out of range values are clamped.
+pub fn sys_setpriority(which: usize, who: usize, nice: isize) -> SysResult {
+    if which != PRIO_PROCESS {
+        return Err(SysError::EINVAL);
+    }
+    let process = process_by_pid(who)?;
+    let nice = nice.clamp(NICE_MIN, NICE_MAX);
+    let inner = process.inner_exclusive_access();
+    for task in inner.tasks.iter().flatten() {
+        task.sched.lock().nice = nice;
+    }
+    Ok(0)
+}
+
+/// Return `20 - nice` of the main thread, as Linux does to keep it positive.
+pub fn sys_getpriority(which: usize, who: usize) -> SysResult {
+    if which != PRIO_PROCESS {
+        return Err(SysError::EINVAL);
     }
+    let process = process_by_pid(who)?;
+    let nice = process
+        .inner_exclusive_access()
+        .get_task(0)
+        .sched
+        .lock()
+        .nice;
+    Ok(20 - nice)
 }
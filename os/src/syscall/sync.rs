@@ -1,17 +1,84 @@
-use crate::sync::{MutexBlocking, MutexSpin, Semaphore};
-use crate::task::{block_current_and_run_next, current_process, current_task};
-use crate::timer::{add_timer, get_time_ms};
+use crate::error::{SysError, SysResult};
+use crate::mm::{copy_from_user, user_byte_buffer, MapPermission, PhysAddr};
+use crate::sync::{
+    futex_wait, futex_wake, Condvar, FutexWait, Mutex, MutexBlocking, MutexSpin, Semaphore,
+};
+use crate::task::{
+    block_current_interruptible, cancel_current_blocked, current_process, current_task,
+    current_user_token, mark_current_blocked,
+};
+use crate::timer::{add_timer, get_time_ms, remove_timer};
 use alloc::sync::Arc;
 
-pub fn sys_sleep(ms: usize) -> isize {
+/// Sleep for `ms` milliseconds, or until a signal comes.
+pub fn sys_sleep(ms: usize) -> SysResult {
     let expire_ms = get_time_ms() + ms;
     let task = current_task().unwrap();
-    add_timer(expire_ms, task);
-    block_current_and_run_next();
-    0
+    add_timer(expire_ms, Arc::clone(&task));
+    loop {
+        mark_current_blocked();
+        if get_time_ms() >= expire_ms {
+            cancel_current_blocked();
+            break;
+        }
+        if let Err(err) = block_current_interruptible() {
+            remove_timer(&task);
+            return Err(err);
+        }
+    }
+    remove_timer(&task);
+    Ok(0)
+}
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+/// Every futex is keyed by its physical address, so private ones are no different.
+const FUTEX_PRIVATE_FLAG: usize = 128;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+/// Wait on or wake up the futex word at `uaddr`. `FUTEX_WAIT` sleeps only
+/// while the word holds `val`, for at most `timeout` unless it is null.
+/// `FUTEX_WAKE` wakes up at most `val` waiters and returns their number.
+pub fn sys_futex(uaddr: *const u32, op: usize, val: u32, timeout: *const TimeSpec) -> SysResult {
+    if uaddr as usize % core::mem::size_of::<u32>() != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let timeout_ms = if op & !FUTEX_PRIVATE_FLAG == FUTEX_WAIT && !timeout.is_null() {
+        let timeout = copy_from_user(token, timeout)?;
+        Some(timeout.sec * 1000 + timeout.nsec / 1_000_000)
+    } else {
+        None
+    };
+    // the word is made private and writable first, a later copy-on-write
+    // fault would move it to another frame and thus to another queue
+    let word = user_byte_buffer(
+        token,
+        uaddr as *const u8,
+        core::mem::size_of::<u32>(),
+        MapPermission::W,
+    )?;
+    // physical memory is mapped one to one into the kernel
+    let pa = PhysAddr::from(word.buffers[0].as_ptr() as usize);
+    match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => match futex_wait(pa, val, timeout_ms) {
+            FutexWait::Woken => Ok(0),
+            FutexWait::Mismatch => Err(SysError::EAGAIN),
+            FutexWait::TimedOut => Err(SysError::ETIMEDOUT),
+            FutexWait::Interrupted => Err(SysError::EINTR),
+        },
+        FUTEX_WAKE => Ok(futex_wake(pa, val as usize) as isize),
+        _ => Err(SysError::EINVAL),
+    }
 }
 
-pub fn sys_mutex_create(blocking: bool) -> isize {
+pub fn sys_mutex_create(blocking: bool) -> SysResult {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
     if let Some(id) = process_inner
@@ -26,36 +93,31 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
         } else {
             Some(Arc::new(MutexBlocking::new()))
         };
-        id as isize
+        Ok(id as isize)
     } else {
-        process_inner
-            .mutex_list
-            .push(Some(Arc::new(MutexSpin::new())));
-        process_inner.mutex_list.len() as isize - 1
+        let mutex: Arc<dyn Mutex> = if !blocking {
+            Arc::new(MutexSpin::new())
+        } else {
+            Arc::new(MutexBlocking::new())
+        };
+        process_inner.mutex_list.push(Some(mutex));
+        Ok(process_inner.mutex_list.len() as isize - 1)
     }
 }
 
-pub fn sys_mutex_lock(mutex_id: usize) -> isize {
-    let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
-    drop(process_inner);
-    drop(process);
+pub fn sys_mutex_lock(mutex_id: usize) -> SysResult {
+    let mutex = mutex_by_id(mutex_id)?;
     mutex.lock();
-    0
+    Ok(0)
 }
 
-pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
-    let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
-    drop(process_inner);
-    drop(process);
+pub fn sys_mutex_unlock(mutex_id: usize) -> SysResult {
+    let mutex = mutex_by_id(mutex_id)?;
     mutex.unlock();
-    0
+    Ok(0)
 }
 
-pub fn sys_semaphore_creare(res_count: usize) -> isize {
+pub fn sys_semaphore_creare(res_count: usize) -> SysResult {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
     let id = if let Some(id) = process_inner
@@ -73,23 +135,96 @@ pub fn sys_semaphore_creare(res_count: usize) -> isize {
             .push(Some(Arc::new(Semaphore::new(res_count))));
         process_inner.semaphore_list.len() - 1
     };
-    id as isize
+    Ok(id as isize)
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> SysResult {
+    let sem = semaphore_by_id(sem_id)?;
+    sem.up();
+    Ok(0)
+}
+
+pub fn sys_semaphore_down(sem_id: usize) -> SysResult {
+    let sem = semaphore_by_id(sem_id)?;
+    sem.down()?;
+    Ok(0)
+}
+
+pub fn sys_semaphore_try_down(sem_id: usize) -> SysResult {
+    let sem = semaphore_by_id(sem_id)?;
+    if sem.try_down() {
+        Ok(0)
+    } else {
+        Err(SysError::EAGAIN)
+    }
+}
+
+pub fn sys_semaphore_down_timeout(sem_id: usize, timeout_ms: usize) -> SysResult {
+    let sem = semaphore_by_id(sem_id)?;
+    if sem.down_timeout(timeout_ms)? {
+        Ok(0)
+    } else {
+        Err(SysError::ETIMEDOUT)
+    }
+}
+
+pub fn sys_condvar_create() -> SysResult {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let id = if let Some(id) = process_inner
+        .condvar_list
+        .iter()
+        .enumerate()
+        .find(|(_, item)| item.is_none())
+        .map(|(id, _)| id)
+    {
+        process_inner.condvar_list[id] = Some(Arc::new(Condvar::new()));
+        id
+    } else {
+        process_inner
+            .condvar_list
+            .push(Some(Arc::new(Condvar::new())));
+        process_inner.condvar_list.len() - 1
+    };
+    Ok(id as isize)
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> SysResult {
+    let condvar = condvar_by_id(condvar_id)?;
+    condvar.signal();
+    Ok(0)
 }
 
-pub fn sys_semaphore_up(sem_id: usize) -> isize {
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> SysResult {
+    let condvar = condvar_by_id(condvar_id)?;
+    let mutex = mutex_by_id(mutex_id)?;
+    condvar.wait(mutex)?;
+    Ok(0)
+}
+
+fn mutex_by_id(mutex_id: usize) -> SysResult<Arc<dyn Mutex>> {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
-    drop(process_inner);
-    sem.up();
-    0
+    match process_inner.mutex_list.get(mutex_id) {
+        Some(Some(mutex)) => Ok(Arc::clone(mutex)),
+        _ => Err(SysError::EINVAL),
+    }
 }
 
-pub fn sys_semaphore_down(sem_id: usize) -> isize {
+fn semaphore_by_id(sem_id: usize) -> SysResult<Arc<Semaphore>> {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
-    drop(process_inner);
-    sem.down();
-    0
+    match process_inner.semaphore_list.get(sem_id) {
+        Some(Some(sem)) => Ok(Arc::clone(sem)),
+        _ => Err(SysError::EINVAL),
+    }
+}
+
+fn condvar_by_id(condvar_id: usize) -> SysResult<Arc<Condvar>> {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    match process_inner.condvar_list.get(condvar_id) {
+        Some(Some(condvar)) => Ok(Arc::clone(condvar)),
+        _ => Err(SysError::EINVAL),
+    }
 }
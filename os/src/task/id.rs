@@ -1,7 +1,7 @@
 use super::ProcessControlBlock;
 use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_STACK_SIZE};
 use crate::mm::{MapPermission, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPSafeCell;
+use crate::sync::SpinMutex;
 use alloc::{
     sync::{Arc, Weak},
     vec::Vec,
@@ -40,21 +40,20 @@ impl RecycleAllocator {
 }
 
 lazy_static! {
-    static ref PID_ALLOCATOR: UPSafeCell<RecycleAllocator> =
-        unsafe { UPSafeCell::new(RecycleAllocator::new()) };
-    static ref KSTACK_ALLOCATOR: UPSafeCell<RecycleAllocator> =
-        unsafe { UPSafeCell::new(RecycleAllocator::new()) };
+    static ref PID_ALLOCATOR: SpinMutex<RecycleAllocator> = SpinMutex::new(RecycleAllocator::new());
+    static ref KSTACK_ALLOCATOR: SpinMutex<RecycleAllocator> =
+        SpinMutex::new(RecycleAllocator::new());
 }
 
 pub struct PidHandle(pub usize);
 
 pub fn pid_alloc() -> PidHandle {
-    PidHandle(PID_ALLOCATOR.inner.borrow_mut().alloc())
+    PidHandle(PID_ALLOCATOR.lock().alloc())
 }
 
 impl Drop for PidHandle {
     fn drop(&mut self) {
-        PID_ALLOCATOR.inner.borrow_mut().dealloc(self.0);
+        PID_ALLOCATOR.lock().dealloc(self.0);
     }
 }
 
@@ -68,9 +67,9 @@ pub fn kernel_stack_position(kstack_id: usize) -> (usize, usize) {
 pub struct KernelStack(pub usize);
 
 pub fn kstack_alloc() -> KernelStack {
-    let kstack_id = KSTACK_ALLOCATOR.inner.borrow_mut().alloc();
+    let kstack_id = KSTACK_ALLOCATOR.lock().alloc();
     let (kstack_bottom, kstack_top) = kernel_stack_position(kstack_id);
-    KERNEL_SPACE.inner.borrow_mut().insert_framed_area(
+    KERNEL_SPACE.lock().insert_framed_area(
         kstack_bottom.into(),
         kstack_top.into(),
         MapPermission::R | MapPermission::W,
@@ -83,8 +82,7 @@ impl Drop for KernelStack {
         let (kernel_stack_bottom, _) = kernel_stack_position(self.0);
         let kernel_stack_bottom_va: VirtAddr = kernel_stack_bottom.into();
         KERNEL_SPACE
-            .inner
-            .borrow_mut()
+            .lock()
             .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
     }
 }
@@ -111,7 +109,6 @@ impl KernelStack {
 pub struct TaskUserRes {
     pub tid: usize,
     pub ustack_base: usize,
-    pub brk_addr: usize,
     pub process: Weak<ProcessControlBlock>,
 }
 
@@ -134,7 +131,6 @@ impl TaskUserRes {
         let task_user_res = Self {
             tid,
             ustack_base,
-            brk_addr: ustack_base - PAGE_SIZE,
             process: Arc::downgrade(&process),
         };
         if alloc_user_res {
@@ -218,17 +214,6 @@ impl TaskUserRes {
     pub fn ustack_top(&self) -> usize {
         ustack_bottom_from_tid(self.ustack_base, self.tid) + USER_STACK_SIZE
     }
-
-    pub fn brk(&mut self, offset: usize) -> usize {
-        println!("brk_addr: {:#x} {:#x}", self.brk_addr, self.ustack_base);
-        match offset {
-            0 => self.brk_addr,
-            _ => {
-                self.brk_addr = offset;
-                self.brk_addr
-            }
-        }
-    }
 }
 
 impl Drop for TaskUserRes {
@@ -1,37 +1,92 @@
-use crate::sync::{SpinMutex, UPSafeCell};
+use crate::sync::SpinMutex;
 
-use super::TaskControlBlock;
-use alloc::collections::VecDeque;
+use super::scheduler::{scheduler_by_name, Scheduler};
+use super::{current_hartid, ProcessControlBlock, TaskControlBlock};
+use crate::config::{MAX_HARTID, SCHED_POLICY};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    scheduler: Box<dyn Scheduler + Send>,
 }
 
-/// A simple FIFO scheduler.
+/// Ready tasks of one hart, ordered by the scheduler chosen at boot.
 impl TaskManager {
     pub fn new() -> Self {
-        Self {
-            ready_queue: VecDeque::new(),
-        }
+        let scheduler = scheduler_by_name(SCHED_POLICY)
+            .unwrap_or_else(|| panic!("unknown scheduler {}", SCHED_POLICY));
+        Self { scheduler }
     }
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+        self.scheduler.add(task);
     }
 
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
+        self.scheduler.fetch()
+    }
+
+    pub fn tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        self.scheduler.tick(task)
+    }
+
+    pub fn ready_count(&self) -> usize {
+        self.scheduler.ready_count()
     }
 }
 
 lazy_static! {
-    static ref TASK_MANAGER: SpinMutex<TaskManager> = SpinMutex::new(TaskManager::new());
+    /// One run queue per hart, a hart running out of tasks steals from the others.
+    static ref TASK_MANAGERS: Vec<SpinMutex<TaskManager>> = {
+        println!("[kernel] scheduler: {}", SCHED_POLICY);
+        (0..MAX_HARTID)
+            .map(|_| SpinMutex::new(TaskManager::new()))
+            .collect()
+    };
+    static ref PID2PCB: SpinMutex<BTreeMap<usize, Arc<ProcessControlBlock>>> =
+        SpinMutex::new(BTreeMap::new());
 }
 
+/// Queue `task` on the current hart, where its caches are likely warm.
 pub fn add_task(task: Arc<TaskControlBlock>) {
-    TASK_MANAGER.lock().add(task);
+    TASK_MANAGERS[current_hartid()].lock().add(task);
 }
 
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
-    TASK_MANAGER.lock().fetch()
+    let hartid = current_hartid();
+    let task = TASK_MANAGERS[hartid].lock().fetch();
+    task.or_else(|| steal_task(hartid))
+}
+
+/// Take a task from the longest run queue of the other harts.
+fn steal_task(hartid: usize) -> Option<Arc<TaskControlBlock>> {
+    let victim = (0..MAX_HARTID)
+        .filter(|&id| id != hartid)
+        .max_by_key(|&id| TASK_MANAGERS[id].lock().ready_count())?;
+    TASK_MANAGERS[victim].lock().fetch()
+}
+
+/// Account a timer tick to `task`, return true if it should be preempted.
+pub fn tick_task(task: &Arc<TaskControlBlock>) -> bool {
+    TASK_MANAGERS[current_hartid()].lock().tick(task)
+}
+
+pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    PID2PCB.lock().get(&pid).map(Arc::clone)
+}
+
+pub fn insert_into_pid2process(pid: usize, process: Arc<ProcessControlBlock>) {
+    PID2PCB.lock().insert(pid, process);
+}
+
+pub fn remove_from_pid2process(pid: usize) {
+    if PID2PCB.lock().remove(&pid).is_none() {
+        panic!("cannot find pid {} in pid2process!", pid);
+    }
+}
+
+/// Snapshot of all live processes.
+pub fn process_list() -> Vec<Arc<ProcessControlBlock>> {
+    PID2PCB.lock().values().cloned().collect()
 }
@@ -3,22 +3,33 @@ mod id;
 mod manager;
 mod process;
 mod processor;
+mod scheduler;
+mod signal;
 mod switch;
 mod task;
 
-use crate::fs::{open_file, OpenFlags};
+use crate::error::{SysError, SysResult};
+use crate::fs::{lookup, root_dentry, OSInode};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
-use manager::fetch_task;
-use process::ProcessControlBlock;
+use manager::{fetch_task, remove_from_pid2process};
 use switch::__switch;
 
 pub use context::TaskContext;
 pub use id::{kstack_alloc, pid_alloc, KernelStack, PidHandle};
-pub use manager::add_task;
+pub use manager::{add_task, pid2process, process_list};
+pub use process::{ProcessControlBlock, FD_LIMIT};
 pub use processor::{
     current_hartid, current_process, current_processor, current_task, current_trap_cx,
-    current_trap_cx_user_va, current_user_token, init_hart, run_tasks, schedule, take_current_task,
+    current_trap_cx_user_va, current_user_token, init_hart, kernel_preemptible, run_tasks,
+    schedule, take_current_task, take_need_resched, tick_current,
+};
+pub use scheduler::{SchedEntity, NICE_MAX, NICE_MIN};
+pub use signal::{
+    current_add_signal, current_force_signal, current_signal_pending, handle_signals,
+    restore_signal_frame, send_signal, set_wait_signal_mask, SignalAction, SignalFlags, MAX_SIG,
+    SIG_BLOCK, SIG_DFL, SIG_IGN, SIG_SETMASK, SIG_UNBLOCK,
 };
 pub use task::{TaskControlBlock, TaskStatus};
 
@@ -42,31 +53,81 @@ pub fn suspend_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
+/// Mark the current task blocked. Call it before the task is handed to a
+/// waker, then give up the CPU with `block_current_and_run_next`. A wakeup
+/// coming in between makes the task ready again, so it is not lost.
+pub fn mark_current_blocked() {
+    current_task().unwrap().inner_lock_access().task_status = TaskStatus::Blocking;
+}
+
+/// Undo `mark_current_blocked` when the task turns out not to need to
+/// sleep. If a waker made it ready in the meantime it is queued already,
+/// so it has to give up the CPU once.
+pub fn cancel_current_blocked() {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_lock_access();
+    if task_inner.task_status == TaskStatus::Blocking {
+        task_inner.task_status = TaskStatus::Running;
+        return;
+    }
+    drop(task_inner);
+    drop(task);
+    block_current_and_run_next();
+}
+
 pub fn block_current_and_run_next() {
     let task = take_current_task().unwrap();
     let mut task_inner = task.inner_lock_access();
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
-    task_inner.task_status = TaskStatus::Blocking;
     drop(task_inner);
     schedule(task_cx_ptr);
 }
 
+/// Like `block_current_and_run_next`, but the task does not go to sleep
+/// if the current process has a signal to act on; `send_signal` wakes it
+/// up for one arriving later. Return EINTR in that case, the caller then
+/// takes the task off whatever it has queued it on.
+pub fn block_current_interruptible() -> SysResult<()> {
+    if current_signal_pending() {
+        cancel_current_blocked();
+        return Err(SysError::EINTR);
+    }
+    block_current_and_run_next();
+    Ok(())
+}
+
+/// Make a blocked task ready again. Return false if it is not blocked,
+/// e.g. because another waker was first.
+pub fn wakeup_task(task: Arc<TaskControlBlock>) -> bool {
+    let mut task_inner = task.inner_lock_access();
+    if task_inner.task_status != TaskStatus::Blocking {
+        return false;
+    }
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    add_task(task);
+    true
+}
+
 pub fn exit_current_and_run_next(exit_code: i32) {
     let task = take_current_task().unwrap();
     let mut task_inner = task.inner_lock_access();
     let process = task.process.upgrade().unwrap();
 
-    let tid = task_inner.res.as_ref().unwrap().tid;
+    let res = task_inner.res.take().unwrap();
+    let tid = res.tid;
+    drop(task_inner);
+    // deallocating user res takes the process, which must never nest in a task
+    drop(res);
     // record exit code
-    task_inner.exit_code = Some(exit_code);
-    task_inner.res = None;
+    task.inner_lock_access().exit_code = Some(exit_code);
     // here we do not remove the thread since we are still using the kstack
     // it will be deallocated when sys_waittid is called
-    drop(task_inner);
     drop(task);
     // however, if this is the main thread of current process
     // the process should terminate at once
     if tid == 0 {
+        remove_from_pid2process(process.getpid());
         let mut process_inner = process.inner_exclusive_access();
         // mark this process as a zombie process
         process_inner.is_zombie = true;
@@ -85,15 +146,24 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         // deallocate user res (including tid/trap_cx/ustack) of all threads
         // it has to be done before we dealloc the whole memory_set
         // otherwise they will be deallocated twice
-        for task in process_inner.tasks.iter().filter(|t| t.is_some()) {
-            let task = task.as_ref().unwrap();
-            let mut task_inner = task.inner_lock_access();
-            task_inner.res = None;
-        }
+        let res: Vec<_> = process_inner
+            .tasks
+            .iter()
+            .flatten()
+            .filter_map(|task| task.inner_lock_access().res.take())
+            .collect();
 
         process_inner.children.clear();
-        // deallocate other data in user space i.e. program code/data section
-        process_inner.memory_set.recycle_data_pages();
+        drop(process_inner);
+        // dropping them takes the process again
+        drop(res);
+        // deallocate other data in user space i.e. program code/data section,
+        // with the process released since files may be written back
+        let areas = process
+            .inner_exclusive_access()
+            .memory_set
+            .recycle_data_pages();
+        drop(areas);
     }
     drop(process);
     // we do not have to save task context
@@ -103,9 +173,8 @@ pub fn exit_current_and_run_next(exit_code: i32) {
 
 lazy_static! {
     pub static ref INITPROC: Arc<ProcessControlBlock> = {
-        let inode = open_file("initproc", OpenFlags::RDONLY).unwrap();
-        let v = inode.read_all();
-        ProcessControlBlock::new(v.as_slice())
+        let dentry = lookup(&root_dentry(), "initproc").unwrap();
+        ProcessControlBlock::new(Arc::new(OSInode::new(true, false, dentry)))
     };
 }
 
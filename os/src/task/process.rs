@@ -1,23 +1,29 @@
-use core::cell::{BorrowMutError, RefMut};
-
 use super::add_task;
 use super::id::RecycleAllocator;
+use super::manager::insert_into_pid2process;
+use super::signal::{current_force_signal, SignalActions, SignalFlags};
 use super::TaskControlBlock;
 use super::{pid_alloc, PidHandle};
-use crate::fs::{root, File, Stdin, Stdout};
-use crate::mm::{translated_refmut, MemorySet, KERNEL_SPACE};
-use crate::sync::{Mutex, Semaphore, UPSafeCell};
+use crate::error::{SysError, SysResult};
+use crate::fs::{open_file, root_dentry, Dentry, File, OSInode, OpenFlags};
+use crate::mm::{copy_bytes_to_user, copy_to_user, kernel_token, MemorySet};
+use crate::sync::{Condvar, Mutex, Semaphore, SpinMutex, SpinMutexGuard};
 use crate::trap::{trap_handler, TrapContext};
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::string::String;
 use alloc::sync::{Arc, Weak};
+use alloc::vec;
 use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+/// Fds of a process are below this.
+pub const FD_LIMIT: usize = 1000;
 
 pub struct ProcessControlBlock {
     // immutable
     pub pid: PidHandle,
     // mutable
-    pub inner: UPSafeCell<ProcessControlBlockInner>,
+    pub inner: SpinMutex<ProcessControlBlockInner>,
 }
 
 pub struct ProcessControlBlockInner {
@@ -27,11 +33,30 @@ pub struct ProcessControlBlockInner {
     pub children: Vec<Arc<ProcessControlBlock>>,
     pub exit_code: i32,
     pub fd_table: BTreeMap<usize, Option<Arc<dyn File + Send + Sync>>>,
+    /// The fds closed by exec, i.e. those with FD_CLOEXEC.
+    pub cloexec_fds: BTreeSet<usize>,
     pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
     pub task_res_allocator: RecycleAllocator,
     pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
     pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
-    pub dir_entry: Option<Arc<dyn File + Send + Sync>>,
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    /// The working directory.
+    pub cwd: Arc<Dentry>,
+    /// File name of the program.
+    pub name: String,
+    /// Arguments the program was started with.
+    pub cmdline: Vec<String>,
+    pub signals: SignalFlags,
+    pub signal_mask: SignalFlags,
+    /// The mask to put back on the way to user mode, once a ppoll and the
+    /// like which waited with a mask of its own have had their signals
+    /// delivered.
+    pub saved_signal_mask: Option<SignalFlags>,
+    pub signal_actions: SignalActions,
+    /// stopped by SIGSTOP and friends until SIGCONT arrives
+    pub frozen: bool,
+    pub heap_bottom: usize,
+    pub program_brk: usize,
 }
 
 impl ProcessControlBlockInner {
@@ -40,14 +65,27 @@ impl ProcessControlBlockInner {
         self.memory_set.token()
     }
 
-    pub fn alloc_fd(&mut self) -> usize {
-        for i in 0..1000usize {
+    pub fn alloc_fd(&mut self) -> SysResult<usize> {
+        self.alloc_fd_from(0)
+    }
+
+    /// Take the lowest free fd not below `min`.
+    pub fn alloc_fd_from(&mut self, min: usize) -> SysResult<usize> {
+        for i in min..FD_LIMIT {
             if !self.fd_table.contains_key(&i) {
                 self.fd_table.insert(i, None);
-                return i;
+                return Ok(i);
             }
         }
-        panic!("alloc fd error")
+        Err(SysError::EMFILE)
+    }
+
+    pub fn set_cloexec(&mut self, fd: usize, cloexec: bool) {
+        if cloexec {
+            self.cloexec_fds.insert(fd);
+        } else {
+            self.cloexec_fds.remove(&fd);
+        }
     }
 
     pub fn alloc_tid(&mut self) -> usize {
@@ -65,45 +103,62 @@ impl ProcessControlBlockInner {
     pub fn get_task(&self, tid: usize) -> Arc<TaskControlBlock> {
         self.tasks[tid].as_ref().unwrap().clone()
     }
+
+    /// No hart is still switching away from one of the threads.
+    pub fn off_cpu(&self) -> bool {
+        self.tasks
+            .iter()
+            .flatten()
+            .all(|task| !task.on_cpu.load(Ordering::Acquire))
+    }
 }
 
 impl ProcessControlBlock {
-    pub fn try_inner_exclusive_access(
-        &self,
-    ) -> Result<RefMut<ProcessControlBlockInner>, BorrowMutError> {
-        self.inner.try_exclusive_access()
+    pub fn try_inner_exclusive_access(&self) -> Option<SpinMutexGuard<ProcessControlBlockInner>> {
+        self.inner.try_lock()
     }
 
-    pub fn inner_exclusive_access(&self) -> RefMut<ProcessControlBlockInner> {
-        self.inner.exclusive_access()
+    pub fn inner_exclusive_access(&self) -> SpinMutexGuard<ProcessControlBlockInner> {
+        self.inner.lock()
     }
 
-    pub fn new(elf_data: &[u8]) -> Arc<Self> {
+    pub fn new(elf_file: Arc<OSInode>) -> Arc<Self> {
+        let name = elf_file.name();
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, heap_bottom, ustack_base, entry_point) = MemorySet::from_elf(elf_file);
         // allocate a pid
         let pid_handle = pid_alloc();
         let mut btree: BTreeMap<usize, Option<Arc<dyn File + Send + Sync>>> = BTreeMap::new();
-        btree.insert(0, Some(Arc::new(Stdin)));
-        btree.insert(1, Some(Arc::new(Stdout)));
-        btree.insert(2, Some(Arc::new(Stdout)));
+        let tty = |flags| open_file(&root_dentry(), "/dev/tty", flags).expect("no /dev/tty");
+        btree.insert(0, Some(tty(OpenFlags::RDONLY)));
+        btree.insert(1, Some(tty(OpenFlags::WRONLY)));
+        btree.insert(2, Some(tty(OpenFlags::WRONLY)));
         let process = Arc::new(Self {
             pid: pid_handle,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: btree,
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    dir_entry: Some(root()),
-                })
-            },
+            inner: SpinMutex::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                parent: None,
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: btree,
+                cloexec_fds: BTreeSet::new(),
+                tasks: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                cwd: root_dentry(),
+                cmdline: vec![String::clone(&name)],
+                name,
+                signals: SignalFlags::empty(),
+                signal_mask: SignalFlags::empty(),
+                saved_signal_mask: None,
+                signal_actions: SignalActions::default(),
+                frozen: false,
+                heap_bottom,
+                program_brk: heap_bottom,
+            }),
         });
         // create a main thread, we should allocate ustack and trap_cx here
         let task = Arc::new(TaskControlBlock::new(
@@ -120,7 +175,7 @@ impl ProcessControlBlock {
         *trap_cx = TrapContext::app_init_context(
             entry_point,
             ustack_top,
-            KERNEL_SPACE.inner.borrow_mut().token(),
+            kernel_token(),
             kstack_top,
             trap_handler as usize,
         );
@@ -128,49 +183,68 @@ impl ProcessControlBlock {
         let mut process_inner = process.inner_exclusive_access();
         process_inner.tasks.push(Some(Arc::clone(&task)));
         drop(process_inner);
+        insert_into_pid2process(process.getpid(), Arc::clone(&process));
         // add main thread to scheduler
         add_task(task);
         process
     }
 
     /// Only support processes with a single thread.
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) {
+    pub fn exec(self: &Arc<Self>, elf_file: Arc<OSInode>, args: Vec<String>) {
         assert_eq!(self.inner_exclusive_access().thread_count(), 1);
+        let name = elf_file.name();
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, heap_bottom, ustack_base, entry_point) = MemorySet::from_elf(elf_file);
         let new_token = memory_set.token();
         // substitute memory_set
-        self.inner_exclusive_access().memory_set = memory_set;
+        let mut inner = self.inner_exclusive_access();
+        let old_memory_set = core::mem::replace(&mut inner.memory_set, memory_set);
+        inner.heap_bottom = heap_bottom;
+        inner.program_brk = heap_bottom;
+        inner.signal_actions.reset_on_exec();
+        inner.name = name;
+        inner.cmdline = args.clone();
+        let cloexec_fds = core::mem::take(&mut inner.cloexec_fds);
+        let closed: Vec<_> = cloexec_fds
+            .iter()
+            .filter_map(|fd| inner.fd_table.remove(fd))
+            .collect();
+        drop(inner);
+        // the files may wake up others as they go, and shared file
+        // mappings are written back
+        drop(closed);
+        drop(old_memory_set);
         // then we alloc user resource for main thread again
         // since memory_set has been changed
         let task = self.inner_exclusive_access().get_task(0);
         let mut task_inner = task.inner_lock_access();
         task_inner.res.as_mut().unwrap().ustack_base = ustack_base;
-        task_inner.res.as_mut().unwrap().brk_addr = ustack_base - 4096;
         task_inner.res.as_mut().unwrap().alloc_user_res();
         task_inner.trap_cx_ppn = task_inner.res.as_mut().unwrap().trap_cx_ppn();
         // push arguments on user stack
         let mut user_sp = task_inner.res.as_mut().unwrap().ustack_top();
+        // user memory is written through the process, which must never
+        // nest in a task
+        drop(task_inner);
         user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
         let argv_base = user_sp;
-        let mut argv: Vec<_> = (0..=args.len())
-            .map(|arg| {
-                translated_refmut(
-                    new_token,
-                    (argv_base + arg * core::mem::size_of::<usize>()) as *mut usize,
-                )
-            })
-            .collect();
-        *argv[args.len()] = 0;
-        for i in 0..args.len() {
-            user_sp -= args[i].len() + 1;
-            *argv[i] = user_sp;
-            let mut p = user_sp;
-            for c in args[i].as_bytes() {
-                *translated_refmut(new_token, p as *mut u8) = *c;
-                p += 1;
-            }
-            *translated_refmut(new_token, p as *mut u8) = 0;
+        let mut argv = Vec::new();
+        let mut pushed = Ok(());
+        for arg in args.iter() {
+            user_sp -= arg.len() + 1;
+            argv.push(user_sp);
+            let mut bytes = Vec::from(arg.as_bytes());
+            bytes.push(0);
+            pushed = pushed.and(copy_bytes_to_user(new_token, user_sp as *mut u8, &bytes));
+        }
+        argv.push(0);
+        for (i, arg_ptr) in argv.iter().enumerate() {
+            let dst = (argv_base as *mut usize).wrapping_add(i);
+            pushed = pushed.and(copy_to_user(new_token, dst, arg_ptr));
+        }
+        if pushed.is_err() {
+            // no memory for the stack, the new image cannot start
+            current_force_signal(SignalFlags::SIGSEGV);
         }
         // make the user_sp aligned to 8B for k210 platform
         user_sp -= user_sp % core::mem::size_of::<usize>();
@@ -178,21 +252,21 @@ impl ProcessControlBlock {
         let mut trap_cx = TrapContext::app_init_context(
             entry_point,
             user_sp,
-            KERNEL_SPACE.inner.borrow_mut().token(),
+            kernel_token(),
             task.kstack.get_top(),
             trap_handler as usize,
         );
         trap_cx.x[10] = args.len();
         trap_cx.x[11] = argv_base;
-        *task_inner.get_trap_cx() = trap_cx;
+        *task.inner_lock_access().get_trap_cx() = trap_cx;
     }
 
     /// Only support processes with a single thread.
     pub fn fork(self: &Arc<Self>) -> Arc<Self> {
         let mut parent = self.inner_exclusive_access();
         assert_eq!(parent.thread_count(), 1);
-        // clone parent's memory_set completely including trampoline/ustacks/trap_cxs
-        let memory_set = MemorySet::from_existed_user(&parent.memory_set);
+        // share parent's user pages copy-on-write, copy trap_cxs
+        let memory_set = MemorySet::from_existed_user(&mut parent.memory_set);
         // alloc a pid
         let pid = pid_alloc();
         // copy fd table
@@ -207,21 +281,31 @@ impl ProcessControlBlock {
         // create child process pcb
         let child = Arc::new(Self {
             pid,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: btree,
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    dir_entry: parent.dir_entry.clone(),
-                })
-            },
+            inner: SpinMutex::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: btree,
+                cloexec_fds: parent.cloexec_fds.clone(),
+                tasks: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                cwd: Arc::clone(&parent.cwd),
+                name: String::clone(&parent.name),
+                cmdline: parent.cmdline.clone(),
+                // pending signals are not inherited
+                signals: SignalFlags::empty(),
+                signal_mask: parent.signal_mask,
+                saved_signal_mask: None,
+                signal_actions: parent.signal_actions.clone(),
+                frozen: false,
+                heap_bottom: parent.heap_bottom,
+                program_brk: parent.program_brk,
+            }),
         });
         // add child
         parent.children.push(Arc::clone(&child));
@@ -239,6 +323,8 @@ impl ProcessControlBlock {
             // but mention that we allocate a new kstack here
             false,
         ));
+        // the nice value is inherited
+        task.sched.lock().nice = parent.get_task(0).sched.lock().nice;
         // attach task to child process
         let mut child_inner = child.inner_exclusive_access();
         child_inner.tasks.push(Some(Arc::clone(&task)));
@@ -248,6 +334,7 @@ impl ProcessControlBlock {
         let trap_cx = task_inner.get_trap_cx();
         trap_cx.kernel_sp = task.kstack.get_top();
         drop(task_inner);
+        insert_into_pid2process(child.getpid(), Arc::clone(&child));
         // add this thread to scheduler
         add_task(task);
         child
@@ -1,17 +1,21 @@
 use super::__switch;
+use super::manager::tick_task;
 use super::{fetch_task, TaskStatus};
 use super::{ProcessControlBlock, TaskContext, TaskControlBlock};
-use crate::config::MAX_HARTID;
-use crate::sync::intr_on;
+use crate::config::{KERNEL_STACK_SIZE, MAX_HARTID};
+use crate::sync::{intr_on, pop_off, push_off};
 use crate::trap::TrapContext;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use core::sync::atomic::Ordering;
 
 pub static mut PROCESSORS: BTreeMap<usize, Processor> = BTreeMap::new();
 
 pub struct Processor {
     pub noff: usize,
     pub intena: bool,
+    /// The time slice ran out while the current task was in the kernel.
+    need_resched: bool,
     current: Option<Arc<TaskControlBlock>>,
     idle_task_cx: TaskContext,
 }
@@ -20,6 +24,7 @@ pub fn new() -> Processor {
     Processor {
         noff: 0,
         intena: false,
+        need_resched: false,
         current: None,
         idle_task_cx: TaskContext::zero_init(),
     }
@@ -30,6 +35,7 @@ impl Processor {
         Self {
             noff: 0,
             intena: false,
+            need_resched: false,
             current: None,
             idle_task_cx: TaskContext::zero_init(),
         }
@@ -64,6 +70,12 @@ pub fn run_tasks(hartid: usize) {
         let mut processor = current_processor().unwrap();
         intr_on();
         if let Some(task) = fetch_task() {
+            // a task woken up right after it blocked on another hart may
+            // not have saved its context yet
+            while task.on_cpu.load(Ordering::Acquire) {
+                core::hint::spin_loop();
+            }
+            task.on_cpu.store(true, Ordering::Relaxed);
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // access coming task TCB exclusively
             let mut task_inner = task.inner_lock_access();
@@ -71,14 +83,18 @@ pub fn run_tasks(hartid: usize) {
             task_inner.task_status = TaskStatus::Running;
             drop(task_inner);
             // release coming task TCB manually
-            processor.current = Some(task);
+            processor.current = Some(Arc::clone(&task));
+            processor.need_resched = false;
             // release processor manually
             drop(processor);
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            // back on the idle stack, the task context has been saved. Holding
+            // `task` until here also keeps an exited task's kernel stack alive.
+            task.on_cpu.store(false, Ordering::Release);
         } else {
-            println!("no tasks available in run_tasks hartid {}", hartid);
+            core::hint::spin_loop();
         }
     }
 }
@@ -87,14 +103,54 @@ pub fn current_processor() -> Option<&'static mut Processor> {
     unsafe { PROCESSORS.get_mut(&current_hartid()) }
 }
 
+/// Look at the processor of this hart with interrupts off, a task preempted
+/// in the kernel may go on on another hart.
+fn with_processor<T>(f: impl FnOnce(&mut Processor) -> T) -> T {
+    push_off();
+    let ret = f(current_processor().unwrap());
+    pop_off();
+    ret
+}
+
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    let processor = current_processor().unwrap();
-    processor.take_current()
+    with_processor(|processor| processor.take_current())
 }
 
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
+    with_processor(|processor| processor.current())
+}
+
+/// Account a timer tick to the current task. If the scheduler wants it
+/// preempted, it gives up the CPU on its way back to user mode.
+pub fn tick_current() {
+    let processor = current_processor().unwrap();
+    if let Some(task) = processor.current() {
+        if tick_task(&task) {
+            processor.need_resched = true;
+        }
+    }
+}
+
+pub fn take_need_resched() -> bool {
+    with_processor(|processor| core::mem::replace(&mut processor.need_resched, false))
+}
+
+/// Whether a timer interrupt taken in the kernel with `sp` may switch the
+/// current task away. The interrupted code must hold no SpinMutex, run on
+/// the task's own kernel stack rather than in `run_tasks`, and not be on
+/// its way to block, where a wakeup would be lost.
+pub fn kernel_preemptible(sp: usize) -> bool {
     let processor = current_processor().unwrap();
-    processor.current()
+    if processor.noff != 0 {
+        return false;
+    }
+    let task = match processor.current() {
+        Some(task) => task,
+        None => return false,
+    };
+    let top = task.kstack.get_top();
+    let on_kstack = (top - KERNEL_STACK_SIZE..top).contains(&sp);
+    on_kstack && task.inner_lock_access().task_status == TaskStatus::Running
 }
 
 pub fn current_process() -> Arc<ProcessControlBlock> {
@@ -0,0 +1,184 @@
+use super::TaskControlBlock;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// Nice values as in Linux, lower means more CPU time.
+pub const NICE_MIN: isize = -20;
+pub const NICE_MAX: isize = 19;
+
+/// Scheduling state of a task, interpreted by the active `Scheduler`.
+#[derive(Copy, Clone)]
+pub struct SchedEntity {
+    pub nice: isize,
+    /// Virtual time of the stride scheduler.
+    pub pass: usize,
+    /// Ticks spent waiting in the ready queue, for priority aging.
+    pub age: usize,
+}
+
+impl SchedEntity {
+    pub fn new(nice: isize) -> Self {
+        Self {
+            nice,
+            pass: 0,
+            age: 0,
+        }
+    }
+    /// From 1 for nice 19 up to 40 for nice -20.
+    pub fn weight(&self) -> usize {
+        (NICE_MAX + 1 - self.nice) as usize
+    }
+}
+
+pub trait Scheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>>;
+    /// Called on every timer tick with the running task,
+    /// return true if it should give up the CPU.
+    fn tick(&mut self, task: &Arc<TaskControlBlock>) -> bool;
+    /// Number of tasks waiting to run.
+    fn ready_count(&self) -> usize;
+}
+
+/// A simple FIFO scheduler, every tick ends the time slice.
+pub struct RoundRobin {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl RoundRobin {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobin {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+    fn tick(&mut self, _task: &Arc<TaskControlBlock>) -> bool {
+        true
+    }
+    fn ready_count(&self) -> usize {
+        self.ready_queue.len()
+    }
+}
+
+const BIG_STRIDE: usize = 1 << 20;
+
+/// Stride scheduling, the task with the smallest pass runs next and each
+/// tick advances its pass in inverse proportion to its weight.
+pub struct Stride {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    /// Pass of the task fetched last, newcomers start from here so that
+    /// they cannot monopolize the CPU with a small pass.
+    min_pass: usize,
+}
+
+impl Stride {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+            min_pass: 0,
+        }
+    }
+}
+
+impl Scheduler for Stride {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        {
+            let mut sched = task.sched.lock();
+            sched.pass = sched.pass.max(self.min_pass);
+        }
+        self.ready_queue.push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let (idx, _) = self
+            .ready_queue
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, task)| task.sched.lock().pass)?;
+        let task = self.ready_queue.remove(idx).unwrap();
+        self.min_pass = task.sched.lock().pass;
+        Some(task)
+    }
+    fn tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        let pass = {
+            let mut sched = task.sched.lock();
+            sched.pass += BIG_STRIDE / sched.weight();
+            sched.pass
+        };
+        self.ready_queue
+            .iter()
+            .any(|waiting| waiting.sched.lock().pass < pass)
+    }
+    fn ready_count(&self) -> usize {
+        self.ready_queue.len()
+    }
+}
+
+/// Highest weight first. Waiting tasks gain one point per tick so that
+/// low priority tasks cannot starve.
+pub struct PriorityAging {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl PriorityAging {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+fn effective_priority(task: &Arc<TaskControlBlock>) -> usize {
+    let sched = task.sched.lock();
+    sched.weight() + sched.age
+}
+
+impl Scheduler for PriorityAging {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        // the first of the best ones, so equal priorities take turns
+        let mut best: Option<(usize, usize)> = None;
+        for (idx, task) in self.ready_queue.iter().enumerate() {
+            let priority = effective_priority(task);
+            if best.map_or(true, |(_, best_priority)| priority > best_priority) {
+                best = Some((idx, priority));
+            }
+        }
+        let task = self.ready_queue.remove(best?.0).unwrap();
+        task.sched.lock().age = 0;
+        Some(task)
+    }
+    fn tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        let priority = task.sched.lock().weight();
+        let mut preempt = false;
+        for waiting in self.ready_queue.iter() {
+            let mut sched = waiting.sched.lock();
+            sched.age += 1;
+            preempt |= sched.weight() + sched.age >= priority;
+        }
+        preempt
+    }
+    fn ready_count(&self) -> usize {
+        self.ready_queue.len()
+    }
+}
+
+/// Pick the scheduler by name, see `SCHED` in the Makefile.
+pub fn scheduler_by_name(name: &str) -> Option<Box<dyn Scheduler + Send>> {
+    match name {
+        "rr" => Some(Box::new(RoundRobin::new())),
+        "stride" => Some(Box::new(Stride::new())),
+        "priority" => Some(Box::new(PriorityAging::new())),
+        _ => None,
+    }
+}
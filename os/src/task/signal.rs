@@ -0,0 +1,392 @@
+use super::manager::{pid2process, process_list};
+use super::{
+    current_process, current_task, current_trap_cx, exit_current_and_run_next,
+    suspend_current_and_run_next, wakeup_task, ProcessControlBlock,
+};
+use crate::drivers::UART_DEVICE;
+use crate::error::SysResult;
+use crate::mm::{copy_from_user, copy_to_user};
+use crate::trap::TrapContext;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+pub const MAX_SIG: usize = 31;
+/// `handler` value of the default action
+pub const SIG_DFL: usize = 0;
+/// `handler` value of an ignored signal
+pub const SIG_IGN: usize = 1;
+
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+bitflags! {
+    /// Bit `n` stands for signal number `n`, as in Linux.
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD = 1 << 17;
+        const SIGCONT = 1 << 18;
+        const SIGSTOP = 1 << 19;
+        const SIGTSTP = 1 << 20;
+        const SIGTTIN = 1 << 21;
+        const SIGTTOU = 1 << 22;
+        const SIGURG = 1 << 23;
+        const SIGXCPU = 1 << 24;
+        const SIGXFSZ = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF = 1 << 27;
+        const SIGWINCH = 1 << 28;
+        const SIGIO = 1 << 29;
+        const SIGPWR = 1 << 30;
+        const SIGSYS = 1 << 31;
+    }
+}
+
+impl SignalFlags {
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if signum == 0 || signum > MAX_SIG {
+            None
+        } else {
+            Self::from_bits(1 << signum)
+        }
+    }
+    /// Signals which can neither be caught, ignored nor blocked.
+    pub fn unmaskable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+    /// Return the lowest signal number in the set.
+    pub fn lowest_signum(&self) -> Option<usize> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.bits.trailing_zeros() as usize)
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+fn default_action(signal: SignalFlags) -> DefaultAction {
+    if signal.intersects(SignalFlags::SIGCHLD | SignalFlags::SIGURG | SignalFlags::SIGWINCH) {
+        DefaultAction::Ignore
+    } else if signal.intersects(
+        SignalFlags::SIGSTOP | SignalFlags::SIGTSTP | SignalFlags::SIGTTIN | SignalFlags::SIGTTOU,
+    ) {
+        DefaultAction::Stop
+    } else if signal.contains(SignalFlags::SIGCONT) {
+        DefaultAction::Continue
+    } else {
+        DefaultAction::Terminate
+    }
+}
+
+/// Layout shared with `user_lib::SignalAction`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalAction {
+    pub handler: usize,
+    /// Where the handler returns to, it must issue `sigreturn`.
+    pub restorer: usize,
+    /// Signals blocked while the handler runs.
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            restorer: 0,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SignalActions {
+    pub table: [SignalAction; MAX_SIG + 1],
+}
+
+impl Default for SignalActions {
+    fn default() -> Self {
+        Self {
+            table: [SignalAction::default(); MAX_SIG + 1],
+        }
+    }
+}
+
+impl SignalActions {
+    /// Caught signals fall back to the default action across exec,
+    /// ignored ones stay ignored.
+    pub fn reset_on_exec(&mut self) {
+        for action in self.table.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+    }
+}
+
+/// Saved on the user stack before entering a handler and restored by
+/// `sys_sigreturn`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalFrame {
+    pub x: [usize; 32],
+    pub sepc: usize,
+    pub mask: SignalFlags,
+}
+
+pub fn current_add_signal(signal: SignalFlags) {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    process_inner.signals |= signal;
+}
+
+/// Raise a fault signal for the current process. It can be neither blocked
+/// nor ignored, otherwise the faulting instruction would be retried forever.
+pub fn current_force_signal(signal: SignalFlags) {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    process_inner.signals |= signal;
+    process_inner.signal_mask.remove(signal);
+    let action = &mut process_inner.signal_actions.table[signal.lowest_signum().unwrap()];
+    if action.handler == SIG_IGN {
+        *action = SignalAction::default();
+    }
+}
+
+/// Post `signal` to `process` and wake up its blocked threads, which
+/// check for it before they go back to sleep.
+fn post_signal(process: &ProcessControlBlock, signal: SignalFlags) {
+    let mut inner = process.inner_exclusive_access();
+    if inner.is_zombie || signal.is_empty() {
+        return;
+    }
+    inner.signals |= signal;
+    let tasks: Vec<_> = inner.tasks.iter().flatten().cloned().collect();
+    drop(inner);
+    for task in tasks {
+        wakeup_task(task);
+    }
+}
+
+/// Post `signal` to the process with the given pid, return false if it does not exist.
+pub fn send_signal(pid: usize, signal: SignalFlags) -> bool {
+    if let Some(process) = pid2process(pid) {
+        post_signal(&process, signal);
+        true
+    } else {
+        false
+    }
+}
+
+/// Ctrl-C on the console interrupts every user process but initproc.
+/// Shells are expected to ignore SIGINT themselves.
+fn check_console_interrupt() {
+    if !UART_DEVICE.take_interrupt() {
+        return;
+    }
+    for process in process_list() {
+        if process.getpid() != 0 {
+            post_signal(&process, SignalFlags::SIGINT);
+        }
+    }
+}
+
+/// Whether delivering `signal` by `action` does nothing at all.
+fn is_ignored(signal: SignalFlags, action: &SignalAction) -> bool {
+    if signal.intersects(SignalFlags::unmaskable()) {
+        return false;
+    }
+    match action.handler {
+        SIG_IGN => true,
+        SIG_DFL => matches!(
+            default_action(signal),
+            DefaultAction::Ignore | DefaultAction::Continue
+        ),
+        _ => false,
+    }
+}
+
+/// Whether the current process has a signal to act on, one which is
+/// neither blocked nor ignored. Blocking syscalls check it before they
+/// sleep and give up with EINTR if there is one.
+pub fn current_signal_pending() -> bool {
+    check_console_interrupt();
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let pending = inner.signals & !inner.signal_mask;
+    (1..=MAX_SIG).any(|signum| {
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        pending.contains(signal) && !is_ignored(signal, &inner.signal_actions.table[signum])
+    })
+}
+
+/// Push a signal frame on the user stack of the current thread and redirect it
+/// to `action.handler`. Return false if the user stack cannot hold the frame.
+fn setup_signal_frame(signum: usize, action: &SignalAction, old_mask: SignalFlags) -> bool {
+    let token = current_process()
+        .inner_exclusive_access()
+        .memory_set
+        .token();
+    let cx = current_trap_cx();
+    let sp = (cx.x[2].wrapping_sub(size_of::<SignalFrame>())) & !0xf;
+    let frame = SignalFrame {
+        x: cx.x,
+        sepc: cx.sepc,
+        mask: old_mask,
+    };
+    if copy_to_user(token, sp as *mut SignalFrame, &frame).is_err() {
+        return false;
+    }
+    cx.set_sp(sp);
+    cx.sepc = action.handler;
+    cx.x[1] = action.restorer;
+    cx.x[10] = signum;
+    true
+}
+
+/// Block `mask` instead of the current signal mask until the current task
+/// returns to user mode, for ppoll and the like. A signal it lets through
+/// is delivered before the old mask is back.
+pub fn set_wait_signal_mask(mask: SignalFlags) {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.saved_signal_mask.is_none() {
+        inner.saved_signal_mask = Some(inner.signal_mask);
+    }
+    inner.signal_mask = mask - SignalFlags::unmaskable();
+}
+
+/// Deliver pending and unblocked signals of the current process.
+/// Called on every return to user mode.
+pub fn handle_signals() {
+    check_console_interrupt();
+    loop {
+        let process = current_process();
+        let mut inner = process.inner_exclusive_access();
+        if inner.frozen {
+            if inner.signals.contains(SignalFlags::SIGCONT) {
+                inner.signals.remove(SignalFlags::SIGCONT);
+                inner.frozen = false;
+            } else if !inner.signals.contains(SignalFlags::SIGKILL) {
+                drop(inner);
+                drop(process);
+                suspend_current_and_run_next();
+                continue;
+            }
+        }
+        let pending = inner.signals & !inner.signal_mask;
+        let signum = match pending.lowest_signum() {
+            Some(signum) => signum,
+            // what the old mask lets through goes out next
+            None => match inner.saved_signal_mask.take() {
+                Some(mask) => {
+                    inner.signal_mask = mask;
+                    continue;
+                }
+                None => return,
+            },
+        };
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        let action = inner.signal_actions.table[signum];
+        let is_main_thread = current_task()
+            .unwrap()
+            .inner_lock_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .tid
+            == 0;
+        let handler = if signal.intersects(SignalFlags::unmaskable()) {
+            SIG_DFL
+        } else {
+            action.handler
+        };
+        match handler {
+            SIG_IGN => {
+                inner.signals.remove(signal);
+            }
+            SIG_DFL => match default_action(signal) {
+                DefaultAction::Ignore | DefaultAction::Continue => {
+                    inner.signals.remove(signal);
+                }
+                DefaultAction::Stop => {
+                    inner.signals.remove(signal);
+                    inner.frozen = true;
+                }
+                DefaultAction::Terminate => {
+                    // other threads exit on their own and leave the signal
+                    // to the main thread, which tears the process down
+                    if is_main_thread {
+                        inner.signals.remove(signal);
+                    }
+                    drop(inner);
+                    drop(process);
+                    exit_current_and_run_next(-(signum as i32));
+                }
+            },
+            _ => {
+                inner.signals.remove(signal);
+                // sigreturn puts back the mask from before a ppoll
+                let old_mask = inner.saved_signal_mask.take().unwrap_or(inner.signal_mask);
+                inner.signal_mask |= (action.mask | signal) - SignalFlags::unmaskable();
+                drop(inner);
+                drop(process);
+                if !setup_signal_frame(signum, &action, old_mask) {
+                    // no room for the frame, fall back to the default action
+                    println!("[kernel] cannot deliver signal {}, core dumped.", signum);
+                    let process = current_process();
+                    let mut inner = process.inner_exclusive_access();
+                    inner.signal_mask = old_mask;
+                    drop(inner);
+                    drop(process);
+                    let signum = SignalFlags::SIGSEGV.lowest_signum().unwrap();
+                    exit_current_and_run_next(-(signum as i32));
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Restore the context saved by `setup_signal_frame`, return the restored `a0`.
+/// A frame which cannot be read is EFAULT and earns the process a SIGSEGV.
+pub fn restore_signal_frame() -> SysResult {
+    let process = current_process();
+    let token = process.inner_exclusive_access().memory_set.token();
+    let cx: &mut TrapContext = current_trap_cx();
+    let frame: SignalFrame = match copy_from_user(token, cx.x[2] as *const SignalFrame) {
+        Ok(frame) => frame,
+        Err(err) => {
+            current_force_signal(SignalFlags::SIGSEGV);
+            return Err(err);
+        }
+    };
+    cx.x = frame.x;
+    cx.sepc = frame.sepc;
+    let mask = SignalFlags::from_bits_truncate(frame.mask.bits());
+    process.inner_exclusive_access().signal_mask = mask - SignalFlags::unmaskable();
+    Ok(cx.x[10] as isize)
+}
@@ -1,9 +1,11 @@
 use super::id::TaskUserRes;
+use super::scheduler::SchedEntity;
 use super::{kstack_alloc, KernelStack, ProcessControlBlock, TaskContext};
+use crate::mm::PhysPageNum;
 use crate::sync::{SpinMutex, SpinMutexGuard};
 use crate::trap::TrapContext;
-use crate::{mm::PhysPageNum, sync::UPSafeCell};
 use alloc::sync::{Arc, Weak};
+use core::sync::atomic::AtomicBool;
 
 pub struct TaskControlBlock {
     // immutable
@@ -11,6 +13,10 @@ pub struct TaskControlBlock {
     pub kstack: KernelStack,
     // mutable
     pub inner: SpinMutex<TaskControlBlockInner>,
+    /// kept apart from `inner` so that the scheduler never nests in it
+    pub sched: SpinMutex<SchedEntity>,
+    /// Set while a hart runs the task or has not finished switching away from it.
+    pub on_cpu: AtomicBool,
 }
 
 impl TaskControlBlock {
@@ -20,7 +26,7 @@ impl TaskControlBlock {
 
     pub fn get_user_token(&self) -> usize {
         let process = self.process.upgrade().unwrap();
-        let inner = process.inner.exclusive_access();
+        let inner = process.inner_exclusive_access();
         inner.memory_set.token()
     }
 }
@@ -64,6 +70,8 @@ impl TaskControlBlock {
                 task_status: TaskStatus::Ready,
                 exit_code: None,
             }),
+            sched: SpinMutex::new(SchedEntity::new(0)),
+            on_cpu: AtomicBool::new(false),
         }
     }
 }
@@ -1,15 +1,14 @@
 use core::cmp::Ordering;
 
-use crate::config::CLOCK_FREQ;
+use crate::config::{CLOCK_FREQ, TIME_SLICE_MS};
 use crate::sbi::set_timer;
-use crate::sync::UPSafeCell;
-use crate::task::{add_task, TaskControlBlock};
+use crate::sync::SpinMutex;
+use crate::task::{wakeup_task, TaskControlBlock};
 use alloc::collections::BinaryHeap;
 use alloc::sync::Arc;
 use lazy_static::*;
 use riscv::register::time;
 
-const TICKS_PER_SEC: usize = 100;
 const MSEC_PER_SEC: usize = 1000;
 const USEC_PER_SEC: usize = 1000000;
 pub fn get_time() -> usize {
@@ -28,8 +27,9 @@ pub fn get_time_usec() -> usize {
     (time::read() / (CLOCK_FREQ / USEC_PER_SEC)) % 1000000
 }
 
+/// Fire the timer interrupt once the current time slice is used up.
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    set_timer(get_time() + CLOCK_FREQ / MSEC_PER_SEC * TIME_SLICE_MS);
 }
 
 pub struct TimerCondVar {
@@ -58,21 +58,29 @@ impl Ord for TimerCondVar {
 }
 
 lazy_static! {
-    static ref TIMERS: UPSafeCell<BinaryHeap<TimerCondVar>> =
-        unsafe { UPSafeCell::new(BinaryHeap::<TimerCondVar>::new()) };
+    static ref TIMERS: SpinMutex<BinaryHeap<TimerCondVar>> =
+        SpinMutex::new(BinaryHeap::<TimerCondVar>::new());
 }
 
 pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
-    let mut timers = TIMERS.exclusive_access();
-    timers.push(TimerCondVar { expire_ms, task });
+    TIMERS.lock().push(TimerCondVar { expire_ms, task });
+}
+
+/// Cancel the timers of `task`. Once this returns, none of them can wake it up.
+pub fn remove_timer(task: &Arc<TaskControlBlock>) {
+    let mut timers = TIMERS.lock();
+    let mut remaining = core::mem::take(&mut *timers).into_vec();
+    remaining.retain(|timer| !Arc::ptr_eq(&timer.task, task));
+    *timers = BinaryHeap::from(remaining);
 }
 
 pub fn check_timer() {
     let current_ms = get_time_ms();
-    let mut timers = TIMERS.exclusive_access();
+    let mut timers = TIMERS.lock();
     while let Some(timer) = timers.peek() {
         if timer.expire_ms <= current_ms {
-            add_task(Arc::clone(&timer.task));
+            // the task may have been woken up by something else already
+            wakeup_task(Arc::clone(&timer.task));
             drop(timer);
             timers.pop();
         } else {
@@ -1,12 +1,14 @@
 mod context;
 use crate::config::TRAMPOLINE;
 use crate::irq::handler_ext;
+use crate::mm::{translate_user, MapPermission, VirtAddr};
 use crate::sbi::sbi_smext_stimer;
 use crate::sync::{intr_off, intr_on};
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_trap_cx_user_va, current_user_token, exit_current_and_run_next,
-    suspend_current_and_run_next,
+    current_force_signal, current_trap_cx, current_trap_cx_user_va, current_user_token,
+    handle_signals, kernel_preemptible, suspend_current_and_run_next, take_need_resched,
+    tick_current, SignalFlags,
 };
 use crate::timer::{check_timer, set_next_trigger};
 pub use context::TrapContext;
@@ -71,25 +73,36 @@ pub fn trap_handler() -> ! {
         | Trap::Exception(Exception::InstructionPageFault)
         | Trap::Exception(Exception::LoadFault)
         | Trap::Exception(Exception::LoadPageFault) => {
-            println!(
-                "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.",
-                scause.cause(),
-                stval,
-                current_trap_cx().sepc,
-            );
-            // page fault exit code
-            exit_current_and_run_next(-2);
+            let access = match scause.cause() {
+                Trap::Exception(Exception::StorePageFault) => Some(MapPermission::W),
+                Trap::Exception(Exception::InstructionPageFault) => Some(MapPermission::X),
+                Trap::Exception(Exception::LoadPageFault) => Some(MapPermission::R),
+                _ => None,
+            };
+            // file pages are read from the disk, like in a syscall
+            intr_on();
+            let resolved = access.map_or(false, |access| {
+                translate_user(current_user_token(), VirtAddr::from(stval).floor(), access)
+                    .is_some()
+            });
+            if !resolved {
+                println!(
+                    "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}.",
+                    scause.cause(),
+                    stval,
+                    current_trap_cx().sepc,
+                );
+                current_force_signal(SignalFlags::SIGSEGV);
+            }
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            println!("[kernel] IllegalInstruction in application, core dumped.");
-            // illegal instruction exit code
-            exit_current_and_run_next(-3);
+            println!("[kernel] IllegalInstruction in application.");
+            current_force_signal(SignalFlags::SIGILL);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            println!("From U SupervisorTimer");
             set_next_trigger();
             check_timer();
-            suspend_current_and_run_next();
+            tick_current();
         }
         Trap::Interrupt(Interrupt::SupervisorSoft) => {
             handler_ext();
@@ -103,11 +116,16 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+    if take_need_resched() {
+        suspend_current_and_run_next();
+    }
     trap_return();
 }
 
 #[no_mangle]
 pub fn trap_return() -> ! {
+    // may not return if a signal terminates the current task
+    handle_signals();
     intr_off();
     set_user_trap_entry();
     let trap_cx_user_va = current_trap_cx_user_va();
@@ -129,8 +147,9 @@ pub fn trap_return() -> ! {
     }
 }
 
+/// `sp` is where `__from_kernel_save` left the interrupted registers.
 #[no_mangle]
-pub fn trap_from_kernel() {
+pub fn trap_from_kernel(sp: usize) {
     assert_eq!(sstatus::read().spp(), SPP::Supervisor);
     let scause = scause::read();
     match scause.cause() {
@@ -139,10 +158,13 @@ pub fn trap_from_kernel() {
             sbi_smext_stimer();
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            println!("From S SupervisorTimer");
             set_next_trigger();
             check_timer();
-            suspend_current_and_run_next();
+            tick_current();
+            // otherwise the task is preempted on its way back to user mode
+            if kernel_preemptible(sp) && take_need_resched() {
+                suspend_current_and_run_next();
+            }
         }
         Trap::Interrupt(_) => todo!(),
         Trap::Exception(e) => {
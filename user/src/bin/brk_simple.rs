@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{brk, sbrk};
+
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = sbrk(0) as usize;
+    println!("brk_simple: break at {:#x}", start);
+    assert_ne!(sbrk(2 * PAGE_SIZE as isize), -1);
+    let heap = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, 2 * PAGE_SIZE) };
+    for (i, b) in heap.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    assert_eq!(heap[PAGE_SIZE + 1], 1);
+    assert_ne!(sbrk(-(PAGE_SIZE as isize)), -1);
+    assert_eq!(sbrk(0) as usize, start + PAGE_SIZE);
+    // the heap must not run into the user stack
+    let on_stack = 0usize;
+    assert_eq!(brk(&on_stack as *const _ as usize + PAGE_SIZE), start + PAGE_SIZE);
+    // allocations beyond the old fixed 32KiB heap
+    let v: Vec<u8> = (0..0x10000).map(|i| i as u8).collect();
+    assert_eq!(v[0x8001], 1);
+    println!("brk_simple: Done");
+    0
+}
@@ -17,7 +17,7 @@ use alloc::string::String;
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
     assert!(argc == 2);
     let fd = open(argv[1], OpenFlags::RDONLY);
-    if fd == -1 {
+    if fd < 0 {
         panic!("Error occured when opening file");
     }
     let fd = fd as usize;
@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{condvar_create, condvar_signal, condvar_wait};
+use user_lib::{exit, thread_create, waittid};
+use user_lib::{mutex_blocking_create, mutex_lock, mutex_unlock};
+
+const BUFFER_SIZE: usize = 4;
+const PRODUCER_COUNT: usize = 3;
+const NUMBER_PER_PRODUCER: usize = 50;
+const MUTEX: usize = 0;
+const NOT_FULL: usize = 0;
+const NOT_EMPTY: usize = 1;
+
+static mut BUFFER: [usize; BUFFER_SIZE] = [0; BUFFER_SIZE];
+static mut HEAD: usize = 0;
+static mut COUNT: usize = 0;
+
+unsafe fn producer() -> ! {
+    for i in 0..NUMBER_PER_PRODUCER {
+        mutex_lock(MUTEX);
+        while COUNT == BUFFER_SIZE { condvar_wait(NOT_FULL, MUTEX); }
+        BUFFER[(HEAD + COUNT) % BUFFER_SIZE] = i + 1;
+        COUNT += 1;
+        condvar_signal(NOT_EMPTY);
+        mutex_unlock(MUTEX);
+    }
+    exit(0)
+}
+
+unsafe fn consumer() -> ! {
+    let mut sum = 0;
+    for _ in 0..PRODUCER_COUNT * NUMBER_PER_PRODUCER {
+        mutex_lock(MUTEX);
+        while COUNT == 0 { condvar_wait(NOT_EMPTY, MUTEX); }
+        sum += BUFFER[HEAD];
+        HEAD = (HEAD + 1) % BUFFER_SIZE;
+        COUNT -= 1;
+        condvar_signal(NOT_FULL);
+        mutex_unlock(MUTEX);
+    }
+    exit(sum as i32)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mutex_blocking_create(), MUTEX as isize);
+    assert_eq!(condvar_create(), NOT_FULL as isize);
+    assert_eq!(condvar_create(), NOT_EMPTY as isize);
+    let mut producers = Vec::new();
+    for _ in 0..PRODUCER_COUNT {
+        producers.push(thread_create(producer as usize, 0));
+    }
+    let consumer = thread_create(consumer as usize, 0);
+    for tid in producers.iter() {
+        assert_eq!(waittid(*tid as usize), 0);
+    }
+    let expected = PRODUCER_COUNT * NUMBER_PER_PRODUCER * (NUMBER_PER_PRODUCER + 1) / 2;
+    assert_eq!(waittid(consumer as usize), expected as isize);
+    println!("condvar_test passed!");
+    0
+}
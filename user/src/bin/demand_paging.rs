@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+const PAGE_SIZE: usize = 4096;
+const BIG_SIZE: usize = 16 * 1024 * 1024;
+
+// far more than the physical memory, only the touched pages get frames
+static mut BIG: [u8; BIG_SIZE] = [0; BIG_SIZE];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let big = unsafe { &mut BIG };
+    for i in (0..BIG_SIZE).step_by(BIG_SIZE / 8) {
+        assert_eq!(big[i], 0);
+        big[i] = (i / PAGE_SIZE) as u8;
+    }
+    for i in (0..BIG_SIZE).step_by(BIG_SIZE / 8) {
+        assert_eq!(big[i], (i / PAGE_SIZE) as u8);
+    }
+    println!("demand_paging: Done");
+    0
+}
@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, read, write, OpenFlags, SEEK_END};
+
+fn open_dev(path: &str, flags: OpenFlags) -> usize {
+    let fd = open(path, flags);
+    assert!(fd > 0, "cannot open {}", path);
+    fd as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buf = [0xffu8; 64];
+
+    let null = open_dev("/dev/null\0", OpenFlags::RDWR);
+    assert_eq!(read(null, &mut buf), 0);
+    assert_eq!(write(null, b"into the void"), 13);
+    close(null);
+
+    let zero = open_dev("/dev/zero\0", OpenFlags::RDONLY);
+    assert_eq!(read(zero, &mut buf), 64);
+    assert!(buf.iter().all(|&b| b == 0));
+    close(zero);
+
+    let random = open_dev("/dev/random\0", OpenFlags::RDONLY);
+    assert_eq!(read(random, &mut buf), 64);
+    assert!(buf.iter().any(|&b| b != buf[0]));
+    close(random);
+
+    // the boot sector of the FAT volume ends with its signature
+    let mut sector = [0u8; 512];
+    let sda = open_dev("/dev/sda\0", OpenFlags::RDONLY);
+    assert_eq!(read(sda, &mut sector), 512);
+    assert_eq!(&sector[510..], &[0x55, 0xaa]);
+    // it ends where the disk does
+    let size = lseek(sda, 0, SEEK_END);
+    assert!(size > 512 && size % 512 == 0);
+    assert_eq!(read(sda, &mut sector), 0);
+    assert_eq!(lseek(sda, -256, SEEK_END), size - 256);
+    assert_eq!(read(sda, &mut sector), 256);
+    close(sda);
+
+    let tty = open_dev("/dev/tty\0", OpenFlags::WRONLY);
+    let msg = b"devfs_test passed!\n";
+    assert_eq!(write(tty, msg), msg.len() as isize);
+    close(tty);
+    0
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::slice;
+use core::str;
+use user_lib::errno::{check, EFAULT};
+use user_lib::{close, getcwd, open, read, write, OpenFlags};
+
+/// Never mapped: the first page.
+const UNMAPPED: usize = 0x10;
+/// Mapped, but without the U bit.
+const TRAMPOLINE: usize = usize::MAX - 4096 + 1;
+/// Above the user half of Sv39, it would alias low addresses if truncated.
+const NON_CANONICAL: usize = 0x8000_0000_0000_0000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let unmapped = unsafe { slice::from_raw_parts_mut(UNMAPPED as *mut u8, 16) };
+    assert_eq!(check(write(1, unmapped)), Err(EFAULT));
+    let kernel_only = unsafe { slice::from_raw_parts_mut(TRAMPOLINE as *mut u8, 16) };
+    assert_eq!(check(read(0, kernel_only)), Err(EFAULT));
+    let aliased = unsafe { slice::from_raw_parts_mut((NON_CANONICAL | 0x1000) as *mut u8, 16) };
+    assert_eq!(check(write(1, aliased)), Err(EFAULT));
+
+    // the text of the program is readable but not writable
+    let text = unsafe { slice::from_raw_parts_mut(main as usize as *mut u8, 64) };
+    assert_eq!(check(getcwd(text)), Err(EFAULT));
+    let path = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(UNMAPPED as *const u8, 1)) };
+    assert_eq!(check(open(path, OpenFlags::RDONLY)), Err(EFAULT));
+
+    // the kernel survived all of it
+    let fd = open("/\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(close(fd as usize), 0);
+    println!("efault_test passed!");
+    0
+}
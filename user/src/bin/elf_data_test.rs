@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exec, fork, waitpid};
+
+const LEN: usize = 3000;
+
+const fn word(i: usize) -> u32 {
+    (i as u32).wrapping_mul(2654435761) | 1
+}
+
+const fn pattern() -> [u32; LEN] {
+    let mut table = [0u32; LEN];
+    let mut i = 0;
+    while i < LEN {
+        table[i] = word(i);
+        i += 1;
+    }
+    table
+}
+
+/// Initialized and writable, so it lands in .data and spans several pages,
+/// all of which have to come from the file.
+static mut TABLE: [u32; LEN] = pattern();
+
+fn check_table() {
+    for i in 0..LEN {
+        assert_eq!(unsafe { TABLE[i] }, word(i), "bad .data word {}", i);
+    }
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    check_table();
+    if argc > 1 && argv[1] == "exec" {
+        return 0;
+    }
+    // a fresh image gets the data from the file again, not from this one
+    unsafe {
+        TABLE.fill(0);
+    }
+    let pid = fork();
+    if pid == 0 {
+        let args = ["elf_data_test\0".as_ptr(), "exec\0".as_ptr(), core::ptr::null()];
+        exec("elf_data_test\0", &args);
+        return 1;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("elf_data_test passed!");
+    0
+}
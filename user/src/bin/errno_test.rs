@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+#![feature(asm)]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, EACCES, EBADF, EISDIR, ENODEV, ENOENT, ENOSYS, ESRCH};
+use user_lib::{close, kill, mmap, open, pipe, read, unlink, write, MmapFlags, MmapProt, OpenFlags, SIGUSR1};
+
+/// A syscall number the kernel does not know.
+const SYSCALL_UNKNOWN: usize = 4095;
+
+fn unknown_syscall() -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!("ecall", lateout("x10") ret, in("x17") SYSCALL_UNKNOWN);
+    }
+    ret
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // not found
+    assert_eq!(check(open("errno_missing\0", OpenFlags::RDONLY)), Err(ENOENT));
+    // bad fd
+    let mut buf = [0u8; 4];
+    assert_eq!(check(read(99, &mut buf)), Err(EBADF));
+    assert_eq!(check(close(99)), Err(EBADF));
+
+    let fd = open("errno_test\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, b"errno");
+    close(fd as usize);
+    let fd = open("errno_test\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    // a read-only fd is a bad fd to write to, but a lack of permission to map shared-writable
+    assert_eq!(check(write(fd as usize, b"x")), Err(EBADF));
+    let ret = mmap(0, 4096, MmapProt::READ | MmapProt::WRITE, MmapFlags::SHARED, fd as usize, 0);
+    assert_eq!(check(ret), Err(EACCES));
+    close(fd as usize);
+    assert_eq!(unlink("errno_test\0"), 0);
+    // only regular files can be mapped
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let ret = mmap(0, 4096, MmapProt::READ, MmapFlags::PRIVATE, fds[0], 0);
+    assert_eq!(check(ret), Err(ENODEV));
+    close(fds[0]);
+    close(fds[1]);
+
+    assert_eq!(check(open("/\0", OpenFlags::WRONLY)), Err(EISDIR));
+    assert_eq!(check(kill(1 << 20, SIGUSR1)), Err(ESRCH));
+    assert_eq!(check(unknown_syscall()), Err(ENOSYS));
+    println!("errno_test passed!");
+    0
+}
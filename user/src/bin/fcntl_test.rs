@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, EAGAIN, EBADF, EEXIST, EINVAL, EMFILE};
+use user_lib::{
+    close, dup3, exec, fcntl, fork, lseek, open, read, unlink, waitpid, write, OpenFlags, FD_CLOEXEC, F_DUPFD,
+    F_DUPFD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL, SEEK_SET,
+};
+
+const PATH: &str = "/tmp/fcntl_test\0";
+/// One past the highest fd the kernel hands out.
+const FD_LIMIT: usize = 1000;
+/// Kept open across exec.
+const KEPT_FD: usize = 20;
+/// Closed by exec.
+const CLOSED_FD: usize = 21;
+
+/// Run again by exec, with the fds set up by the first run.
+fn after_exec() -> i32 {
+    assert_eq!(fcntl(KEPT_FD, F_GETFD, 0), 0);
+    assert_eq!(check(fcntl(CLOSED_FD, F_GETFD, 0)), Err(EBADF));
+    0
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 && argv[1] == "exec" {
+        return after_exec();
+    }
+    let fd = open(PATH, OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(check(open(PATH, OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRONLY)), Err(EEXIST));
+    assert_eq!(write(fd, b"hello"), 5);
+    close(fd);
+
+    // every write of an O_APPEND file goes to its end
+    let fd = open(PATH, OpenFlags::WRONLY | OpenFlags::APPEND) as usize;
+    assert_eq!(fcntl(fd, F_GETFL, 0), (OpenFlags::WRONLY | OpenFlags::APPEND).bits() as isize);
+    lseek(fd, 0, SEEK_SET);
+    assert_eq!(write(fd, b" world"), 6);
+    // and F_SETFL can take it away
+    assert_eq!(fcntl(fd, F_SETFL, 0), 0);
+    lseek(fd, 0, SEEK_SET);
+    assert_eq!(write(fd, b"H"), 1);
+    close(fd);
+    let fd = open(PATH, OpenFlags::RDONLY) as usize;
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fd, &mut buf), 11);
+    assert_eq!(&buf[..11], b"Hello world");
+    close(fd);
+    let fd = open(PATH, OpenFlags::RDWR | OpenFlags::TRUNC) as usize;
+    assert_eq!(read(fd, &mut buf), 0);
+
+    // F_DUPFD takes the lowest free fd from its argument up
+    let dup_fd = fcntl(fd, F_DUPFD, 10);
+    assert!(dup_fd >= 10);
+    assert_eq!(fcntl(dup_fd as usize, F_GETFD, 0), 0);
+    close(dup_fd as usize);
+    let dup_fd = fcntl(fd, F_DUPFD_CLOEXEC, 10) as usize;
+    assert_eq!(fcntl(dup_fd, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(dup_fd, F_SETFD, 0), 0);
+    assert_eq!(fcntl(dup_fd, F_GETFD, 0), 0);
+    close(dup_fd);
+    // an argument past the limit is invalid, a full range is not
+    assert_eq!(check(fcntl(fd, F_DUPFD, FD_LIMIT)), Err(EINVAL));
+    assert_eq!(fcntl(fd, F_DUPFD, FD_LIMIT - 1), (FD_LIMIT - 1) as isize);
+    assert_eq!(check(fcntl(fd, F_DUPFD_CLOEXEC, FD_LIMIT - 1)), Err(EMFILE));
+    close(FD_LIMIT - 1);
+    assert_eq!(check(fcntl(fd, 9999, 0)), Err(EINVAL));
+
+    // a terminal without input would block, unless O_NONBLOCK
+    let tty = open("/dev/tty\0", OpenFlags::RDONLY | OpenFlags::NONBLOCK) as usize;
+    assert_eq!(check(read(tty, &mut buf)), Err(EAGAIN));
+    close(tty);
+
+    // exec closes the O_CLOEXEC fds only
+    assert_eq!(dup3(fd, KEPT_FD, OpenFlags::empty()), KEPT_FD as isize);
+    assert_eq!(dup3(fd, CLOSED_FD, OpenFlags::CLOEXEC), CLOSED_FD as isize);
+    assert_eq!(check(dup3(fd, KEPT_FD, OpenFlags::APPEND)), Err(EINVAL));
+    // fds stop at a limit, far below this
+    assert_eq!(check(dup3(fd, 1 << 20, OpenFlags::empty())), Err(EBADF));
+    let pid = fork();
+    if pid == 0 {
+        let args = ["fcntl_test\0".as_ptr(), "exec\0".as_ptr(), core::ptr::null()];
+        exec("fcntl_test\0", &args);
+        return 1;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(KEPT_FD);
+    close(CLOSED_FD);
+    close(fd);
+    assert_eq!(unlink(PATH), 0);
+    println!("fcntl_test passed!");
+    0
+}
@@ -0,0 +1,74 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, EEXIST, ENXIO, EPERM};
+use user_lib::{close, exit, fork, mkfifo, open, read, sleep, unlink, waitpid, write, OpenFlags};
+
+const PATH: &str = "/tmp/fifo_test\0";
+const MESSAGE: &[u8] = b"through the filesystem";
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkfifo(PATH), 0);
+    assert_eq!(check(mkfifo(PATH)), Err(EEXIST));
+    // the FAT root cannot hold one
+    assert_eq!(check(mkfifo("/fifo_test\0")), Err(EPERM));
+
+    // no reader yet: a nonblocking writer is turned away, a reader is not
+    assert_eq!(check(open(PATH, OpenFlags::WRONLY | OpenFlags::NONBLOCK)), Err(ENXIO));
+    let fd = open(PATH, OpenFlags::RDONLY | OpenFlags::NONBLOCK);
+    assert!(fd > 0);
+    let mut buf = [0u8; 64];
+    assert_eq!(read(fd as usize, &mut buf), 0);
+    close(fd as usize);
+
+    // the writer waits in open until the reader comes
+    let pid = fork();
+    if pid == 0 {
+        let fd = open(PATH, OpenFlags::WRONLY);
+        assert!(fd > 0);
+        assert_eq!(write(fd as usize, MESSAGE), MESSAGE.len() as isize);
+        close(fd as usize);
+        exit(0);
+    }
+    sleep(50);
+    let fd = open(PATH, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut received = 0;
+    loop {
+        let len = read(fd as usize, &mut buf[received..]);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        received += len as usize;
+    }
+    assert_eq!(&buf[..received], MESSAGE);
+    close(fd as usize);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // and the reader waits in open until the writer comes
+    let pid = fork();
+    if pid == 0 {
+        sleep(50);
+        let fd = open(PATH, OpenFlags::WRONLY);
+        assert!(fd > 0);
+        write(fd as usize, MESSAGE);
+        close(fd as usize);
+        exit(0);
+    }
+    let fd = open(PATH, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert!(read(fd as usize, &mut buf) > 0);
+    close(fd as usize);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+
+    assert_eq!(unlink(PATH), 0);
+    println!("fifo_test passed!");
+    0
+}
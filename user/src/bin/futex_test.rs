@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicU32;
+use user_lib::futex::{futex_wait, Condvar, Mutex};
+use user_lib::{exit, get_time, thread_create, waittid};
+
+const THREAD_COUNT: usize = 4;
+const PER_THREAD: usize = 1000;
+
+static LOCK: Mutex = Mutex::new();
+static READY: Condvar = Condvar::new();
+static mut COUNTER: usize = 0;
+static mut STARTED: bool = false;
+
+unsafe fn adder() -> ! {
+    LOCK.lock();
+    while !STARTED {
+        READY.wait(&LOCK);
+    }
+    LOCK.unlock();
+    for _ in 0..PER_THREAD {
+        LOCK.lock();
+        let old = COUNTER;
+        for _ in 0..100 {}
+        COUNTER = old + 1;
+        LOCK.unlock();
+    }
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a word holding another value must not block, a matching one times out
+    let word = AtomicU32::new(1);
+    assert_eq!(futex_wait(&word, 0, None), -11);
+    let start = get_time();
+    assert_eq!(futex_wait(&word, 1, Some(100)), -110);
+    assert!(get_time() - start >= 100);
+
+    let mut v = Vec::new();
+    for _ in 0..THREAD_COUNT {
+        v.push(thread_create(adder as usize, 0));
+    }
+    LOCK.lock();
+    unsafe { STARTED = true; }
+    READY.broadcast();
+    LOCK.unlock();
+    for tid in v.iter() {
+        assert_eq!(waittid(*tid as usize), 0);
+    }
+    assert_eq!(unsafe { COUNTER }, THREAD_COUNT * PER_THREAD);
+    println!("futex_test passed!");
+    0
+}
@@ -10,6 +10,7 @@ use user_lib::{
     exec,
     yield_,
 };
+use user_lib::errno::ECHILD;
 
 #[no_mangle]
 fn main() -> i32 {
@@ -19,7 +20,7 @@ fn main() -> i32 {
         loop {
             let mut exit_code: i32 = 0;
             let pid = wait(&mut exit_code);
-            if pid == -1 {
+            if pid == ECHILD.ret() {
                 yield_();
                 continue;
             }
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{fork, mmap, munmap, waitpid, MmapFlags, MmapProt};
+
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let len = 4 * PAGE_SIZE;
+    let start = mmap(0, len, MmapProt::READ | MmapProt::WRITE, MmapFlags::PRIVATE | MmapFlags::ANONYMOUS, 0, 0);
+    assert!(start > 0, "anonymous mmap failed");
+    let start = start as usize;
+    let buf = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, len) };
+    assert!(buf.iter().all(|b| *b == 0));
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    println!("mmap_simple: private mapping at {:#x}", start);
+    // punch a hole in the middle, the pages around it must survive
+    assert_eq!(munmap(start + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(buf[0], 0);
+    assert_eq!(buf[2 * PAGE_SIZE + 1], 1);
+    assert_eq!(munmap(start, len), 0);
+
+    let shared = mmap(0, PAGE_SIZE, MmapProt::READ | MmapProt::WRITE, MmapFlags::SHARED | MmapFlags::ANONYMOUS, 0, 0);
+    assert!(shared > 0, "shared mmap failed");
+    let value = unsafe { &mut *(shared as usize as *mut usize) };
+    *value = 0;
+    let pid = fork();
+    if pid == 0 {
+        *value = 0x5a5a;
+        return 0;
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(*value, 0x5a5a);
+    println!("mmap_simple: shared mapping seen by parent");
+    println!("mmap_simple: Done");
+    0
+}
@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, EBUSY, EINVAL, ENODEV, ENOENT};
+use user_lib::{chdir, close, mkdir, mount, open, read, rmdir, umount, write, OpenFlags};
+
+const OUTER: &str = "/tmp/mount_test\0";
+const INNER: &str = "/tmp/mount_test/inner\0";
+const FILE: &str = "/tmp/mount_test/file\0";
+
+/// A file on a fresh tmpfs lives and dies with the mount.
+fn mount_and_umount() {
+    let test_str = "mounted on top";
+    assert_eq!(mount("none\0", OUTER, "tmpfs\0"), 0);
+    assert_eq!(check(mount("none\0", OUTER, "tmpfs\0")), Err(EBUSY));
+
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+
+    let fd = open(FILE, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 32];
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    close(fd as usize);
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+
+    assert_eq!(umount(OUTER), 0);
+    assert_eq!(check(open(FILE, OpenFlags::RDONLY)), Err(ENOENT));
+    assert_eq!(check(umount(OUTER)), Err(EINVAL));
+}
+
+/// An easy-fs volume on the RAM disk keeps its files across mounts.
+fn easyfs() {
+    let test_str = "kept on the ram disk";
+    // easy-fs lives on the RAM disk only
+    assert_eq!(check(mount("/dev/sda\0", OUTER, "easyfs\0")), Err(ENODEV));
+    assert_eq!(mount("/dev/ram0\0", OUTER, "easyfs\0"), 0);
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, test_str.as_bytes()), test_str.len() as isize);
+    close(fd as usize);
+    assert_eq!(umount(OUTER), 0);
+    assert_eq!(check(open(FILE, OpenFlags::RDONLY)), Err(ENOENT));
+
+    assert_eq!(mount("/dev/ram0\0", OUTER, "easyfs\0"), 0);
+    let fd = open(FILE, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 32];
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    close(fd as usize);
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    assert_eq!(umount(OUTER), 0);
+}
+
+/// A filesystem stays mounted while something still uses it.
+fn busy() {
+    assert_eq!(mount("none\0", OUTER, "tmpfs\0"), 0);
+    assert_eq!(mkdir(INNER), 0);
+    assert_eq!(mount("none\0", INNER, "tmpfs\0"), 0);
+    // another filesystem mounted below it
+    assert_eq!(check(umount(OUTER)), Err(EBUSY));
+    assert_eq!(umount(INNER), 0);
+    // an open file
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(check(umount(OUTER)), Err(EBUSY));
+    close(fd as usize);
+    // a working directory
+    assert_eq!(chdir(OUTER), 0);
+    assert_eq!(check(umount(OUTER)), Err(EBUSY));
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(umount(OUTER), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir(OUTER), 0);
+    // the root volume is mounted already
+    assert_eq!(check(mount("/dev/sda\0", OUTER, "vfat\0")), Err(EBUSY));
+    assert_eq!(check(mount("none\0", OUTER, "nofs\0")), Err(ENODEV));
+    assert_eq!(check(umount("/\0")), Err(EBUSY));
+    mount_and_umount();
+    easyfs();
+    busy();
+    assert_eq!(rmdir(OUTER), 0);
+    println!("mount_test passed!");
+    0
+}
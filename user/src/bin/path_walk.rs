@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, ENOENT, ENOTDIR, ENOTEMPTY};
+use user_lib::{chdir, close, getcwd, mkdir, open, rmdir, unlink, OpenFlags};
+
+fn assert_cwd(expected: &str) {
+    let mut buf = [0u8; 64];
+    assert!(getcwd(&mut buf) > 0);
+    let len = buf.iter().position(|&b| b == 0).unwrap();
+    assert_eq!(core::str::from_utf8(&buf[..len]).unwrap(), expected);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("/walk_a\0"), 0);
+    assert_eq!(mkdir("walk_a/b\0"), 0);
+    assert_eq!(chdir("/walk_a/b\0"), 0);
+    assert_cwd("/walk_a/b");
+
+    let fd = open("../b/./file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    let fd = open("/walk_a/b/file\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(check(open("missing\0", OpenFlags::RDONLY)), Err(ENOENT));
+    assert_eq!(check(open("file/x\0", OpenFlags::RDONLY)), Err(ENOTDIR));
+    assert_eq!(check(chdir("file\0")), Err(ENOTDIR));
+
+    assert_eq!(chdir("..\0"), 0);
+    assert_cwd("/walk_a");
+    assert_eq!(chdir("../..\0"), 0);
+    assert_cwd("/");
+
+    assert_eq!(check(rmdir("walk_a/b\0")), Err(ENOTEMPTY));
+    assert_eq!(unlink("walk_a/b/file\0"), 0);
+    assert_eq!(check(open("walk_a/b/file\0", OpenFlags::RDONLY)), Err(ENOENT));
+    assert_eq!(rmdir("walk_a/b\0"), 0);
+    assert_eq!(rmdir("walk_a\0"), 0);
+    println!("path_walk passed!");
+    0
+}
@@ -0,0 +1,110 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+
+use user_lib::errno::{check, EAGAIN, EINVAL, EPIPE};
+use user_lib::{
+    close, exit, fcntl, fork, get_time, pipe, pipe2, poll, read, sigaction, waitpid, write, OpenFlags, PollFd,
+    SignalAction, FD_CLOEXEC, F_GETFD, F_GETFL, POLLHUP, POLLIN, SIGPIPE, SIG_IGN,
+};
+
+/// More than a pipe holds at first, so the writer has to wait for room.
+const TOTAL: usize = 1 << 20;
+const CHUNK: usize = 32 * 1024;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut fds = [0usize; 2];
+    assert_eq!(check(pipe2(&mut fds, OpenFlags::APPEND)), Err(EINVAL));
+
+    // nonblocking: an empty pipe has nothing to read, a full one no room
+    assert_eq!(pipe2(&mut fds, OpenFlags::NONBLOCK | OpenFlags::CLOEXEC), 0);
+    let [rfd, wfd] = fds;
+    assert!(fcntl(rfd, F_GETFL, 0) as u32 & OpenFlags::NONBLOCK.bits() != 0);
+    assert_eq!(fcntl(wfd, F_GETFD, 0) as usize, FD_CLOEXEC);
+    // the user stack is too small for these
+    let mut buf = vec![0u8; CHUNK];
+    assert_eq!(check(read(rfd, &mut buf)), Err(EAGAIN));
+    let mut stored = 0;
+    loop {
+        match check(write(wfd, &buf)) {
+            Ok(len) => stored += len,
+            Err(errno) => {
+                assert_eq!(errno, EAGAIN);
+                break;
+            }
+        }
+    }
+    assert!(stored > 4096);
+    let mut drained = 0;
+    while let Ok(len) = check(read(rfd, &mut buf)) {
+        drained += len;
+    }
+    assert_eq!(drained, stored);
+    close(rfd);
+    close(wfd);
+
+    // blocking: a child streams data through, EOF once it is done
+    assert_eq!(pipe(&mut fds), 0);
+    let [rfd, wfd] = fds;
+    let start = get_time();
+    let pid = fork();
+    if pid == 0 {
+        close(rfd);
+        let chunk = vec![0x5au8; CHUNK];
+        let mut sent = 0;
+        while sent < TOTAL {
+            sent += write(wfd, &chunk) as usize;
+        }
+        exit(0);
+    }
+    close(wfd);
+    let mut received = 0;
+    loop {
+        let len = read(rfd, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        assert!(buf[..len as usize].iter().all(|b| *b == 0x5a));
+        received += len as usize;
+    }
+    assert_eq!(received, TOTAL);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("pipe2_test: {} KiB in {} ms", TOTAL / 1024, get_time() - start);
+    let mut events = [PollFd::new(rfd as i32, POLLIN)];
+    assert_eq!(poll(&mut events, 0), 1);
+    assert_eq!(events[0].revents, POLLHUP);
+    close(rfd);
+
+    // no reader left: SIGPIPE kills by default
+    assert_eq!(pipe(&mut fds), 0);
+    let [rfd, wfd] = fds;
+    close(rfd);
+    let pid = fork();
+    if pid == 0 {
+        write(wfd, b"lost");
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -SIGPIPE);
+
+    // and with SIGPIPE ignored the write fails with EPIPE
+    let action = SignalAction {
+        handler: SIG_IGN,
+        ..Default::default()
+    };
+    assert_eq!(sigaction(SIGPIPE, Some(&action), None), 0);
+    assert_eq!(check(write(wfd, b"lost")), Err(EPIPE));
+    close(wfd);
+
+    println!("pipe2_test passed!");
+    0
+}
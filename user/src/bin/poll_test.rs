@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, EBADF, EEXIST, EINVAL, ENOENT};
+use user_lib::{
+    close, epoll_create1, epoll_ctl, epoll_wait, get_time, open, poll, select, unlink, EpollEvent, FdSet, OpenFlags,
+    PollFd, EPOLLIN, EPOLLOUT, EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD, POLLIN, POLLNVAL, POLLOUT,
+};
+
+/// Nothing is typed while the tests run, so the terminal never has input.
+const TTY: usize = 0;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let null = open("/dev/null\0", OpenFlags::RDWR);
+    let file = open("/tmp/poll_test\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(null > 0 && file > 0);
+    let (null, file) = (null as usize, file as usize);
+
+    // poll reports only what is asked for, and skips negative fds
+    let mut fds = [
+        PollFd::new(null as i32, POLLIN | POLLOUT),
+        PollFd::new(file as i32, POLLIN | POLLOUT),
+        PollFd::new(TTY as i32, POLLIN),
+        PollFd::new(-1, POLLIN),
+        PollFd::new(99, POLLIN),
+    ];
+    assert_eq!(poll(&mut fds, 0), 3);
+    assert_eq!(fds[0].revents, POLLIN | POLLOUT);
+    assert_eq!(fds[1].revents, POLLOUT);
+    assert_eq!(fds[2].revents, 0);
+    assert_eq!(fds[3].revents, 0);
+    assert_eq!(fds[4].revents, POLLNVAL);
+
+    // nothing ready: the timeout runs out
+    let mut tty = [PollFd::new(TTY as i32, POLLIN)];
+    let start = get_time();
+    assert_eq!(poll(&mut tty, 50), 0);
+    assert!(get_time() - start >= 50);
+
+    // select keeps the ready fds only
+    let mut readfds = FdSet::default();
+    readfds.set(null);
+    readfds.set(TTY);
+    let mut writefds = FdSet::default();
+    writefds.set(file);
+    assert_eq!(select(file + 1, Some(&mut readfds), Some(&mut writefds), None, Some(0)), 2);
+    assert!(readfds.is_set(null) && !readfds.is_set(TTY));
+    assert!(writefds.is_set(file));
+    let mut badfds = FdSet::default();
+    badfds.set(99);
+    assert_eq!(check(select(100, Some(&mut badfds), None, None, Some(0))), Err(EBADF));
+
+    // epoll, level-triggered
+    let epfd = epoll_create1(0);
+    assert!(epfd > 0);
+    let epfd = epfd as usize;
+    let mut events = [EpollEvent::default(); 4];
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, null, &EpollEvent { events: EPOLLIN, data: 1 }), 0);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, file, &EpollEvent { events: EPOLLIN, data: 2 }), 0);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, TTY, &EpollEvent { events: EPOLLIN, data: 3 }), 0);
+    assert_eq!(check(epoll_ctl(epfd, EPOLL_CTL_ADD, null, &EpollEvent::default())), Err(EEXIST));
+    assert_eq!(epoll_wait(epfd, &mut events, 0), 1);
+    assert_eq!((events[0].events, events[0].data), (EPOLLIN, 1));
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_MOD, file, &EpollEvent { events: EPOLLOUT, data: 2 }), 0);
+    assert_eq!(epoll_wait(epfd, &mut events, 0), 2);
+    assert_eq!(epoll_wait(epfd, &mut events[..1], 0), 1);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_DEL, file, &EpollEvent::default()), 0);
+    assert_eq!(check(epoll_ctl(epfd, EPOLL_CTL_DEL, file, &EpollEvent::default())), Err(ENOENT));
+    // a closed file drops out of the set
+    close(null);
+    let start = get_time();
+    assert_eq!(epoll_wait(epfd, &mut events, 50), 0);
+    assert!(get_time() - start >= 50);
+
+    assert_eq!(check(epoll_ctl(epfd, EPOLL_CTL_ADD, epfd, &EpollEvent::default())), Err(EINVAL));
+    assert_eq!(check(epoll_ctl(file, EPOLL_CTL_ADD, TTY, &EpollEvent::default())), Err(EINVAL));
+    assert_eq!(check(epoll_wait(epfd, &mut [], 0)), Err(EINVAL));
+    close(epfd);
+    close(file);
+    assert_eq!(unlink("/tmp/poll_test\0"), 0);
+    println!("poll_test passed!");
+    0
+}
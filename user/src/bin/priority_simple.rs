@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{fork, getpriority, setpriority, waitpid};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(getpriority(), 0);
+    assert_eq!(setpriority(5), 0);
+    assert_eq!(getpriority(), 5);
+    // out of range values are clamped
+    setpriority(100);
+    assert_eq!(getpriority(), 19);
+    setpriority(-100);
+    assert_eq!(getpriority(), -20);
+    setpriority(3);
+    let pid = fork();
+    if pid == 0 {
+        // the child inherits the nice value
+        assert_eq!(getpriority(), 3);
+        return 0;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("priority_simple: Done");
+    0
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use user_lib::errno::{check, ENOENT};
+use user_lib::{close, getpid, open, read, OpenFlags};
+
+/// Read all of a file of /proc, they have no size to ask for.
+fn read_proc(path: &str) -> String {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0, "cannot open {}", path);
+    let mut text = String::new();
+    let mut buf = [0u8; 64];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        text.push_str(core::str::from_utf8(&buf[..len as usize]).unwrap());
+    }
+    close(fd as usize);
+    text
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let status = read_proc("/proc/self/status\0");
+    assert!(status.contains("Name:\tproc_test\n"));
+    assert!(status.contains("State:\tR (running)\n"));
+    assert!(status.contains(&format!("Pid:\t{}\n", getpid())));
+    // the same process under its pid
+    let by_pid = read_proc(&format!("/proc/{}/status\0", getpid()));
+    assert!(by_pid.contains(&format!("Pid:\t{}\n", getpid())));
+
+    // the text of the program is mapped readable and executable
+    let maps = read_proc("/proc/self/maps\0");
+    assert!(maps.lines().any(|line| line.contains(" r-xp ")));
+    assert_eq!(read_proc("/proc/self/fd/0\0"), "/dev/tty\n");
+    assert_eq!(check(open("/proc/self/fd/99\0", OpenFlags::RDONLY)), Err(ENOENT));
+
+    assert!(read_proc("/proc/meminfo\0").starts_with("MemTotal:"));
+    assert!(read_proc("/proc/cpuinfo\0").contains("processor\t: 0\n"));
+    assert!(read_proc("/proc/mounts\0").contains(" /proc proc "));
+    assert!(read_proc("/proc/uptime\0").ends_with(" 0.00\n"));
+    assert_eq!(check(open("/proc/999999/status\0", OpenFlags::RDONLY)), Err(ENOENT));
+    println!("proc_test passed!");
+    0
+}
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, EINVAL, ESPIPE};
+use user_lib::{
+    close, lseek, open, pread, pwrite, read, readv, unlink, write, writev, IoVec, OpenFlags, SEEK_CUR, SEEK_END,
+    SEEK_SET,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/tmp/seek_test\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+
+    // writev gathers, in order
+    let iov = [IoVec::new(b"hello"), IoVec::new(b", "), IoVec::new(b"world")];
+    assert_eq!(writev(fd, &iov), 12);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 12);
+    assert_eq!(lseek(fd, -5, SEEK_END), 7);
+    let mut buf = [0u8; 5];
+    assert_eq!(read(fd, &mut buf), 5);
+    assert_eq!(&buf, b"world");
+    assert_eq!(check(lseek(fd, -1, SEEK_SET)), Err(EINVAL));
+    assert_eq!(check(lseek(fd, 0, 7)), Err(EINVAL));
+
+    // pread and pwrite leave the offset alone
+    assert_eq!(lseek(fd, 2, SEEK_SET), 2);
+    assert_eq!(pwrite(fd, b"J", 0), 1);
+    assert_eq!(pread(fd, &mut buf, 0), 5);
+    assert_eq!(&buf, b"Jello");
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 2);
+
+    // readv scatters, in order
+    let mut head = [0u8; 3];
+    let mut tail = [0u8; 4];
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let iov = [IoVec::new_mut(&mut head), IoVec::new_mut(&mut tail)];
+    assert_eq!(readv(fd, &iov), 7);
+    assert_eq!(&head, b"Jel");
+    assert_eq!(&tail, b"lo, ");
+    close(fd);
+    assert_eq!(unlink("/tmp/seek_test\0"), 0);
+
+    // the console has no offset
+    assert_eq!(check(lseek(1, 0, SEEK_CUR)), Err(ESPIPE));
+    assert_eq!(check(pread(0, &mut buf, 0)), Err(ESPIPE));
+    println!("seek_test passed!");
+    0
+}
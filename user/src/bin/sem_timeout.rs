@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, get_time, sleep, thread_create, waittid};
+use user_lib::{semaphore_create, semaphore_down, semaphore_down_timeout, semaphore_try_down, semaphore_up};
+
+const SEM: usize = 0;
+
+fn waker() -> ! {
+    sleep(50);
+    semaphore_up(SEM);
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(semaphore_create(1), SEM as isize);
+    assert!(semaphore_try_down(SEM));
+    assert!(!semaphore_try_down(SEM));
+    // nobody ups the semaphore, so the timeout fires
+    let start = get_time();
+    assert!(!semaphore_down_timeout(SEM, 100));
+    assert!(get_time() - start >= 100);
+    // the waker ups it well before the timeout
+    let tid = thread_create(waker as usize, 0);
+    let start = get_time();
+    assert!(semaphore_down_timeout(SEM, 5000));
+    assert!(get_time() - start < 5000);
+    assert_eq!(waittid(tid as usize), 0);
+    // a plain down sleeps until the up
+    let tid = thread_create(waker as usize, 0);
+    semaphore_down(SEM);
+    assert_eq!(waittid(tid as usize), 0);
+    println!("sem_timeout: Done");
+    0
+}
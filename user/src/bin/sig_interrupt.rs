@@ -0,0 +1,119 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, EINTR};
+use user_lib::{close, exit, fork, kill, pipe, ppoll, read, sigaction, sigprocmask, sleep, thread_create};
+use user_lib::{waitpid, write, PollFd, SignalAction, SignalFlags, POLLIN, SIG_BLOCK};
+use user_lib::{SIGTERM, SIGUSR1};
+
+static mut RECEIVED: i32 = 0;
+
+fn func(signum: i32) {
+    unsafe { RECEIVED = signum; }
+}
+
+/// Fork a child which runs `body` once it has told the parent it is about
+/// to block, then sleep a little so that it really does. The parent keeps
+/// the write end of the pipe `body` reads, so nothing ends the read but a
+/// signal.
+fn spawn(body: fn(usize) -> i32) -> usize {
+    let mut ready = [0usize; 2];
+    let mut idle = [0usize; 2];
+    assert_eq!(pipe(&mut ready), 0);
+    assert_eq!(pipe(&mut idle), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(ready[0]);
+        close(idle[1]);
+        let new = SignalAction { handler: func as usize, ..Default::default() };
+        assert_eq!(sigaction(SIGUSR1, Some(&new), None), 0);
+        assert_eq!(write(ready[1], b"x"), 1);
+        exit(body(idle[0]));
+    }
+    close(ready[1]);
+    close(idle[0]);
+    let mut buf = [0u8; 1];
+    assert_eq!(read(ready[0], &mut buf), 1);
+    close(ready[0]);
+    sleep(50);
+    pid as usize
+}
+
+fn read_interrupted(fd: usize) -> i32 {
+    let mut buf = [0u8; 1];
+    assert_eq!(check(read(fd, &mut buf)), Err(EINTR));
+    assert_eq!(unsafe { RECEIVED }, SIGUSR1);
+    0
+}
+
+fn read_forever(fd: usize) -> i32 {
+    let mut buf = [0u8; 1];
+    read(fd, &mut buf);
+    panic!("read returned without data");
+}
+
+fn ppoll_unmasked(fd: usize) -> i32 {
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGUSR1), None), 0);
+    let mut fds = [PollFd::new(fd as i32, POLLIN)];
+    assert_eq!(check(ppoll(&mut fds, -1, SignalFlags::empty())), Err(EINTR));
+    assert_eq!(unsafe { RECEIVED }, SIGUSR1);
+    // blocked again once the wait is over
+    let mut mask = SignalFlags::empty();
+    assert_eq!(sigprocmask(SIG_BLOCK, None, Some(&mut mask)), 0);
+    assert!(mask.contains(SignalFlags::SIGUSR1));
+    0
+}
+
+fn ppoll_masked(fd: usize) -> i32 {
+    let mut fds = [PollFd::new(fd as i32, POLLIN)];
+    assert_eq!(ppoll(&mut fds, 500, SignalFlags::SIGUSR1), 0);
+    // held back during the wait only
+    assert_eq!(unsafe { RECEIVED }, SIGUSR1);
+    0
+}
+
+fn sleep_forever() -> ! {
+    loop {
+        sleep(1000);
+    }
+}
+
+fn threads_forever(fd: usize) -> i32 {
+    assert!(thread_create(sleep_forever as usize, 0) > 0);
+    read_forever(fd)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a handled signal makes the blocked read fail with EINTR
+    let pid = spawn(read_interrupted);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    assert_eq!(exit_code, 0);
+
+    // ppoll waits with the mask it is given
+    for body in [ppoll_unmasked, ppoll_masked] {
+        let pid = spawn(body);
+        assert_eq!(kill(pid, SIGUSR1), 0);
+        assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+        assert_eq!(exit_code, 0);
+    }
+
+    // a fatal one kills the reader without waiting for data
+    let pid = spawn(read_forever);
+    assert_eq!(kill(pid, SIGTERM), 0);
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    assert_eq!(exit_code, -SIGTERM);
+
+    // so does it with other threads whose resources go along with it
+    let pid = spawn(threads_forever);
+    assert_eq!(kill(pid, SIGTERM), 0);
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    assert_eq!(exit_code, -SIGTERM);
+    println!("sig_interrupt passed!");
+    0
+}
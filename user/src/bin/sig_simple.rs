@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{getpid, kill, sigaction, sigprocmask, SignalAction, SignalFlags};
+use user_lib::{SIGUSR1, SIG_BLOCK, SIG_UNBLOCK};
+
+static mut RECEIVED: i32 = 0;
+
+fn func(signum: i32) {
+    println!("user_sig_test succsess, got signal {}", signum);
+    unsafe { RECEIVED = signum; }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let new = SignalAction { handler: func as usize, ..Default::default() };
+    let mut old = SignalAction::default();
+    println!("signal_simple: sigaction");
+    if sigaction(SIGUSR1, Some(&new), Some(&mut old)) < 0 {
+        panic!("Sigaction failed!");
+    }
+    println!("signal_simple: block SIGUSR1 and kill");
+    sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGUSR1), None);
+    if kill(getpid() as usize, SIGUSR1) < 0 {
+        panic!("Kill failed!");
+    }
+    assert_eq!(unsafe { RECEIVED }, 0);
+    println!("signal_simple: unblock SIGUSR1");
+    sigprocmask(SIG_UNBLOCK, Some(SignalFlags::SIGUSR1), None);
+    assert_eq!(unsafe { RECEIVED }, SIGUSR1);
+    println!("signal_simple: Done");
+    0
+}
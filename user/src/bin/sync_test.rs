@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, EBADF};
+use user_lib::{close, fsync, open, read, sync, syncfs, unlink, write, OpenFlags};
+
+/// The value of `key` in /proc/blockcache.
+fn cache_stat(key: &str) -> usize {
+    let fd = open("/proc/blockcache\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 256];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    let text = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    let line = text.lines().find(|line| line.starts_with(key)).unwrap();
+    line[key.len()..].trim().parse().unwrap()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("sync_test\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // small writes only dirty the cached sectors
+    for _ in 0..64 {
+        assert_eq!(write(fd, b"sync"), 4);
+    }
+    assert_eq!(fsync(fd), 0);
+    assert_eq!(cache_stat("dirty"), 0);
+    assert!(cache_stat("hits") > 0);
+    assert_eq!(write(fd, b"more"), 4);
+    assert_eq!(syncfs(fd), 0);
+    assert_eq!(cache_stat("dirty"), 0);
+    close(fd);
+
+    assert_eq!(sync(), 0);
+    assert_eq!(check(fsync(99)), Err(EBADF));
+    assert_eq!(unlink("sync_test\0"), 0);
+    println!("sync_test passed!");
+    0
+}
@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, ENOENT, ENOTEMPTY};
+use user_lib::{close, mkdir, mount, open, read, rmdir, umount, unlink, write, OpenFlags};
+
+const LEN: usize = 10000;
+
+fn pattern(i: usize) -> u8 {
+    (i * 7 % 251) as u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a file spanning several pages
+    let mut data = [0u8; LEN];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = pattern(i);
+    }
+    let fd = open("/tmp/tmpfs_test\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, &data), LEN as isize);
+    close(fd as usize);
+
+    let mut back = [0u8; LEN];
+    let fd = open("/tmp/tmpfs_test\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, &mut back[..4000]), 4000);
+    assert_eq!(read(fd as usize, &mut back[4000..]), (LEN - 4000) as isize);
+    assert_eq!(read(fd as usize, &mut back), 0);
+    close(fd as usize);
+    assert!(back.iter().enumerate().all(|(i, &byte)| byte == pattern(i)));
+
+    // directories
+    assert_eq!(mkdir("/tmp/dir\0"), 0);
+    let fd = open("/tmp/dir/inner\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(check(rmdir("/tmp/dir\0")), Err(ENOTEMPTY));
+    assert_eq!(unlink("/tmp/dir/inner\0"), 0);
+    assert_eq!(rmdir("/tmp/dir\0"), 0);
+    assert_eq!(unlink("/tmp/tmpfs_test\0"), 0);
+    assert_eq!(check(open("/tmp/tmpfs_test\0", OpenFlags::RDONLY)), Err(ENOENT));
+
+    // a fresh tmpfs starts empty and forgets everything when unmounted
+    assert_eq!(mkdir("/tmp/mnt\0"), 0);
+    assert_eq!(mount("tmpfs\0", "/tmp/mnt\0", "tmpfs\0"), 0);
+    let fd = open("/tmp/mnt/scratch\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(umount("/tmp/mnt\0"), 0);
+    assert_eq!(check(open("/tmp/mnt/scratch\0", OpenFlags::RDONLY)), Err(ENOENT));
+    assert_eq!(rmdir("/tmp/mnt\0"), 0);
+    println!("tmpfs_test passed!");
+    0
+}
@@ -0,0 +1,129 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, EADDRINUSE, EAGAIN, ECONNREFUSED, ENOTSOCK, ENXIO, EPIPE, EPROTOTYPE};
+use user_lib::{
+    accept, bind, close, connect, exit, fork, getsockname, listen, open, read, recv, recvfrom, send, sendto,
+    shutdown, socket, socketpair, unlink, waitpid, write, OpenFlags, SockaddrUn, AF_UNIX, MSG_DONTWAIT,
+    MSG_NOSIGNAL, SHUT_WR, SOCK_DGRAM, SOCK_STREAM,
+};
+
+const SERVER: &str = "/tmp/unix_socket_test.server\0";
+const CLIENT: &str = "/tmp/unix_socket_test.client\0";
+
+fn stream_pair() {
+    let mut sv = [0usize; 2];
+    assert_eq!(socketpair(AF_UNIX, SOCK_STREAM, 0, &mut sv), 0);
+    let mut buf = [0u8; 16];
+    // plain read and write work on sockets too
+    assert_eq!(write(sv[0], b"ping"), 4);
+    assert_eq!(read(sv[1], &mut buf), 4);
+    assert_eq!(&buf[..4], b"ping");
+    assert_eq!(send(sv[1], b"pong", 0), 4);
+    assert_eq!(recv(sv[0], &mut buf, 0), 4);
+    assert_eq!(&buf[..4], b"pong");
+    assert_eq!(check(recv(sv[0], &mut buf, MSG_DONTWAIT)), Err(EAGAIN));
+
+    // shutting down writing gives the peer EOF
+    assert_eq!(shutdown(sv[0], SHUT_WR), 0);
+    assert_eq!(recv(sv[1], &mut buf, 0), 0);
+    // and closing gives it EPIPE
+    close(sv[0]);
+    assert_eq!(check(send(sv[1], b"lost", MSG_NOSIGNAL)), Err(EPIPE));
+    close(sv[1]);
+}
+
+fn stream_server() {
+    let server = socket(AF_UNIX, SOCK_STREAM, 0);
+    assert!(server > 0);
+    let server = server as usize;
+    let addr = SockaddrUn::new(SERVER);
+    assert_eq!(bind(server, &addr), 0);
+    let other = socket(AF_UNIX, SOCK_STREAM, 0) as usize;
+    assert_eq!(check(bind(other, &addr)), Err(EADDRINUSE));
+    close(other);
+    let mut name = SockaddrUn::default();
+    assert_eq!(getsockname(server, &mut name), 0);
+    assert_eq!(name.path(), SERVER.trim_end_matches('\0'));
+    // the path names the socket, it cannot be opened
+    assert_eq!(check(open(SERVER, OpenFlags::RDONLY)), Err(ENXIO));
+    assert_eq!(listen(server, 4), 0);
+
+    let pid = fork();
+    if pid == 0 {
+        let client = socket(AF_UNIX, SOCK_STREAM, 0) as usize;
+        assert_eq!(connect(client, &addr), 0);
+        assert_eq!(send(client, b"hello", 0), 5);
+        let mut buf = [0u8; 16];
+        assert_eq!(recv(client, &mut buf, 0), 5);
+        assert_eq!(&buf[..5], b"world");
+        close(client);
+        exit(0);
+    }
+    let conn = accept(server, None);
+    assert!(conn > 0);
+    let conn = conn as usize;
+    let mut buf = [0u8; 16];
+    assert_eq!(recv(conn, &mut buf, 0), 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(send(conn, b"world", 0), 5);
+    // the client closes after reading the answer
+    assert_eq!(recv(conn, &mut buf, 0), 0);
+    close(conn);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // nobody listens once the server is closed
+    close(server);
+    let client = socket(AF_UNIX, SOCK_STREAM, 0) as usize;
+    assert_eq!(check(connect(client, &addr)), Err(ECONNREFUSED));
+    close(client);
+}
+
+fn datagram() {
+    let server = socket(AF_UNIX, SOCK_DGRAM, 0) as usize;
+    let client = socket(AF_UNIX, SOCK_DGRAM, 0) as usize;
+    let server_addr = SockaddrUn::new(SERVER);
+    assert_eq!(bind(server, &server_addr), 0);
+    assert_eq!(bind(client, &SockaddrUn::new(CLIENT)), 0);
+    let mut buf = [0u8; 16];
+    assert_eq!(check(recv(server, &mut buf, MSG_DONTWAIT)), Err(EAGAIN));
+
+    // each datagram keeps its bounds and tells where it came from
+    assert_eq!(sendto(client, b"first", 0, &server_addr), 5);
+    assert_eq!(sendto(client, b"second", 0, &server_addr), 6);
+    let mut from = SockaddrUn::default();
+    assert_eq!(recvfrom(server, &mut buf, 0, &mut from), 5);
+    assert_eq!(&buf[..5], b"first");
+    assert_eq!(from.path(), CLIENT.trim_end_matches('\0'));
+    // what does not fit is dropped
+    assert_eq!(recv(server, &mut buf[..3], 0), 3);
+    assert_eq!(&buf[..3], b"sec");
+    assert_eq!(sendto(server, b"reply", 0, &from), 5);
+    assert_eq!(recv(client, &mut buf, 0), 5);
+    assert_eq!(&buf[..5], b"reply");
+
+    // a stream cannot talk to a datagram socket
+    let stream = socket(AF_UNIX, SOCK_STREAM, 0) as usize;
+    assert_eq!(check(connect(stream, &server_addr)), Err(EPROTOTYPE));
+    close(stream);
+    close(server);
+    close(client);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(check(listen(0, 1)), Err(ENOTSOCK));
+    stream_pair();
+    stream_server();
+    assert_eq!(unlink(SERVER), 0);
+    datagram();
+    assert_eq!(unlink(SERVER), 0);
+    assert_eq!(unlink(CLIENT), 0);
+    println!("unix_socket_test passed!");
+    0
+}
@@ -21,12 +21,19 @@ use user_lib::{
     OpenFlags,
     close,
     dup,
+    sigaction,
+    SignalAction,
+    SIGINT,
+    SIG_IGN,
 };
 use user_lib::console::getchar;
 
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Rust user shell");
+    // Ctrl-C is meant for the foreground child, not for us
+    let ignore = SignalAction { handler: SIG_IGN, ..Default::default() };
+    sigaction(SIGINT, Some(&ignore), None);
     let mut line: String = String::new();
     print!(">> ");
     loop {
@@ -78,10 +85,12 @@ pub fn main() -> i32 {
                     args_addr.push(0 as *const u8);
                     let pid = fork();
                     if pid == 0 {
+                        // ignored signals survive exec, let the child be interrupted
+                        sigaction(SIGINT, Some(&SignalAction::default()), None);
                         // input redirection
                         if !input.is_empty() {
                             let input_fd = open(input.as_str(), OpenFlags::RDONLY);
-                            if input_fd == -1 {
+                            if input_fd < 0 {
                                 println!("Error when opening file {}", input);
                                 return -4;
                             }
@@ -96,7 +105,7 @@ pub fn main() -> i32 {
                                 output.as_str(),
                                 OpenFlags::CREATE | OpenFlags::WRONLY
                             );
-                            if output_fd == -1 {
+                            if output_fd < 0 {
                                 println!("Error when opening file {}", output);
                                 return -4;
                             }
@@ -106,7 +115,7 @@ pub fn main() -> i32 {
                             close(output_fd);
                         }
                         // child process
-                        if exec(args_copy[0].as_str(), args_addr.as_slice()) == -1 {
+                        if exec(args_copy[0].as_str(), args_addr.as_slice()) < 0 {
                             println!("Error when executing!");
                             return -4;
                         }
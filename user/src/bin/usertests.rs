@@ -5,16 +5,44 @@
 extern crate user_lib;
 
 static TESTS: &[&str] = &[
+    "brk_simple\0",
+    "condvar_test\0",
+    "demand_paging\0",
+    "devfs_test\0",
+    "efault_test\0",
+    "elf_data_test\0",
+    "errno_test\0",
     "exit\0",
     "fantastic_text\0",
+    "fcntl_test\0",
+    "fifo_test\0",
     "forktest\0",
     "forktest2\0",
     "forktest_simple\0",
+    "futex_test\0",
     "hello_world\0",
     "matrix\0",
+    "mmap_simple\0",
+    "mount_test\0",
+    "mpsc_sem\0",
+    "path_walk\0",
+    "phil_din_mutex\0",
+    "pipe2_test\0",
+    "poll_test\0",
+    "priority_simple\0",
+    "proc_test\0",
+    "race_adder_mutex_blocking\0",
+    "seek_test\0",
+    "sig_interrupt\0",
+    "sem_timeout\0",
+    "sig_simple\0",
     "sleep\0",
     "sleep_simple\0",
     "stack_overflow\0",
+    "sync_test\0",
+    "threads\0",
+    "tmpfs_test\0",
+    "unix_socket_test\0",
     "yield\0",
 ];
 
@@ -0,0 +1,97 @@
+//! Locks whose uncontended paths stay in user space, built on `sys_futex`.
+
+use super::syscall::sys_futex;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+const FUTEX_PRIVATE_FLAG: usize = 128;
+
+#[repr(C)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+/// Sleep while `futex` holds `val`, at most `timeout_ms` if given.
+/// Return 0 when woken up, -11 if `futex` did not hold `val` and -110 on timeout.
+pub fn futex_wait(futex: &AtomicU32, val: u32, timeout_ms: Option<usize>) -> isize {
+    let timeout = timeout_ms.map(|ms| TimeSpec { sec: ms / 1000, nsec: ms % 1000 * 1_000_000 });
+    let timeout_ptr = match timeout.as_ref() {
+        Some(timeout) => timeout as *const TimeSpec,
+        None => core::ptr::null(),
+    };
+    sys_futex(futex as *const AtomicU32 as *const u32, FUTEX_WAIT | FUTEX_PRIVATE_FLAG, val, timeout_ptr)
+}
+
+/// Wake up at most `count` tasks sleeping on `futex`, return how many were woken.
+pub fn futex_wake(futex: &AtomicU32, count: u32) -> isize {
+    sys_futex(futex as *const AtomicU32 as *const u32, FUTEX_WAKE | FUTEX_PRIVATE_FLAG, count, core::ptr::null())
+}
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// Locked, and some tasks may be sleeping on the lock.
+const CONTENDED: u32 = 2;
+
+pub struct Mutex {
+    state: AtomicU32,
+}
+
+impl Mutex {
+    pub const fn new() -> Self {
+        Self { state: AtomicU32::new(UNLOCKED) }
+    }
+    pub fn lock(&self) {
+        if self.state.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+            return;
+        }
+        self.lock_contended();
+    }
+    pub fn try_lock(&self) -> bool {
+        self.state.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+    /// Once a task has slept, it cannot tell whether others still sleep,
+    /// so it takes the lock as contended and wakes one up on unlock.
+    fn lock_contended(&self) {
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            futex_wait(&self.state, CONTENDED, None);
+        }
+    }
+    pub fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex_wake(&self.state, 1);
+        }
+    }
+}
+
+pub struct Condvar {
+    /// Bumped by every signal, a waiter sleeps only while it is unchanged.
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self { seq: AtomicU32::new(0) }
+    }
+    /// Release `mutex`, sleep until signaled and take `mutex` again.
+    pub fn wait(&self, mutex: &Mutex) {
+        self.wait_timeout(mutex, None);
+    }
+    /// Like `wait`, but return false if not signaled within `timeout_ms`.
+    pub fn wait_timeout(&self, mutex: &Mutex, timeout_ms: Option<usize>) -> bool {
+        let seq = self.seq.load(Ordering::Relaxed);
+        mutex.unlock();
+        let ret = futex_wait(&self.seq, seq, timeout_ms);
+        mutex.lock_contended();
+        ret != -110
+    }
+    pub fn signal(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, 1);
+    }
+    pub fn broadcast(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, u32::MAX);
+    }
+}
@@ -1,5 +1,6 @@
 #![no_std]
 #![feature(asm)]
+#![feature(global_asm)]
 #![feature(linkage)]
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
@@ -8,21 +9,61 @@
 pub mod console;
 mod syscall;
 mod lang_items;
+pub mod futex;
+pub mod errno;
 
 extern crate alloc;
 #[macro_use]
 extern crate bitflags;
 
 use syscall::*;
-use buddy_system_allocator::LockedHeap;
+use errno::EAGAIN;
+use futex::TimeSpec;
+use buddy_system_allocator::{Heap, LockedHeap};
 use alloc::vec::Vec;
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
+use core::ptr::NonNull;
 
-const USER_HEAP_SIZE: usize = 32768;
+/// The heap grows by at least this much each time it runs out.
+const HEAP_GROW_SIZE: usize = 0x4000;
 
-static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
+static HEAP: LockedHeap = LockedHeap::empty();
+
+/// A buddy heap which moves the program break when it runs out of memory.
+struct BrkHeap;
 
 #[global_allocator]
-static HEAP: LockedHeap = LockedHeap::empty();
+static HEAP_ALLOCATOR: BrkHeap = BrkHeap;
+
+/// Twice the block size is always enough to hold an aligned block.
+fn grow_heap(heap: &mut Heap, layout: &Layout) -> bool {
+    let size = layout.size().max(layout.align()).next_power_of_two() * 2;
+    let size = size.max(HEAP_GROW_SIZE);
+    let old_brk = sys_brk(0) as usize;
+    if sys_brk(old_brk + size) as usize != old_brk + size {
+        return false;
+    }
+    unsafe { heap.add_to_heap(old_brk, old_brk + size); }
+    true
+}
+
+unsafe impl GlobalAlloc for BrkHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = HEAP.lock();
+        loop {
+            if let Ok(ptr) = heap.alloc(layout) {
+                return ptr.as_ptr();
+            }
+            if !grow_heap(&mut heap, &layout) {
+                return core::ptr::null_mut();
+            }
+        }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        HEAP.lock().dealloc(NonNull::new_unchecked(ptr), layout)
+    }
+}
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
@@ -32,10 +73,6 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
 #[no_mangle]
 #[link_section = ".text.entry"]
 pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
-    unsafe {
-        HEAP.lock()
-            .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
-    }
     let mut v: Vec<&'static str> = Vec::new();
     for i in 0..argc {
         let str_start = unsafe {
@@ -59,33 +96,329 @@ fn main(_argc: usize, _argv: &[&str]) -> i32 {
     panic!("Cannot find main!");
 }
 
+const AT_REMOVEDIR: usize = 0x200;
+
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+/// One buffer of `readv` and `writev`.
+#[repr(C)]
+pub struct IoVec {
+    pub base: *mut u8,
+    pub len: usize,
+}
+
+impl IoVec {
+    /// A buffer to write from.
+    pub fn new(buf: &[u8]) -> Self {
+        Self { base: buf.as_ptr() as *mut u8, len: buf.len() }
+    }
+    /// A buffer to read into.
+    pub fn new_mut(buf: &mut [u8]) -> Self {
+        Self { base: buf.as_mut_ptr(), len: buf.len() }
+    }
+}
+
+#[repr(C)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+pub const POLLIN: i16 = 0x001;
+pub const POLLPRI: i16 = 0x002;
+pub const POLLOUT: i16 = 0x004;
+pub const POLLERR: i16 = 0x008;
+pub const POLLHUP: i16 = 0x010;
+pub const POLLNVAL: i16 = 0x020;
+
+/// One fd of `poll`.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+impl PollFd {
+    pub fn new(fd: i32, events: i16) -> Self {
+        Self { fd, events, revents: 0 }
+    }
+}
+
+/// A set of fds for `select`.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct FdSet([u64; 16]);
+
+impl FdSet {
+    pub fn set(&mut self, fd: usize) { self.0[fd / 64] |= 1 << (fd % 64); }
+    pub fn is_set(&self, fd: usize) -> bool { self.0[fd / 64] & 1 << (fd % 64) != 0 }
+}
+
+pub const EPOLL_CTL_ADD: usize = 1;
+pub const EPOLL_CTL_DEL: usize = 2;
+pub const EPOLL_CTL_MOD: usize = 3;
+/// The events of epoll are the same bits as those of `poll`.
+pub const EPOLLIN: u32 = POLLIN as u32;
+pub const EPOLLOUT: u32 = POLLOUT as u32;
+pub const EPOLLERR: u32 = POLLERR as u32;
+pub const EPOLLHUP: u32 = POLLHUP as u32;
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
 bitflags! {
     pub struct OpenFlags: u32 {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
-        const CREATE = 1 << 9;
-        const TRUNC = 1 << 10;
+        const CREATE = 1 << 6;
+        const EXCL = 1 << 7;
+        const TRUNC = 1 << 9;
+        const APPEND = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        const CLOEXEC = 1 << 19;
+    }
+}
+
+pub const F_DUPFD: usize = 0;
+pub const F_GETFD: usize = 1;
+pub const F_SETFD: usize = 2;
+pub const F_GETFL: usize = 3;
+pub const F_SETFL: usize = 4;
+pub const F_DUPFD_CLOEXEC: usize = 1030;
+pub const FD_CLOEXEC: usize = 1;
+
+pub const AF_UNIX: usize = 1;
+pub const SOCK_STREAM: usize = 1;
+pub const SOCK_DGRAM: usize = 2;
+pub const SOCK_NONBLOCK: usize = 0o4000;
+pub const SOCK_CLOEXEC: usize = 0o2000000;
+pub const MSG_DONTWAIT: u32 = 0x40;
+pub const MSG_NOSIGNAL: u32 = 0x4000;
+pub const SHUT_RD: usize = 0;
+pub const SHUT_WR: usize = 1;
+pub const SHUT_RDWR: usize = 2;
+
+/// `struct sockaddr_un`, a socket named by a path.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SockaddrUn {
+    pub sun_family: u16,
+    pub sun_path: [u8; 108],
+}
+
+impl SockaddrUn {
+    /// `path` may end in a zero as the paths of `open` do. It is cut to
+    /// 107 bytes, leaving room for the terminating zero.
+    pub fn new(path: &str) -> Self {
+        let path = path.trim_end_matches('\0');
+        let mut addr = Self::default();
+        let len = path.len().min(107);
+        addr.sun_path[..len].copy_from_slice(&path.as_bytes()[..len]);
+        addr
+    }
+    pub fn path(&self) -> &str {
+        let len = self.sun_path.iter().position(|b| *b == 0).unwrap_or(108);
+        core::str::from_utf8(&self.sun_path[..len]).unwrap_or("")
+    }
+}
+
+impl Default for SockaddrUn {
+    fn default() -> Self {
+        Self { sun_family: AF_UNIX as u16, sun_path: [0; 108] }
+    }
+}
+
+/// File types of `mknod`.
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFSOCK: u32 = 0o140000;
+
+bitflags! {
+    pub struct MmapProt: u32 {
+        const NONE = 0;
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    pub struct MmapFlags: u32 {
+        const SHARED = 1 << 0;
+        const PRIVATE = 1 << 1;
+        const FIXED = 1 << 4;
+        const ANONYMOUS = 1 << 5;
     }
 }
 
 pub fn dup(fd: usize) -> isize { sys_dup(fd) }
+/// Make `new` refer to the file of `old`, `flags` may only hold CLOEXEC.
+pub fn dup3(old: usize, new: usize, flags: OpenFlags) -> isize { sys_dup3(old, new, flags.bits) }
+/// `cmd` is one of the F_* constants.
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> isize { sys_fcntl(fd, cmd, arg) }
 pub fn open(path: &str, flags: OpenFlags) -> isize { sys_open(path, flags.bits) }
 pub fn close(fd: usize) -> isize { sys_close(fd) }
-pub fn pipe(pipe_fd: &mut [usize]) -> isize { sys_pipe(pipe_fd) }
+pub fn mkdir(path: &str) -> isize { sys_mkdir(path) }
+pub fn mknod(path: &str, mode: u32, dev: usize) -> isize { sys_mknod(path, mode, dev) }
+pub fn mkfifo(path: &str) -> isize { sys_mknod(path, S_IFIFO, 0) }
+pub fn unlink(path: &str) -> isize { sys_unlinkat(path, 0) }
+pub fn rmdir(path: &str) -> isize { sys_unlinkat(path, AT_REMOVEDIR) }
+pub fn chdir(path: &str) -> isize { sys_chdir(path) }
+/// Write the working directory into `buf` with a trailing `\0`.
+pub fn getcwd(buf: &mut [u8]) -> isize { sys_getcwd(buf) }
+/// Mount the filesystem `fs_type` on the device `source` at `target`.
+pub fn mount(source: &str, target: &str, fs_type: &str) -> isize { sys_mount(source, target, fs_type) }
+pub fn umount(target: &str) -> isize { sys_umount(target) }
+pub fn pipe(pipe_fd: &mut [usize]) -> isize { pipe2(pipe_fd, OpenFlags::empty()) }
+/// `flags` may hold `NONBLOCK` and `CLOEXEC`.
+pub fn pipe2(pipe_fd: &mut [usize], flags: OpenFlags) -> isize {
+    // the kernel stores two ints
+    let mut fds = [0i32; 2];
+    let ret = sys_pipe2(&mut fds, flags.bits);
+    if ret == 0 {
+        pipe_fd[0] = fds[0] as usize;
+        pipe_fd[1] = fds[1] as usize;
+    }
+    ret
+}
 pub fn read(fd: usize, buf: &mut [u8]) -> isize { sys_read(fd, buf) }
 pub fn write(fd: usize, buf: &[u8]) -> isize { sys_write(fd, buf) }
+/// Move the offset of `fd`, `whence` is one of SEEK_SET, SEEK_CUR and SEEK_END.
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize { sys_lseek(fd, offset, whence) }
+/// Read at `offset` without moving the offset of `fd`.
+pub fn pread(fd: usize, buf: &mut [u8], offset: usize) -> isize { sys_pread64(fd, buf, offset) }
+/// Write at `offset` without moving the offset of `fd`.
+pub fn pwrite(fd: usize, buf: &[u8], offset: usize) -> isize { sys_pwrite64(fd, buf, offset) }
+/// Read into the buffers of `iov` one after another.
+pub fn readv(fd: usize, iov: &[IoVec]) -> isize { sys_readv(fd, iov) }
+pub fn writev(fd: usize, iov: &[IoVec]) -> isize { sys_writev(fd, iov) }
+/// Write back what the kernel caches of all filesystems.
+pub fn sync() -> isize { sys_sync() }
+pub fn fsync(fd: usize) -> isize { sys_fsync(fd) }
+pub fn syncfs(fd: usize) -> isize { sys_syncfs(fd) }
+
+fn timespec_of(timeout_ms: usize) -> TimeSpec {
+    TimeSpec { sec: timeout_ms / 1000, nsec: timeout_ms % 1000 * 1_000_000 }
+}
+
+/// Wait at most `timeout_ms`, forever if negative, until one of `fds` is
+/// ready. Return how many are.
+pub fn poll(fds: &mut [PollFd], timeout_ms: isize) -> isize {
+    let timeout = (timeout_ms >= 0).then(|| timespec_of(timeout_ms as usize));
+    let timeout_ptr = timeout.as_ref().map_or(core::ptr::null(), |timeout| timeout as *const TimeSpec);
+    sys_ppoll(fds, timeout_ptr, core::ptr::null())
+}
+/// Like `poll`, but block `sigmask` instead of the signal mask while waiting.
+pub fn ppoll(fds: &mut [PollFd], timeout_ms: isize, sigmask: SignalFlags) -> isize {
+    let timeout = (timeout_ms >= 0).then(|| timespec_of(timeout_ms as usize));
+    let timeout_ptr = timeout.as_ref().map_or(core::ptr::null(), |timeout| timeout as *const TimeSpec);
+    sys_ppoll(fds, timeout_ptr, &sigmask.bits())
+}
+/// Wait like `poll`, then keep only the ready fds in the sets.
+pub fn select(
+    nfds: usize,
+    readfds: Option<&mut FdSet>,
+    writefds: Option<&mut FdSet>,
+    exceptfds: Option<&mut FdSet>,
+    timeout_ms: Option<usize>,
+) -> isize {
+    let set_ptr = |set: Option<&mut FdSet>| set.map_or(core::ptr::null_mut(), |set| set as *mut FdSet);
+    let timeout = timeout_ms.map(timespec_of);
+    let timeout_ptr = timeout.as_ref().map_or(core::ptr::null(), |timeout| timeout as *const TimeSpec);
+    sys_pselect6(nfds, set_ptr(readfds), set_ptr(writefds), set_ptr(exceptfds), timeout_ptr)
+}
+pub fn epoll_create1(flags: usize) -> isize { sys_epoll_create1(flags) }
+/// `op` is one of EPOLL_CTL_ADD, EPOLL_CTL_DEL and EPOLL_CTL_MOD.
+pub fn epoll_ctl(epfd: usize, op: usize, fd: usize, event: &EpollEvent) -> isize {
+    sys_epoll_ctl(epfd, op, fd, event as *const EpollEvent)
+}
+/// Wait at most `timeout_ms`, forever if negative, then fill `events` with the ready fds.
+pub fn epoll_wait(epfd: usize, events: &mut [EpollEvent], timeout_ms: isize) -> isize {
+    sys_epoll_pwait(epfd, events, timeout_ms)
+}
 pub fn exit(exit_code: i32) -> ! { sys_exit(exit_code); }
 pub fn yield_() -> isize { sys_yield() }
-pub fn get_time() -> isize { sys_get_time() }
+/// Milliseconds since boot.
+pub fn get_time() -> isize {
+    let mut time = TimeVal { sec: 0, usec: 0 };
+    sys_get_time(&mut time);
+    (time.sec * 1000 + time.usec / 1000) as isize
+}
 pub fn getpid() -> isize { sys_getpid() }
+pub fn socket(domain: usize, ty: usize, protocol: usize) -> isize { sys_socket(domain, ty, protocol) }
+pub fn socketpair(domain: usize, ty: usize, protocol: usize, sv: &mut [usize]) -> isize {
+    // the kernel stores two ints
+    let mut fds = [0i32; 2];
+    let ret = sys_socketpair(domain, ty, protocol, &mut fds);
+    if ret == 0 {
+        sv[0] = fds[0] as usize;
+        sv[1] = fds[1] as usize;
+    }
+    ret
+}
+pub fn bind(fd: usize, addr: &SockaddrUn) -> isize { sys_bind(fd, addr) }
+pub fn listen(fd: usize, backlog: usize) -> isize { sys_listen(fd, backlog) }
+pub fn accept(fd: usize, addr: Option<&mut SockaddrUn>) -> isize { accept4(fd, addr, 0) }
+/// `flags` may hold `SOCK_NONBLOCK` and `SOCK_CLOEXEC`.
+pub fn accept4(fd: usize, addr: Option<&mut SockaddrUn>, flags: usize) -> isize {
+    let mut addrlen = size_of::<SockaddrUn>() as u32;
+    let addr = addr.map_or(core::ptr::null_mut(), |addr| addr as *mut _);
+    sys_accept4(fd, addr, &mut addrlen, flags as u32)
+}
+pub fn connect(fd: usize, addr: &SockaddrUn) -> isize { sys_connect(fd, addr) }
+pub fn getsockname(fd: usize, addr: &mut SockaddrUn) -> isize {
+    let mut addrlen = size_of::<SockaddrUn>() as u32;
+    sys_getsockname(fd, addr, &mut addrlen)
+}
+pub fn getpeername(fd: usize, addr: &mut SockaddrUn) -> isize {
+    let mut addrlen = size_of::<SockaddrUn>() as u32;
+    sys_getpeername(fd, addr, &mut addrlen)
+}
+pub fn send(fd: usize, buf: &[u8], flags: u32) -> isize { sys_sendto(fd, buf, flags, core::ptr::null(), 0) }
+pub fn sendto(fd: usize, buf: &[u8], flags: u32, addr: &SockaddrUn) -> isize {
+    sys_sendto(fd, buf, flags, addr, size_of::<SockaddrUn>())
+}
+pub fn recv(fd: usize, buf: &mut [u8], flags: u32) -> isize {
+    sys_recvfrom(fd, buf, flags, core::ptr::null_mut(), core::ptr::null_mut())
+}
+/// The address of the sender goes to `addr`, zeroed if it is unbound.
+pub fn recvfrom(fd: usize, buf: &mut [u8], flags: u32, addr: &mut SockaddrUn) -> isize {
+    *addr = SockaddrUn::default();
+    let mut addrlen = size_of::<SockaddrUn>() as u32;
+    sys_recvfrom(fd, buf, flags, addr, &mut addrlen)
+}
+pub fn shutdown(fd: usize, how: usize) -> isize { sys_shutdown(fd, how) }
 pub fn fork() -> isize { sys_fork() }
+pub fn mmap(start: usize, len: usize, prot: MmapProt, flags: MmapFlags, fd: usize, offset: usize) -> isize {
+    sys_mmap(start, len, prot.bits, flags.bits, fd, offset)
+}
+pub fn munmap(start: usize, len: usize) -> isize { sys_munmap(start, len) }
+/// Set the program break, return the new one or the unchanged one on failure.
+pub fn brk(addr: usize) -> usize { sys_brk(addr) as usize }
+/// Move the program break by `increment`, return the old one or -1 on failure.
+pub fn sbrk(increment: isize) -> isize {
+    let old_brk = sys_brk(0) as usize;
+    let new_brk = (old_brk as isize + increment) as usize;
+    if sys_brk(new_brk) as usize != new_brk {
+        return -1;
+    }
+    old_brk as isize
+}
 pub fn exec(path: &str, args: &[*const u8]) -> isize { sys_exec(path, args) }
 pub fn wait(exit_code: &mut i32) -> isize {
     loop {
         match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => { yield_(); }
-            // -1 or a real pid
+            ret if ret == EAGAIN.ret() => { yield_(); }
+            // -ECHILD or a real pid
             exit_pid => return exit_pid,
         }
     }
@@ -94,8 +427,8 @@ pub fn wait(exit_code: &mut i32) -> isize {
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
     loop {
         match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => { yield_(); }
-            // -1 or a real pid
+            ret if ret == EAGAIN.ret() => { yield_(); }
+            // -ECHILD or a real pid
             exit_pid => return exit_pid,
         }
     }
@@ -109,7 +442,7 @@ pub fn gettid() -> isize { sys_gettid() }
 pub fn waittid(tid: usize) -> isize {
     loop {
         match sys_waittid(tid) {
-            -2 => { yield_(); }
+            ret if ret == EAGAIN.ret() => { yield_(); }
             exit_code => return exit_code,
         }
     }
@@ -128,4 +461,157 @@ pub fn semaphore_up(sem_id: usize) {
 pub fn semaphore_down(sem_id: usize) {
     sys_semaphore_down(sem_id);
 }
+/// Return false instead of blocking if the semaphore is not available.
+pub fn semaphore_try_down(sem_id: usize) -> bool {
+    sys_semaphore_try_down(sem_id) == 0
+}
+/// Return false if the semaphore is still not available after `timeout_ms`.
+pub fn semaphore_down_timeout(sem_id: usize, timeout_ms: usize) -> bool {
+    sys_semaphore_down_timeout(sem_id, timeout_ms) == 0
+}
+pub fn condvar_create() -> isize {
+    sys_condvar_create()
+}
+pub fn condvar_signal(condvar_id: usize) {
+    sys_condvar_signal(condvar_id);
+}
+/// Release the mutex, wait for a signal and take the mutex again.
+pub fn condvar_wait(condvar_id: usize, mutex_id: usize) {
+    sys_condvar_wait(condvar_id, mutex_id);
+}
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGQUIT: i32 = 3;
+pub const SIGILL: i32 = 4;
+pub const SIGTRAP: i32 = 5;
+pub const SIGABRT: i32 = 6;
+pub const SIGBUS: i32 = 7;
+pub const SIGFPE: i32 = 8;
+pub const SIGKILL: i32 = 9;
+pub const SIGUSR1: i32 = 10;
+pub const SIGSEGV: i32 = 11;
+pub const SIGUSR2: i32 = 12;
+pub const SIGPIPE: i32 = 13;
+pub const SIGALRM: i32 = 14;
+pub const SIGTERM: i32 = 15;
+pub const SIGCHLD: i32 = 17;
+pub const SIGCONT: i32 = 18;
+pub const SIGSTOP: i32 = 19;
+pub const SIGTSTP: i32 = 20;
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+bitflags! {
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD = 1 << 17;
+        const SIGCONT = 1 << 18;
+        const SIGSTOP = 1 << 19;
+        const SIGTSTP = 1 << 20;
+        const SIGTTIN = 1 << 21;
+        const SIGTTOU = 1 << 22;
+        const SIGURG = 1 << 23;
+        const SIGXCPU = 1 << 24;
+        const SIGXFSZ = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF = 1 << 27;
+        const SIGWINCH = 1 << 28;
+        const SIGIO = 1 << 29;
+        const SIGPWR = 1 << 30;
+        const SIGSYS = 1 << 31;
+    }
+}
+
+/// `handler` is `SIG_DFL`, `SIG_IGN` or a `fn(signum: i32)`.
+/// `restorer` is filled in by `sigaction`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalAction {
+    pub handler: usize,
+    pub restorer: usize,
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            restorer: 0,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+// Signal handlers return here. It must not touch sp,
+// the kernel finds the saved context right on top of the stack.
+global_asm!("
+    .section .text
+    .globl __sigreturn_trampoline
+__sigreturn_trampoline:
+    li a7, 139
+    ecall
+");
+
+extern "C" {
+    fn __sigreturn_trampoline();
+}
+
+pub fn kill(pid: usize, signum: i32) -> isize { sys_kill(pid, signum) }
+pub fn sigaction(signum: i32, action: Option<&SignalAction>, old_action: Option<&mut SignalAction>) -> isize {
+    let action = action.map(|action| SignalAction {
+        restorer: __sigreturn_trampoline as usize,
+        ..*action
+    });
+    sys_sigaction(
+        signum,
+        action.as_ref().map_or(core::ptr::null(), |action| action as *const _),
+        old_action.map_or(core::ptr::null_mut(), |action| action as *mut _),
+    )
+}
+pub fn sigprocmask(how: usize, set: Option<SignalFlags>, old_set: Option<&mut SignalFlags>) -> isize {
+    let set = set.map(|set| set.bits());
+    let mut old = 0u32;
+    let ret = sys_sigprocmask(
+        how,
+        set.as_ref().map_or(core::ptr::null(), |set| set as *const u32),
+        if old_set.is_some() { &mut old as *mut u32 } else { core::ptr::null_mut() },
+    );
+    if let Some(old_set) = old_set {
+        *old_set = SignalFlags::from_bits_truncate(old);
+    }
+    ret
+}
+pub fn sigreturn() -> isize { sys_sigreturn() }
+
+/// Set the nice value of the calling process, from -20 to 19.
+pub fn setpriority(nice: isize) -> isize { sys_setpriority(0, 0, nice) }
+/// Get the nice value of the calling process. The kernel returns 20 - nice,
+/// so that errors stay negative; they are passed on unchanged.
+pub fn getpriority() -> isize {
+    match sys_getpriority(0, 0) {
+        err if err < 0 => err,
+        prio => 20 - prio,
+    }
+}
 
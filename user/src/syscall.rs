@@ -1,17 +1,64 @@
-const SYSCALL_DUP: usize = 24;
+use super::{EpollEvent, FdSet, IoVec, PollFd, SignalAction, SockaddrUn, TimeVal};
+use super::futex::TimeSpec;
+use core::mem::size_of;
+
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_EPOLL_CREATE1: usize = 20;
+const SYSCALL_EPOLL_CTL: usize = 21;
+const SYSCALL_EPOLL_PWAIT: usize = 22;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP3: usize = 24;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_MKNODAT: usize = 33;
+const SYSCALL_MKDIR: usize = 34;
+const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_UMOUNT: usize = 39;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_CHDIR: usize = 49;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
-const SYSCALL_PIPE: usize = 59;
+const SYSCALL_PIPE2: usize = 59;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_READV: usize = 65;
+const SYSCALL_WRITEV: usize = 66;
+const SYSCALL_PREAD64: usize = 67;
+const SYSCALL_PWRITE64: usize = 68;
+const SYSCALL_PSELECT6: usize = 72;
+const SYSCALL_PPOLL: usize = 73;
+const SYSCALL_SYNC: usize = 81;
+const SYSCALL_FSYNC: usize = 82;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_FUTEX: usize = 98;
 const SYSCALL_SLEEP: usize = 101;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_GETPRIORITY: usize = 141;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_SOCKET: usize = 198;
+const SYSCALL_SOCKETPAIR: usize = 199;
+const SYSCALL_BIND: usize = 200;
+const SYSCALL_LISTEN: usize = 201;
+const SYSCALL_CONNECT: usize = 203;
+const SYSCALL_GETSOCKNAME: usize = 204;
+const SYSCALL_GETPEERNAME: usize = 205;
+const SYSCALL_SENDTO: usize = 206;
+const SYSCALL_RECVFROM: usize = 207;
+const SYSCALL_SHUTDOWN: usize = 210;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_ACCEPT4: usize = 242;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_SYNCFS: usize = 267;
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
 const SYSCALL_WAITTID: usize = 1002;
@@ -21,6 +68,11 @@ const SYSCALL_MUTEX_UNLOCK: usize = 1012;
 const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
 const SYSCALL_SEMAPHORE_UP: usize = 1021;
 const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_SEMAPHORE_TRY_DOWN: usize = 1023;
+const SYSCALL_SEMAPHORE_DOWN_TIMEOUT: usize = 1024;
+const SYSCALL_CONDVAR_CREATE: usize = 1030;
+const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
+const SYSCALL_CONDVAR_WAIT: usize = 1032;
 
 fn syscall(id: usize, args: [usize; 3]) -> isize {
     let mut ret: isize;
@@ -36,20 +88,76 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+fn syscall6(id: usize, args: [usize; 6]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") args[0] => ret,
+            in("x11") args[1],
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
+            in("x17") id
+        );
+    }
+    ret
+}
+
 pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
 
+pub fn sys_dup3(old: usize, new: usize, flags: u32) -> isize {
+    syscall(SYSCALL_DUP3, [old, new, flags as usize])
+}
+
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_FCNTL, [fd, cmd, arg])
+}
+
+/// Relative paths of the `*at` syscalls start from the working directory.
+const AT_FDCWD: isize = -100;
+
 pub fn sys_open(path: &str, flags: u32) -> isize {
-    syscall(SYSCALL_OPEN, [path.as_ptr() as usize, flags as usize, 0])
+    syscall(SYSCALL_OPEN, [AT_FDCWD as usize, path.as_ptr() as usize, flags as usize])
+}
+
+pub fn sys_mknod(path: &str, mode: u32, dev: usize) -> isize {
+    syscall6(SYSCALL_MKNODAT, [AT_FDCWD as usize, path.as_ptr() as usize, mode as usize, dev, 0, 0])
+}
+
+pub fn sys_mkdir(path: &str) -> isize {
+    syscall(SYSCALL_MKDIR, [AT_FDCWD as usize, path.as_ptr() as usize, 0])
+}
+
+pub fn sys_unlinkat(path: &str, flags: usize) -> isize {
+    syscall(SYSCALL_UNLINKAT, [AT_FDCWD as usize, path.as_ptr() as usize, flags])
+}
+
+pub fn sys_chdir(path: &str) -> isize {
+    syscall(SYSCALL_CHDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_getcwd(buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETCWD, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+pub fn sys_mount(source: &str, target: &str, fs_type: &str) -> isize {
+    syscall6(SYSCALL_MOUNT, [source.as_ptr() as usize, target.as_ptr() as usize, fs_type.as_ptr() as usize, 0, 0, 0])
+}
+
+pub fn sys_umount(target: &str) -> isize {
+    syscall(SYSCALL_UMOUNT, [target.as_ptr() as usize, 0, 0])
 }
 
 pub fn sys_close(fd: usize) -> isize {
     syscall(SYSCALL_CLOSE, [fd, 0, 0])
 }
 
-pub fn sys_pipe(pipe: &mut [usize]) -> isize {
-    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
+pub fn sys_pipe2(pipe: &mut [i32; 2], flags: u32) -> isize {
+    syscall(SYSCALL_PIPE2, [pipe.as_mut_ptr() as usize, flags as usize, 0])
 }
 
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
@@ -60,11 +168,73 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+}
+
+pub fn sys_readv(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_READV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_writev(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_WRITEV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_pread64(fd: usize, buffer: &mut [u8], offset: usize) -> isize {
+    syscall6(SYSCALL_PREAD64, [fd, buffer.as_mut_ptr() as usize, buffer.len(), offset, 0, 0])
+}
+
+pub fn sys_pwrite64(fd: usize, buffer: &[u8], offset: usize) -> isize {
+    syscall6(SYSCALL_PWRITE64, [fd, buffer.as_ptr() as usize, buffer.len(), offset, 0, 0])
+}
+
+pub fn sys_pselect6(
+    nfds: usize,
+    readfds: *mut FdSet,
+    writefds: *mut FdSet,
+    exceptfds: *mut FdSet,
+    timeout: *const TimeSpec,
+) -> isize {
+    syscall6(SYSCALL_PSELECT6, [nfds, readfds as usize, writefds as usize, exceptfds as usize, timeout as usize, 0])
+}
+
+pub fn sys_ppoll(fds: &mut [PollFd], timeout: *const TimeSpec, sigmask: *const u32) -> isize {
+    syscall6(SYSCALL_PPOLL, [fds.as_mut_ptr() as usize, fds.len(), timeout as usize, sigmask as usize, 0, 0])
+}
+
+pub fn sys_epoll_create1(flags: usize) -> isize {
+    syscall(SYSCALL_EPOLL_CREATE1, [flags, 0, 0])
+}
+
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: *const EpollEvent) -> isize {
+    syscall6(SYSCALL_EPOLL_CTL, [epfd, op, fd, event as usize, 0, 0])
+}
+
+pub fn sys_epoll_pwait(epfd: usize, events: &mut [EpollEvent], timeout: isize) -> isize {
+    syscall6(SYSCALL_EPOLL_PWAIT, [epfd, events.as_mut_ptr() as usize, events.len(), timeout as usize, 0, 0])
+}
+
+pub fn sys_sync() -> isize {
+    syscall(SYSCALL_SYNC, [0, 0, 0])
+}
+
+pub fn sys_fsync(fd: usize) -> isize {
+    syscall(SYSCALL_FSYNC, [fd, 0, 0])
+}
+
+pub fn sys_syncfs(fd: usize) -> isize {
+    syscall(SYSCALL_SYNCFS, [fd, 0, 0])
+}
+
 pub fn sys_exit(exit_code: i32) -> ! {
     syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0]);
     panic!("sys_exit never returns!");
 }
 
+pub fn sys_futex(uaddr: *const u32, op: usize, val: u32, timeout: *const TimeSpec) -> isize {
+    syscall6(SYSCALL_FUTEX, [uaddr as usize, op, val as usize, timeout as usize, 0, 0])
+}
+
 pub fn sys_sleep(sleep_ms: usize) -> isize {
     syscall(SYSCALL_SLEEP, [sleep_ms, 0, 0])
 }
@@ -73,14 +243,100 @@ pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
 
-pub fn sys_get_time() -> isize {
-    syscall(SYSCALL_GET_TIME, [0, 0, 0])
+pub fn sys_kill(pid: usize, signum: i32) -> isize {
+    syscall(SYSCALL_KILL, [pid, signum as usize, 0])
+}
+
+pub fn sys_sigaction(signum: i32, action: *const SignalAction, old_action: *mut SignalAction) -> isize {
+    syscall(SYSCALL_SIGACTION, [signum as usize, action as usize, old_action as usize])
+}
+
+pub fn sys_sigprocmask(how: usize, set: *const u32, old_set: *mut u32) -> isize {
+    syscall(SYSCALL_SIGPROCMASK, [how, set as usize, old_set as usize])
+}
+
+pub fn sys_sigreturn() -> isize {
+    syscall(SYSCALL_SIGRETURN, [0, 0, 0])
+}
+
+pub fn sys_setpriority(which: usize, who: usize, prio: isize) -> isize {
+    syscall(SYSCALL_SETPRIORITY, [which, who, prio as usize])
+}
+
+pub fn sys_getpriority(which: usize, who: usize) -> isize {
+    syscall(SYSCALL_GETPRIORITY, [which, who, 0])
+}
+
+pub fn sys_get_time(time: &mut TimeVal) -> isize {
+    syscall(SYSCALL_GET_TIME, [time as *mut TimeVal as usize, 0, 0])
 }
 
 pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0, 0, 0])
 }
 
+pub fn sys_socket(domain: usize, ty: usize, protocol: usize) -> isize {
+    syscall(SYSCALL_SOCKET, [domain, ty, protocol])
+}
+
+pub fn sys_socketpair(domain: usize, ty: usize, protocol: usize, sv: &mut [i32; 2]) -> isize {
+    syscall6(SYSCALL_SOCKETPAIR, [domain, ty, protocol, sv.as_mut_ptr() as usize, 0, 0])
+}
+
+pub fn sys_bind(fd: usize, addr: &SockaddrUn) -> isize {
+    syscall(SYSCALL_BIND, [fd, addr as *const _ as usize, size_of::<SockaddrUn>()])
+}
+
+pub fn sys_listen(fd: usize, backlog: usize) -> isize {
+    syscall(SYSCALL_LISTEN, [fd, backlog, 0])
+}
+
+pub fn sys_accept4(fd: usize, addr: *mut SockaddrUn, addrlen: *mut u32, flags: u32) -> isize {
+    syscall6(SYSCALL_ACCEPT4, [fd, addr as usize, addrlen as usize, flags as usize, 0, 0])
+}
+
+pub fn sys_connect(fd: usize, addr: &SockaddrUn) -> isize {
+    syscall(SYSCALL_CONNECT, [fd, addr as *const _ as usize, size_of::<SockaddrUn>()])
+}
+
+pub fn sys_getsockname(fd: usize, addr: &mut SockaddrUn, addrlen: &mut u32) -> isize {
+    syscall(SYSCALL_GETSOCKNAME, [fd, addr as *mut _ as usize, addrlen as *mut _ as usize])
+}
+
+pub fn sys_getpeername(fd: usize, addr: &mut SockaddrUn, addrlen: &mut u32) -> isize {
+    syscall(SYSCALL_GETPEERNAME, [fd, addr as *mut _ as usize, addrlen as *mut _ as usize])
+}
+
+pub fn sys_sendto(fd: usize, buffer: &[u8], flags: u32, addr: *const SockaddrUn, addrlen: usize) -> isize {
+    syscall6(
+        SYSCALL_SENDTO,
+        [fd, buffer.as_ptr() as usize, buffer.len(), flags as usize, addr as usize, addrlen],
+    )
+}
+
+pub fn sys_recvfrom(fd: usize, buffer: &mut [u8], flags: u32, addr: *mut SockaddrUn, addrlen: *mut u32) -> isize {
+    syscall6(
+        SYSCALL_RECVFROM,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len(), flags as usize, addr as usize, addrlen as usize],
+    )
+}
+
+pub fn sys_shutdown(fd: usize, how: usize) -> isize {
+    syscall(SYSCALL_SHUTDOWN, [fd, how, 0])
+}
+
+pub fn sys_brk(addr: usize) -> isize {
+    syscall(SYSCALL_BRK, [addr, 0, 0])
+}
+
+pub fn sys_mmap(start: usize, len: usize, prot: u32, flags: u32, fd: usize, offset: usize) -> isize {
+    syscall6(SYSCALL_MMAP, [start, len, prot as usize, flags as usize, fd, offset])
+}
+
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [start, len, 0])
+}
+
 pub fn sys_fork() -> isize {
     syscall(SYSCALL_FORK, [0, 0, 0])
 }
@@ -128,3 +384,23 @@ pub fn sys_semaphore_up(sem_id: usize) -> isize {
 pub fn sys_semaphore_down(sem_id: usize) -> isize {
     syscall(SYSCALL_SEMAPHORE_DOWN, [sem_id, 0, 0])
 }
+
+pub fn sys_semaphore_try_down(sem_id: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_TRY_DOWN, [sem_id, 0, 0])
+}
+
+pub fn sys_semaphore_down_timeout(sem_id: usize, timeout_ms: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_DOWN_TIMEOUT, [sem_id, timeout_ms, 0])
+}
+
+pub fn sys_condvar_create() -> isize {
+    syscall(SYSCALL_CONDVAR_CREATE, [0, 0, 0])
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    syscall(SYSCALL_CONDVAR_SIGNAL, [condvar_id, 0, 0])
+}
+
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    syscall(SYSCALL_CONDVAR_WAIT, [condvar_id, mutex_id, 0])
+}